@@ -0,0 +1,27 @@
+-- Local event index: raw events per contract, filled by on-demand fetches and the background syncer
+
+CREATE TABLE IF NOT EXISTS indexed_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    contract_address TEXT NOT NULL,
+    block_number INTEGER NOT NULL,
+    event_index INTEGER NOT NULL, -- position among the contract's events in the block
+    transaction_hash TEXT NOT NULL,
+    keys TEXT NOT NULL, -- JSON array of hex felts
+    data TEXT NOT NULL, -- JSON array of hex felts
+    timestamp INTEGER NOT NULL,
+    timestamp_estimated BOOLEAN NOT NULL DEFAULT 0,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(contract_address, block_number, event_index)
+);
+
+-- One cursor per indexed contract: blocks start_block..=synced_to_block are fully indexed
+CREATE TABLE IF NOT EXISTS event_sync_cursors (
+    contract_address TEXT PRIMARY KEY,
+    start_block INTEGER NOT NULL,
+    synced_to_block INTEGER NOT NULL,
+    last_synced_at TIMESTAMP,
+    last_error TEXT,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_indexed_events_contract_block ON indexed_events(contract_address, block_number);
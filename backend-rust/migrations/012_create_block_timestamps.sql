@@ -0,0 +1,8 @@
+-- Block timestamps seen by the backend, used to turn dates into block numbers without a chain-wide search
+
+CREATE TABLE IF NOT EXISTS block_timestamps (
+    block_number INTEGER PRIMARY KEY,
+    timestamp INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_block_timestamps_timestamp ON block_timestamps(timestamp);
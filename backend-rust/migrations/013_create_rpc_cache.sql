@@ -0,0 +1,10 @@
+-- Immutable JSON-RPC results evicted from the in-memory cache
+
+CREATE TABLE IF NOT EXISTS rpc_cache (
+    cache_key TEXT PRIMARY KEY,
+    method TEXT NOT NULL,
+    response TEXT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_rpc_cache_method ON rpc_cache(method);
@@ -0,0 +1,11 @@
+-- Token name, symbol and decimals resolved through contract calls, per chain
+
+CREATE TABLE IF NOT EXISTS token_metadata (
+    chain TEXT NOT NULL,
+    address TEXT NOT NULL, -- normalized: Starknet felts without leading zeros, EVM lowercase
+    name TEXT,
+    symbol TEXT,
+    decimals INTEGER, -- NULL for tokens without decimals, e.g. NFTs
+    resolved_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (chain, address)
+);
@@ -0,0 +1,17 @@
+-- Block timestamps of every chain, so dates resolve to blocks on EVM chains as well as Starknet.
+-- SQLite cannot change a primary key in place; existing rows are Starknet's.
+
+CREATE TABLE IF NOT EXISTS block_timestamps_by_chain (
+    chain TEXT NOT NULL,
+    block_number INTEGER NOT NULL,
+    timestamp INTEGER NOT NULL,
+    PRIMARY KEY (chain, block_number)
+);
+
+INSERT OR IGNORE INTO block_timestamps_by_chain (chain, block_number, timestamp)
+SELECT 'starknet', block_number, timestamp FROM block_timestamps;
+
+DROP TABLE block_timestamps;
+ALTER TABLE block_timestamps_by_chain RENAME TO block_timestamps;
+
+CREATE INDEX IF NOT EXISTS idx_block_timestamps_timestamp ON block_timestamps(chain, timestamp);
@@ -0,0 +1,3 @@
+-- Entries spilled before only finalized results were cached may hold data that has since changed
+
+DELETE FROM rpc_cache;
@@ -0,0 +1,3 @@
+-- Timestamps stored before only finalized blocks were recorded may belong to reorged blocks
+
+DELETE FROM block_timestamps;
@@ -1,5 +1,88 @@
+use serde::Deserialize;
 use std::env;
 
+/// One Starknet JSON-RPC provider. `url` may contain an `{api_key}` placeholder;
+/// otherwise the key (if any) is sent in the `x-api-key` header.
+#[derive(Clone, Debug, Deserialize)]
+pub struct RpcProviderConfig {
+    pub url: String,
+    #[serde(default = "default_provider_weight")]
+    pub weight: u32,
+    #[serde(default = "default_provider_timeout")]
+    pub timeout_secs: u64,
+    #[serde(default)]
+    pub api_key: Option<String>,
+}
+
+fn default_provider_weight() -> u32 {
+    1
+}
+
+fn default_provider_timeout() -> u64 {
+    10
+}
+
+impl RpcProviderConfig {
+    fn public(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            weight: default_provider_weight(),
+            timeout_secs: default_provider_timeout(),
+            api_key: None,
+        }
+    }
+
+    /// Public Starknet mainnet endpoints used when `STARKNET_RPC_PROVIDERS` is not set
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            Self::public("https://rpc.starknet.lava.build"),
+            Self::public("https://starknet-mainnet.g.alchemy.com/v2/demo"),
+            Self::public("https://starknet-mainnet.public.blastapi.io"),
+            Self::public("https://free-rpc.nethermind.io/mainnet-juno"),
+        ]
+    }
+}
+
+/// JSON-RPC endpoints of one EVM chain. `chain` is the id requests select it by, matching
+/// the `payment_chains` ids (`ethereum`, `polygon`, `base`, ...).
+#[derive(Clone, Debug, Deserialize)]
+pub struct EvmChainConfig {
+    pub chain: String,
+    pub name: String,
+    /// Symbol of the coin gas is paid in
+    #[serde(default = "default_native_symbol")]
+    pub native_symbol: String,
+    pub rpc_providers: Vec<RpcProviderConfig>,
+}
+
+fn default_native_symbol() -> String {
+    "ETH".to_string()
+}
+
+impl EvmChainConfig {
+    fn public(chain: &str, name: &str, native_symbol: &str, url: &str) -> Self {
+        Self {
+            chain: chain.to_string(),
+            name: name.to_string(),
+            native_symbol: native_symbol.to_string(),
+            rpc_providers: vec![RpcProviderConfig::public(url)],
+        }
+    }
+
+    /// Public endpoints for every payment chain, used when `EVM_CHAINS` is not set
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            Self::public("ethereum", "Ethereum", "ETH", "https://ethereum-rpc.publicnode.com"),
+            Self::public("polygon", "Polygon", "POL", "https://polygon-bor-rpc.publicnode.com"),
+            Self::public("avalanche", "Avalanche", "AVAX", "https://avalanche-c-chain-rpc.publicnode.com"),
+            Self::public("bnb", "BNB Smart Chain", "BNB", "https://bsc-rpc.publicnode.com"),
+            Self::public("arbitrum", "Arbitrum", "ETH", "https://arbitrum-one-rpc.publicnode.com"),
+            Self::public("optimism", "Optimism", "ETH", "https://optimism-rpc.publicnode.com"),
+            Self::public("base", "Base", "ETH", "https://base-rpc.publicnode.com"),
+        ]
+    }
+}
+
 #[derive(Clone)]
 #[allow(dead_code)]
 pub struct Config {
@@ -13,11 +96,38 @@ pub struct Config {
     pub google_client_secret: String,
     pub rate_limit_max: usize,
     pub rate_limit_window: u64,
+    pub rpc_providers: Vec<RpcProviderConfig>,
+    pub evm_chains: Vec<EvmChainConfig>,
+    pub evm_log_chunk_blocks: u64,
+    pub rpc_failure_threshold: u32,
+    pub rpc_circuit_cooldown_secs: u64,
+    pub analysis_workers: usize,
+    pub block_fetch_retries: u32,
+    pub rpc_batch_size: usize,
+    pub rpc_cache_capacity: usize,
+    pub rpc_cache_spill: bool,
+    pub rpc_cache_spill_entries: usize,
+    pub event_sync_interval_secs: u64,
+    pub event_sync_chunk_blocks: u64,
+    pub event_sync_pass_blocks: u64,
+    /// `off`, `record` or `replay`; see `services::cassette`
+    pub rpc_cassette_mode: String,
+    pub rpc_cassette_dir: String,
+    /// Alchemy endpoint with `{chain}` and `{api_key}` placeholders
+    pub alchemy_base_url: String,
+    /// Pages of asset transfers read per direction before a fetch returns a continuation key
+    pub alchemy_max_pages: usize,
+    pub alchemy_page_size: usize,
+    pub alchemy_timeout_secs: u64,
+    /// Retries of a page after a 429, 5xx or network failure
+    pub alchemy_max_retries: u32,
+    /// Compute units per second this process may spend; requests beyond it queue
+    pub alchemy_cu_per_second: u32,
 }
 
 impl Config {
     pub fn from_env() -> Self {
-        Self {
+        let mut config = Self {
             host: env::var("HOST").unwrap_or_else(|_| "0.0.0.0".to_string()),
             port: env::var("PORT")
                 .unwrap_or_else(|_| "5000".to_string())
@@ -52,6 +162,121 @@ impl Config {
                 .unwrap_or_else(|_| "900".to_string())
                 .parse()
                 .unwrap_or(900),
+            rpc_providers: env::var("STARKNET_RPC_PROVIDERS")
+                .ok()
+                .and_then(|raw| match serde_json::from_str::<Vec<RpcProviderConfig>>(&raw) {
+                    Ok(providers) if !providers.is_empty() => Some(providers),
+                    Ok(_) => None,
+                    Err(e) => {
+                        log::warn!("Invalid STARKNET_RPC_PROVIDERS ({}), using default providers", e);
+                        None
+                    }
+                })
+                .unwrap_or_else(RpcProviderConfig::defaults),
+            evm_chains: env::var("EVM_CHAINS")
+                .ok()
+                .and_then(|raw| match serde_json::from_str::<Vec<EvmChainConfig>>(&raw) {
+                    Ok(chains) => Some(chains),
+                    Err(e) => {
+                        log::warn!("Invalid EVM_CHAINS ({}), using default chains", e);
+                        None
+                    }
+                })
+                .unwrap_or_else(EvmChainConfig::defaults),
+            evm_log_chunk_blocks: env::var("EVM_LOG_CHUNK_BLOCKS")
+                .unwrap_or_else(|_| "2000".to_string())
+                .parse()
+                .unwrap_or(2000),
+            rpc_failure_threshold: env::var("RPC_CIRCUIT_FAILURE_THRESHOLD")
+                .unwrap_or_else(|_| "3".to_string())
+                .parse()
+                .unwrap_or(3),
+            rpc_circuit_cooldown_secs: env::var("RPC_CIRCUIT_COOLDOWN_SECS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .unwrap_or(30),
+            analysis_workers: env::var("ANALYSIS_WORKERS")
+                .unwrap_or_else(|_| "8".to_string())
+                .parse()
+                .unwrap_or(8),
+            block_fetch_retries: env::var("BLOCK_FETCH_RETRIES")
+                .unwrap_or_else(|_| "3".to_string())
+                .parse()
+                .unwrap_or(3),
+            rpc_batch_size: env::var("RPC_BATCH_SIZE")
+                .unwrap_or_else(|_| "20".to_string())
+                .parse()
+                .unwrap_or(20),
+            rpc_cache_capacity: env::var("RPC_CACHE_CAPACITY")
+                .unwrap_or_else(|_| "5000".to_string())
+                .parse()
+                .unwrap_or(5000),
+            rpc_cache_spill: env::var("RPC_CACHE_SPILL")
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(false),
+            rpc_cache_spill_entries: env::var("RPC_CACHE_SPILL_ENTRIES")
+                .unwrap_or_else(|_| "100000".to_string())
+                .parse()
+                .unwrap_or(100_000),
+            event_sync_interval_secs: env::var("EVENT_SYNC_INTERVAL_SECS")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()
+                .unwrap_or(60),
+            event_sync_chunk_blocks: env::var("EVENT_SYNC_CHUNK_BLOCKS")
+                .unwrap_or_else(|_| "5000".to_string())
+                .parse()
+                .unwrap_or(5000),
+            event_sync_pass_blocks: env::var("EVENT_SYNC_PASS_BLOCKS")
+                .unwrap_or_else(|_| "100000".to_string())
+                .parse()
+                .unwrap_or(100_000),
+            rpc_cassette_mode: env::var("RPC_CASSETTE_MODE")
+                .map(|mode| mode.to_lowercase())
+                .ok()
+                .filter(|mode| {
+                    let known = ["off", "record", "replay"].contains(&mode.as_str());
+                    if !known {
+                        log::warn!("Invalid RPC_CASSETTE_MODE '{}', using live endpoints", mode);
+                    }
+                    known
+                })
+                .unwrap_or_else(|| "off".to_string()),
+            rpc_cassette_dir: env::var("RPC_CASSETTE_DIR")
+                .unwrap_or_else(|_| "./cassettes".to_string()),
+            alchemy_base_url: env::var("ALCHEMY_BASE_URL")
+                .unwrap_or_else(|_| "https://{chain}-mainnet.g.alchemy.com/v2/{api_key}".to_string()),
+            alchemy_max_pages: env::var("ALCHEMY_MAX_PAGES")
+                .unwrap_or_else(|_| "10".to_string())
+                .parse()
+                .unwrap_or(10),
+            alchemy_page_size: env::var("ALCHEMY_PAGE_SIZE")
+                .unwrap_or_else(|_| "1000".to_string())
+                .parse()
+                .unwrap_or(1000),
+            alchemy_timeout_secs: env::var("ALCHEMY_TIMEOUT_SECS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .unwrap_or(30),
+            alchemy_max_retries: env::var("ALCHEMY_MAX_RETRIES")
+                .unwrap_or_else(|_| "4".to_string())
+                .parse()
+                .unwrap_or(4),
+            alchemy_cu_per_second: env::var("ALCHEMY_CU_PER_SECOND")
+                .unwrap_or_else(|_| "330".to_string())
+                .parse()
+                .unwrap_or(330),
+        };
+
+        // One base URL for every JSON-RPC client, e.g. a local stub server: Starknet at the
+        // URL itself, each EVM chain under `/<chain>`
+        if let Ok(base_url) = env::var("RPC_BASE_URL") {
+            let base_url = base_url.trim_end_matches('/');
+            config.rpc_providers = vec![RpcProviderConfig::public(base_url)];
+            for chain in &mut config.evm_chains {
+                chain.rpc_providers = vec![RpcProviderConfig::public(&format!("{}/{}", base_url, chain.chain))];
+            }
         }
+
+        config
     }
 }
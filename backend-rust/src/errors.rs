@@ -8,6 +8,9 @@ pub enum AppError {
     Unauthorized(String),
     BadRequest(String),
     NotFound(String),
+    ServiceUnavailable(String),
+    /// A JSON-RPC error the node answered with: code and message
+    Rpc(i32, String),
 }
 
 impl fmt::Display for AppError {
@@ -17,6 +20,8 @@ impl fmt::Display for AppError {
             AppError::Unauthorized(msg) => write!(f, "Unauthorized: {}", msg),
             AppError::BadRequest(msg) => write!(f, "Bad request: {}", msg),
             AppError::NotFound(msg) => write!(f, "Not found: {}", msg),
+            AppError::ServiceUnavailable(msg) => write!(f, "Service unavailable: {}", msg),
+            AppError::Rpc(code, msg) => write!(f, "RPC error {}: {}", code, msg),
         }
     }
 }
@@ -28,6 +33,8 @@ impl ResponseError for AppError {
             AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
             AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
             AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::ServiceUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Rpc(..) => StatusCode::BAD_REQUEST,
         }
     }
 
@@ -6,3 +6,4 @@ pub mod dashboard;
 pub mod query;
 pub mod feedback;
 pub mod dashboard_builder;
+pub mod transaction;
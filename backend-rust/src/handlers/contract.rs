@@ -1,5 +1,10 @@
-use crate::{db::DbPool, models::*, utils::jwt, errors::AppError, services::rpc::RpcService};
-use actix_web::HttpRequest;
+use crate::{db::DbPool, models::*, utils::jwt, errors::AppError, services::{block_index::BlockIndex, chain::{ChainClient, ChainRegistry, STARKNET}, event_index::EventIndex, event_filter::{matches_keys, EventFilter}, rpc::{BlockId, EventData, Finality, RpcService, MAX_EVENT_PAGES}, time_range::TimeRange, token::TokenService, comparison::{self, MAX_COMPARED_BLOCKS, MAX_COMPARED_CONTRACTS, MAX_COMPARED_DAYS}}};
+use crate::utils::felt::{normalize_hex, parse_felt, short_string_to_felt, FIELD_PRIME};
+use actix_web::{web::Bytes, HttpRequest};
+use num_bigint::BigUint;
+use num_traits::Num;
+use futures::stream::{self, Stream, StreamExt};
+use std::convert::Infallible;
 use uuid::Uuid;
 use serde_json::{json, Value};
 
@@ -33,83 +38,684 @@ pub async fn get_query(pool: &DbPool, id: &str) -> Result<ContractQuery, AppErro
         .bind(id).fetch_one(pool).await?)
 }
 
-// RPC-based contract event fetching
-pub async fn get_contract_events(rpc: &RpcService, payload: Value) -> Result<Value, AppError> {
-    let contract_address = payload.get("contractAddress")
-        .and_then(|v| v.as_str())
-        .ok_or(AppError::BadRequest("contractAddress required".to_string()))?;
+// Finality mode of a request, `latest` unless the payload says otherwise
+fn parse_finality(payload: &Value) -> Result<Finality, AppError> {
+    match payload.get("finality").and_then(|v| v.as_str()) {
+        None => Ok(Finality::default()),
+        Some(value) => Finality::parse(value).ok_or_else(|| AppError::BadRequest(format!(
+            "Invalid finality '{}': expected pending, latest or l1_accepted", value
+        ))),
+    }
+}
 
-    let from_date = payload.get("fromDate")
-        .and_then(|v| v.as_str())
-        .and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
-        .map(|dt| dt.timestamp() as u64);
+// Blocks read by an events request that gives no `fromBlock` or `fromDate`
+const DEFAULT_EVENT_BLOCKS: u64 = 10_000;
+
+// Client for the `chain` of a request, Starknet when absent
+fn parse_chain(chains: &ChainRegistry, payload: &Value) -> Result<std::sync::Arc<dyn ChainClient>, AppError> {
+    chains.get(payload.get("chain").and_then(|v| v.as_str()))
+}
+
+// Block range of a request on a chain other than Starknet: `fromBlock`/`toBlock`, or
+// else `fromDate`/`toDate` resolved against that chain's blocks
+async fn resolve_block_bounds(
+    blocks: &BlockIndex,
+    client: &std::sync::Arc<dyn ChainClient>,
+    payload: &Value,
+) -> Result<(Option<u64>, Option<u64>), AppError> {
+    let from_block = payload.get("fromBlock").and_then(|v| v.as_u64());
+    let to_block = payload.get("toBlock").and_then(|v| v.as_u64());
 
-    let to_date = payload.get("toDate")
+    let mut range = TimeRange::from_payload(payload)?;
+    if from_block.is_some() {
+        range.from = None;
+    }
+    if to_block.is_some() {
+        range.to = None;
+    }
+    let (from_date, to_date) = range.resolve(&blocks.on(client.clone())).await?;
+
+    Ok((from_block.or(from_date), to_block.or(to_date)))
+}
+
+// Most contracts one events request may read
+const MAX_EVENT_CONTRACTS: usize = 10;
+
+// Contracts of an events request: `contractAddress`, `contractAddresses`, or both.
+// Repeated addresses are read once.
+fn parse_event_contracts(payload: &Value) -> Result<Vec<String>, AppError> {
+    let mut addresses: Vec<String> = payload.get("contractAddress")
         .and_then(|v| v.as_str())
-        .and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
-        .map(|dt| dt.timestamp() as u64);
+        .map(String::from)
+        .into_iter()
+        .collect();
+    match payload.get("contractAddresses") {
+        None | Some(Value::Null) => {}
+        Some(Value::Array(items)) => {
+            for item in items {
+                let address = item.as_str()
+                    .ok_or(AppError::BadRequest("contractAddresses must be an array of addresses".to_string()))?;
+                addresses.push(address.to_string());
+            }
+        }
+        Some(_) => return Err(AppError::BadRequest("contractAddresses must be an array of addresses".to_string())),
+    }
 
-    let latest = rpc.get_block_number().await?;
-    
-    let from_block = if let Some(ts) = from_date {
-        rpc.find_block_by_timestamp(ts).await?
-    } else {
-        // UNLIMITED MODE: Start from block 0 (genesis) for complete historical analysis
-        0
+    let mut seen = std::collections::HashSet::new();
+    addresses.retain(|address| seen.insert(normalize_hex(address)));
+    if addresses.is_empty() {
+        return Err(AppError::BadRequest("contractAddress required".to_string()));
+    }
+    if addresses.len() > MAX_EVENT_CONTRACTS {
+        return Err(AppError::BadRequest(format!(
+            "At most {} contracts can be queried at once, got {}", MAX_EVENT_CONTRACTS, addresses.len()
+        )));
+    }
+    Ok(addresses)
+}
+
+// Events of several contracts in chain order: by block, transaction and position in the
+// transaction as far as they are known (see `RpcService::locate_events`), then by position
+// in the block.
+fn sort_events(events: &mut [EventData]) {
+    events.sort_by_key(|event| (event.block_number, event.transaction_index, event.receipt_index, event.event_index));
+}
+
+// Block range of an events request. The range never extends past the head allowed by
+// the request's finality mode, and without a start covers the last `DEFAULT_EVENT_BLOCKS`
+// blocks up to its end.
+async fn resolve_event_range(
+    rpc: &RpcService,
+    blocks: &BlockIndex,
+    payload: &Value,
+    finality: Finality,
+) -> Result<(u64, BlockId), AppError> {
+    let (from_block, to_block) = TimeRange::from_payload(payload)?.resolve(blocks).await?;
+    let head = rpc.head_block(finality).await?;
+
+    let to_block = match to_block {
+        Some(block) if block < head.number() => BlockId::Number(block),
+        _ => head,
     };
+    let from_block = from_block.unwrap_or_else(|| to_block.number().saturating_sub(DEFAULT_EVENT_BLOCKS));
+
+    if from_block > to_block.number() {
+        return Err(AppError::BadRequest(format!(
+            "Invalid block range: {} is after {} ({:?} head)", from_block, to_block.number(), finality
+        )));
+    }
+
+    Ok((from_block, to_block))
+}
 
-    let to_block = if let Some(ts) = to_date {
-        rpc.find_block_by_timestamp(ts).await?
+// Events of one Starknet contract, as served for an events request
+struct ContractEvents {
+    events: Vec<EventData>,
+    source: &'static str,
+    complete: bool,
+    /// Which events `event_index` counts: all of the contract's, or the matching ones
+    index_scope: &'static str,
+}
+
+// Events of one Starknet contract matching a key pattern. Served from the local index
+// when it covers the range, filtered there with positions among all of the contract's
+// events. Otherwise the node applies the pattern; an unfiltered fetch has its L1-accepted
+// part indexed, later blocks never are since they can still change.
+async fn starknet_contract_events(
+    rpc: &RpcService,
+    index: &EventIndex,
+    contract_address: &str,
+    from_block: u64,
+    to_block_id: BlockId,
+    keys: &[Vec<String>],
+) -> Result<ContractEvents, AppError> {
+    let to_block = to_block_id.number();
+    let covered = match to_block_id {
+        BlockId::Number(_) => index.query_covered(contract_address, from_block, to_block).await?,
+        BlockId::Pending(_) => None,
+    };
+    if let Some(events) = covered {
+        let events = events.into_iter().filter(|event| matches_keys(keys, &event.keys)).collect();
+        return Ok(ContractEvents { events, source: "index", complete: true, index_scope: "contract" });
+    }
+
+    let fetch = rpc.fetch_events(contract_address, from_block, to_block_id, keys, Some(MAX_EVENT_PAGES)).await?;
+    let indexed = if matches!(to_block_id, BlockId::Pending(_)) {
+        Ok(false)
+    } else if !fetch.complete && from_block > 0 {
+        // Too large for one request: let the background syncer build the index
+        index.track(contract_address, from_block).await.map(|_| false)
+    } else if !fetch.complete || !keys.is_empty() {
+        // A request without a start does not commit the syncer to the whole history, and
+        // filtered events are not the contract's full record
+        Ok(false)
     } else {
-        latest
+        match rpc.try_l1_accepted_head().await {
+            Some(head) if head >= from_block => {
+                let indexed_to = to_block.min(head);
+                let end = fetch.events.partition_point(|event| event.block_number <= indexed_to);
+                index.record(contract_address, from_block, indexed_to, &fetch.events[..end]).await
+            }
+            _ => Ok(false),
+        }
     };
+    if let Err(e) = indexed {
+        log::warn!("Failed to index events for {}: {}", contract_address, e);
+    }
+    let index_scope = if keys.is_empty() { "contract" } else { "matching" };
+    Ok(ContractEvents { events: fetch.events, source: "rpc", complete: fetch.complete, index_scope })
+}
 
-    println!("🚀 UNLIMITED MODE: Fetching events from block {} to {} ({} blocks)", from_block, to_block, to_block - from_block);
+// The value every item shares, or "mixed"
+fn common<'a>(mut values: impl Iterator<Item = &'a str>) -> &'a str {
+    let first = values.next().unwrap_or_default();
+    if values.all(|value| value == first) { first } else { "mixed" }
+}
 
-    let events = rpc.get_events(contract_address, from_block, to_block).await?;
-    
-    println!("✅ Fetched {} events", events.len());
+// RPC-based contract event fetching
+pub async fn get_contract_events(
+    chains: &ChainRegistry,
+    rpc: &RpcService,
+    blocks: &BlockIndex,
+    index: &EventIndex,
+    tokens: &TokenService,
+    payload: Value,
+) -> Result<Value, AppError> {
+    let client = parse_chain(chains, &payload)?;
+    if client.chain() != STARKNET {
+        return get_chain_events(&client, blocks, tokens, &payload).await;
+    }
+
+    let contracts = parse_event_contracts(&payload)?;
+    let keys = EventFilter::from_payload(&payload)?.starknet_keys()?;
+    let finality = parse_finality(&payload)?;
+    let (from_block, to_block_id) = resolve_event_range(rpc, blocks, &payload, finality).await?;
+    let to_block = to_block_id.number();
+
+    log::info!("Fetching events from block {} to {} ({} blocks)", from_block, to_block, to_block - from_block);
+
+    let keys = &keys;
+    let fetched = futures::future::try_join_all(contracts.iter().map(|contract_address| async move {
+        let mut fetched = starknet_contract_events(rpc, index, contract_address, from_block, to_block_id, keys).await?;
+        tokens.annotate_events(STARKNET, contract_address, &mut fetched.events).await;
+        Ok::<_, AppError>(fetched)
+    })).await?;
+
+    let complete = fetched.iter().all(|fetched| fetched.complete);
+    let source = common(fetched.iter().map(|fetched| fetched.source));
+    let index_scope = common(fetched.iter().map(|fetched| fetched.index_scope));
+    let mut events: Vec<EventData> = fetched.into_iter().flat_map(|fetched| fetched.events).collect();
+    if contracts.len() > 1 {
+        rpc.locate_events(&mut events).await?;
+    }
+    sort_events(&mut events);
+
+    log::info!("Fetched {} events from {}", events.len(), source);
 
     Ok(json!({
         "success": true,
         "data": {
+            "contracts": contracts,
             "events": events,
             "fromBlock": from_block,
             "toBlock": to_block,
-            "totalEvents": events.len()
+            "totalEvents": events.len(),
+            "complete": complete,
+            "finality": finality,
+            "source": source,
+            "eventIndexScope": index_scope
         }
     }))
 }
 
-// RPC-based contract analysis
-pub async fn analyze_contract(rpc: &RpcService, payload: Value) -> Result<Value, AppError> {
+// Events of contracts on a chain without a local index, fetched straight from its RPC
+async fn get_chain_events(
+    client: &std::sync::Arc<dyn ChainClient>,
+    blocks: &BlockIndex,
+    tokens: &TokenService,
+    payload: &Value,
+) -> Result<Value, AppError> {
+    let contracts = parse_event_contracts(payload)?;
+    let filter = EventFilter::from_payload(payload)?;
+    let finality = parse_finality(payload)?;
+    let (from_block, to_block) = resolve_block_bounds(blocks, client, payload).await?;
+
+    let head = client.head_block(finality).await?;
+    let to_block = match to_block {
+        Some(block) if block < head.number() => BlockId::Number(block),
+        _ => head,
+    };
+    let from_block = from_block.unwrap_or_else(|| to_block.number().saturating_sub(DEFAULT_EVENT_BLOCKS));
+    if from_block > to_block.number() {
+        return Err(AppError::BadRequest(format!(
+            "Invalid block range: {} is after {} ({:?} head)", from_block, to_block.number(), finality
+        )));
+    }
+
+    let filter = &filter;
+    let fetched = futures::future::try_join_all(contracts.iter().map(|contract_address| async move {
+        let mut fetch = client.get_events(contract_address, from_block, to_block, payload.get("abi"), filter).await?;
+        tokens.annotate_events(client.chain(), contract_address, &mut fetch.events).await;
+        log::info!("Fetched {} {} events for {}", fetch.events.len(), client.chain(), contract_address);
+        Ok::<_, AppError>(fetch)
+    })).await?;
+
+    let complete = fetched.iter().all(|fetch| fetch.complete);
+    let mut events: Vec<EventData> = fetched.into_iter().flat_map(|fetch| fetch.events).collect();
+    sort_events(&mut events);
+
+    Ok(json!({
+        "success": true,
+        "data": {
+            "chain": client.chain(),
+            "contracts": contracts,
+            "totalEvents": events.len(),
+            "events": events,
+            "fromBlock": from_block,
+            "toBlock": to_block.number(),
+            "complete": complete,
+            "finality": finality,
+            "source": "rpc"
+        }
+    }))
+}
+
+/// Wire format of a streamed events response
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamFormat {
+    Ndjson,
+    Sse,
+}
+
+impl StreamFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            StreamFormat::Ndjson => "application/x-ndjson",
+            StreamFormat::Sse => "text/event-stream",
+        }
+    }
+
+    // One message: a JSON line, or an SSE event named after the message type
+    fn frame(self, kind: &str, body: Value) -> Bytes {
+        match self {
+            StreamFormat::Ndjson => {
+                let mut line = json!({ "type": kind });
+                if let (Some(line), Value::Object(fields)) = (line.as_object_mut(), body) {
+                    line.extend(fields);
+                }
+                Bytes::from(format!("{}\n", line))
+            }
+            StreamFormat::Sse => Bytes::from(format!("event: {}\ndata: {}\n\n", kind, body)),
+        }
+    }
+}
+
+// Running totals of a streamed events response
+struct StreamProgress {
+    pages: usize,
+    total_events: usize,
+    last_block: Option<u64>,
+    complete: bool,
+    error: Option<String>,
+}
+
+/// Streams the events of a Starknet contract page by page. Each page is followed by a progress
+/// message, and the stream always ends with a summary saying whether the range was complete.
+/// Range errors are returned before streaming starts; RPC errors mid-way end the stream
+/// with an incomplete summary carrying the error.
+pub async fn stream_contract_events(
+    chains: &ChainRegistry,
+    rpc: &RpcService,
+    blocks: &BlockIndex,
+    payload: Value,
+    format: StreamFormat,
+) -> Result<impl Stream<Item = Result<Bytes, Infallible>> + 'static, AppError> {
+    let client = parse_chain(chains, &payload)?;
+    if client.chain() != STARKNET {
+        return Err(AppError::BadRequest(format!(
+            "Streaming is only supported on {}; fetch {} events from /api/contracts/events", STARKNET, client.chain()
+        )));
+    }
+    if payload.get("contractAddresses").is_some_and(|v| !v.is_null()) {
+        return Err(AppError::BadRequest(
+            "contractAddresses is not supported when streaming; stream each contract separately".to_string()
+        ));
+    }
+    let contract_address = payload.get("contractAddress")
+        .and_then(|v| v.as_str())
+        .ok_or(AppError::BadRequest("contractAddress required".to_string()))?;
+    let keys = EventFilter::from_payload(&payload)?.starknet_keys()?;
+    let index_scope = if keys.is_empty() { "contract" } else { "matching" };
+    let finality = parse_finality(&payload)?;
+    let (from_block, to_block_id) = resolve_event_range(rpc, blocks, &payload, finality).await?;
+    let to_block = to_block_id.number();
+    let max_pages = payload.get("maxPages")
+        .and_then(|v| v.as_u64())
+        .map(|n| n as usize);
+    let total_blocks = to_block - from_block + 1;
+
+    log::info!(
+        "Streaming events for {} from block {} to {} as {:?}",
+        contract_address, from_block, to_block, format
+    );
+
+    let pages = Box::pin(rpc.stream_events(contract_address, from_block, to_block_id, &keys, max_pages));
+    let progress = StreamProgress {
+        pages: 0,
+        total_events: 0,
+        last_block: None,
+        complete: false,
+        error: None,
+    };
+
+    Ok(stream::unfold(Some((pages, progress)), move |state| async move {
+        let (mut pages, mut progress) = state?;
+        let mut frames = Vec::new();
+        let mut finished = false;
+        match pages.next().await {
+            Some(Ok(page)) => {
+                progress.pages = page.page;
+                progress.total_events += page.events.len();
+                if let Some(last) = page.events.last() {
+                    progress.last_block = Some(last.block_number);
+                }
+                if let Some(complete) = page.complete {
+                    progress.complete = complete;
+                    finished = true;
+                }
+
+                // Events arrive in block order, so every block before the last one seen is done
+                let blocks_covered = match (page.complete, progress.last_block) {
+                    (Some(true), _) => total_blocks,
+                    (_, Some(last)) => last - from_block,
+                    (_, None) => 0,
+                };
+
+                frames.extend_from_slice(&format.frame("page", json!({
+                    "page": page.page,
+                    "events": page.events,
+                })));
+                frames.extend_from_slice(&format.frame("progress", json!({
+                    "pages": progress.pages,
+                    "eventsSent": progress.total_events,
+                    "lastBlock": progress.last_block,
+                    "blocksCovered": blocks_covered,
+                    "totalBlocks": total_blocks,
+                })));
+            }
+            Some(Err(e)) => {
+                log::warn!("Event stream stopped after {} pages: {}", progress.pages, e);
+                progress.error = Some(e.to_string());
+                finished = true;
+            }
+            None => finished = true,
+        }
+
+        // The summary goes out in the same chunk as the last page, then the stream ends
+        if finished {
+            frames.extend_from_slice(&format.frame("summary", json!({
+                "complete": progress.complete,
+                "totalEvents": progress.total_events,
+                "pages": progress.pages,
+                "fromBlock": from_block,
+                "toBlock": to_block,
+                "finality": finality,
+                "lastBlock": progress.last_block,
+                "eventIndexScope": index_scope,
+                "error": progress.error,
+            })));
+        }
+
+        Some((Ok(Bytes::from(frames)), (!finished).then_some((pages, progress))))
+    }))
+}
+
+/// Read-only call of a contract function. On Starknet `args` may be positional or keyed by
+/// input name; on EVM chains `function` is a Solidity signature with positional `args`.
+/// The call runs at `blockNumber` when given, otherwise at the head of the finality mode.
+pub async fn call_contract(chains: &ChainRegistry, payload: Value) -> Result<Value, AppError> {
+    let client = parse_chain(chains, &payload)?;
     let contract_address = payload.get("contractAddress")
         .and_then(|v| v.as_str())
         .ok_or(AppError::BadRequest("contractAddress required".to_string()))?;
+    let function = payload.get("function")
+        .and_then(|v| v.as_str())
+        .ok_or(AppError::BadRequest("function required".to_string()))?;
+    let args = payload.get("args").cloned().unwrap_or(Value::Null);
+
+    let block = match payload.get("blockNumber").and_then(|v| v.as_u64()) {
+        Some(number) => BlockId::Number(number),
+        None => client.head_block(parse_finality(&payload)?).await?,
+    };
+
+    let call = client.call(contract_address, function, &args, block).await?;
+
+    Ok(json!({
+        "success": true,
+        "data": call
+    }))
+}
 
-    // Validate contract address format
-    if !contract_address.starts_with("0x") || contract_address.len() != 66 {
-        return Err(AppError::BadRequest("Invalid contract address format".to_string()));
+// One mapping key as felts: a felt (number, hex or short string), `{"u256": value}` for
+// the two-felt u256 encoding, or an array of felts for any other serialized key
+fn parse_storage_key(key: &Value) -> Result<Vec<BigUint>, AppError> {
+    let felt = |value: &Value| -> Result<BigUint, AppError> {
+        let parsed = match value {
+            Value::String(text) => parse_felt(text).or_else(|| short_string_to_felt(text)),
+            Value::Number(n) => n.as_u64().map(BigUint::from),
+            _ => None,
+        };
+        parsed
+            .filter(|felt| felt < &*FIELD_PRIME)
+            .ok_or_else(|| AppError::BadRequest(format!("Invalid storage key {}", value)))
+    };
+
+    match key {
+        Value::Array(felts) => felts.iter().map(felt).collect(),
+        Value::Object(fields) => {
+            let value = fields.get("u256")
+                .ok_or(AppError::BadRequest("Storage key objects must be {\"u256\": value}".to_string()))?;
+            let amount = parse_u256(value)
+                .ok_or_else(|| AppError::BadRequest(format!("Invalid u256 storage key {}", value)))?;
+            let mask = (BigUint::from(1u8) << 128u32) - BigUint::from(1u8);
+            Ok(vec![&amount & &mask, amount >> 128u32])
+        }
+        other => Ok(vec![felt(other)?]),
     }
+}
 
-    let from_date = payload.get("fromDate")
+// An unsigned 256-bit integer as a number, or a hex or decimal string
+fn parse_u256(value: &Value) -> Option<BigUint> {
+    let parsed = match value {
+        Value::String(text) => match text.trim().strip_prefix("0x").or_else(|| text.trim().strip_prefix("0X")) {
+            Some(hex) => BigUint::from_str_radix(hex, 16).ok(),
+            None => BigUint::from_str_radix(text.trim(), 10).ok(),
+        },
+        Value::Number(n) => n.as_u64().map(BigUint::from),
+        _ => None,
+    };
+    parsed.filter(|amount| amount.bits() <= 256)
+}
+
+/// Raw storage read of a contract variable, optionally through mapping keys and at a
+/// historical block (`blockNumber`, otherwise the head of the finality mode)
+pub async fn read_storage(rpc: &RpcService, payload: Value) -> Result<Value, AppError> {
+    let contract_address = payload.get("contractAddress")
+        .and_then(|v| v.as_str())
+        .ok_or(AppError::BadRequest("contractAddress required".to_string()))?;
+    let variable = payload.get("variable")
         .and_then(|v| v.as_str())
-        .and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
-        .map(|dt| dt.timestamp() as u64);
+        .ok_or(AppError::BadRequest("variable required".to_string()))?;
 
-    let to_date = payload.get("toDate")
+    let mut keys = Vec::new();
+    for key in payload.get("keys").and_then(|v| v.as_array()).into_iter().flatten() {
+        keys.extend(parse_storage_key(key)?);
+    }
+    let value_type = payload.get("valueType").and_then(|v| v.as_str());
+    let slots = payload.get("slots").and_then(|v| v.as_u64()).map(|n| n as usize);
+
+    let block = match payload.get("blockNumber").and_then(|v| v.as_u64()) {
+        Some(number) => BlockId::Number(number),
+        None => rpc.head_block(parse_finality(&payload)?).await?,
+    };
+
+    let storage = rpc.read_storage(contract_address, variable, &keys, value_type, slots, block).await?;
+
+    Ok(json!({
+        "success": true,
+        "data": storage
+    }))
+}
+
+// RPC-based contract analysis
+pub async fn analyze_contract(chains: &ChainRegistry, blocks: &BlockIndex, payload: Value) -> Result<Value, AppError> {
+    let client = parse_chain(chains, &payload)?;
+    let contract_address = payload.get("contractAddress")
         .and_then(|v| v.as_str())
-        .and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
-        .map(|dt| dt.timestamp() as u64);
+        .ok_or(AppError::BadRequest("contractAddress required".to_string()))?;
+
+    check_contract_address(client.as_ref(), contract_address)?;
+
+    let finality = parse_finality(&payload)?;
+
+    let (from_block, to_block) = if client.chain() == STARKNET {
+        TimeRange::from_payload(&payload)?.resolve(blocks).await?
+    } else {
+        resolve_block_bounds(blocks, &client, &payload).await?
+    };
 
-    let analysis = rpc.analyze_contract(contract_address, from_date, to_date).await?;
+    let analysis = client.analyze_contract(contract_address, from_block, to_block, finality).await?;
 
     Ok(json!({
         "success": true,
+        "chain": client.chain(),
         "data": analysis
     }))
 }
 
+// Validate contract address format: a felt on Starknet, 20 bytes on EVM chains
+fn check_contract_address(client: &dyn ChainClient, contract_address: &str) -> Result<(), AppError> {
+    let address_len = if client.chain() == STARKNET { 66 } else { 42 };
+    if !contract_address.starts_with("0x") || contract_address.len() != address_len {
+        return Err(AppError::BadRequest(format!("Invalid contract address format: {}", contract_address)));
+    }
+    Ok(())
+}
+
+// Address of a compared contract and the client of its chain
+type ComparedContract = (String, std::sync::Arc<dyn ChainClient>);
+
+// Contracts of a comparison, as addresses or `{"address", "chain"}` objects. The
+// request's `chain` applies to entries that name none.
+fn parse_compared_contracts(chains: &ChainRegistry, payload: &Value) -> Result<Vec<ComparedContract>, AppError> {
+    let entries = payload.get("contracts")
+        .and_then(|v| v.as_array())
+        .filter(|entries| !entries.is_empty())
+        .ok_or(AppError::BadRequest("contracts required".to_string()))?;
+    if entries.len() > MAX_COMPARED_CONTRACTS {
+        return Err(AppError::BadRequest(format!(
+            "At most {} contracts can be compared, got {}", MAX_COMPARED_CONTRACTS, entries.len()
+        )));
+    }
+
+    let default_chain = payload.get("chain").and_then(|v| v.as_str());
+    entries.iter()
+        .map(|entry| {
+            let (address, chain) = match entry {
+                Value::String(address) => (Some(address.as_str()), default_chain),
+                entry => (
+                    entry.get("address").or_else(|| entry.get("contractAddress")).and_then(|v| v.as_str()),
+                    entry.get("chain").and_then(|v| v.as_str()).or(default_chain),
+                ),
+            };
+            let address = address.ok_or(AppError::BadRequest("Every contract needs an address".to_string()))?;
+            let client = chains.get(chain)?;
+            check_contract_address(client.as_ref(), address)?;
+            Ok((address.to_string(), client))
+        })
+        .collect()
+}
+
+/// Activity of several contracts, possibly on different chains, over one time range.
+/// Each contract is scanned over the blocks its own chain produced in that range, at most
+/// `MAX_COMPARED_BLOCKS` of them, and reported on a shared daily axis. A contract whose
+/// range was cut short is reported `truncated` and incomplete. A contract that cannot be
+/// scanned carries its error instead of failing the whole comparison.
+pub async fn compare_contracts(chains: &ChainRegistry, blocks: &BlockIndex, payload: Value) -> Result<Value, AppError> {
+    let contracts = parse_compared_contracts(chains, &payload)?;
+    let finality = parse_finality(&payload)?;
+
+    let range = TimeRange::from_payload(&payload)?;
+    let from = range.from.ok_or(AppError::BadRequest("fromDate required".to_string()))?;
+    let to = range.to.unwrap_or_else(|| chrono::Utc::now().timestamp() as u64);
+    let days = comparison::days_between(from, to);
+    if days.len() > MAX_COMPARED_DAYS {
+        return Err(AppError::BadRequest(format!(
+            "A comparison spans at most {} days, got {}", MAX_COMPARED_DAYS, days.len()
+        )));
+    }
+
+    let days = &days;
+    let columns = futures::future::join_all(contracts.iter().map(|(address, client)| async move {
+        let compared = async {
+            let (from_block, to_block) = range.resolve(&blocks.on(client.clone())).await?;
+            let to_block = match to_block {
+                Some(block) => block,
+                None => client.head_block(finality).await?.number(),
+            };
+            let from_block = from_block.unwrap_or(0);
+            // Every block is fetched with its receipts, so long ranges keep only their newest blocks
+            let scan_from = from_block.max(to_block.saturating_sub(MAX_COMPARED_BLOCKS - 1));
+            let scan = client.scan_contract(address, Some(scan_from), Some(to_block), finality).await?;
+            let events = client.get_events(address, scan.from_block, BlockId::Number(scan.to_block), None, &EventFilter::default()).await?;
+            Ok::<_, AppError>((scan, events, from_block))
+        };
+
+        match compared.await {
+            Ok((scan, events, requested_from)) => {
+                let (totals, daily) = comparison::summarize(&scan, &events.events, days);
+                let truncated = scan.from_block > requested_from;
+                if truncated {
+                    log::warn!(
+                        "Comparison of {} on {} scanned blocks {}-{} of {}-{}",
+                        address, client.chain(), scan.from_block, scan.to_block, requested_from, scan.to_block
+                    );
+                }
+                json!({
+                    "address": address,
+                    "chain": client.chain(),
+                    "fromBlock": scan.from_block,
+                    "toBlock": scan.to_block,
+                    "requestedFromBlock": requested_from,
+                    "truncated": truncated,
+                    "complete": !truncated && events.complete && scan.failed_blocks.is_empty(),
+                    "totals": totals,
+                    "daily": daily,
+                })
+            }
+            Err(e) => {
+                log::warn!("Comparison of {} on {} failed: {}", address, client.chain(), e);
+                json!({
+                    "address": address,
+                    "chain": client.chain(),
+                    "error": e.to_string(),
+                })
+            }
+        }
+    })).await;
+
+    Ok(json!({
+        "success": true,
+        "data": {
+            "fromDate": chrono::DateTime::from_timestamp(from as i64, 0).map(|dt| dt.to_rfc3339()),
+            "toDate": chrono::DateTime::from_timestamp(to as i64, 0).map(|dt| dt.to_rfc3339()),
+            "finality": finality,
+            "days": days,
+            "contracts": columns,
+        }
+    }))
+}
+
 // Save contract query
 pub async fn save_contract_query(pool: &DbPool, req: &HttpRequest, payload: Value) -> Result<Value, AppError> {
     let user_id = jwt::extract_user_id(req)?;
@@ -174,3 +780,126 @@ pub async fn get_saved_queries(pool: &DbPool, req: &HttpRequest) -> Result<Value
         }
     }))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, EvmChainConfig, RpcProviderConfig};
+    use crate::services::evm::EvmClient;
+    use std::sync::Arc;
+
+    // Starknet, answered from the `starknet-events` recordings, next to an EVM chain that
+    // is never reached
+    fn chains(rpc: &RpcService) -> ChainRegistry {
+        let ethereum = EvmChainConfig {
+            chain: "ethereum".to_string(),
+            name: "Ethereum".to_string(),
+            native_symbol: "ETH".to_string(),
+            rpc_providers: vec![RpcProviderConfig {
+                url: "http://replay.invalid".to_string(),
+                weight: 1,
+                timeout_secs: 10,
+                api_key: None,
+            }],
+        };
+        let evm = EvmClient::new(&ethereum, &Config::from_env(), None);
+        ChainRegistry::new(vec![Arc::new(rpc.clone()), Arc::new(evm)])
+    }
+
+    fn block_index(rpc: &RpcService) -> BlockIndex {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new().connect_lazy("sqlite::memory:").expect("valid URL");
+        BlockIndex::new(pool, Arc::new(rpc.clone()))
+    }
+
+    async fn stream(rpc: &RpcService, payload: Value) -> Result<Vec<Value>, AppError> {
+        let body = stream_contract_events(&chains(rpc), rpc, &block_index(rpc), payload, StreamFormat::Ndjson).await?;
+        let chunks: Vec<Bytes> = body.map(|chunk| chunk.unwrap_or_default()).collect().await;
+        let text: String = chunks.iter().map(|chunk| String::from_utf8_lossy(chunk).into_owned()).collect();
+        Ok(text.lines().map(|line| serde_json::from_str(line).expect("one JSON object per line")).collect())
+    }
+
+    // Contract 0xa has four events in blocks 10 and 11; the head is block 12
+    fn storage_key(key: Value) -> Result<Vec<String>, AppError> {
+        Ok(parse_storage_key(&key)?.iter().map(|felt| format!("{:#x}", felt)).collect())
+    }
+
+    #[test]
+    fn splits_u256_storage_keys_into_low_and_high() {
+        assert_eq!(storage_key(json!({"u256": 5})).unwrap(), vec!["0x5", "0x0"]);
+        assert_eq!(
+            storage_key(json!({"u256": "340282366920938463463374607431768211457"})).unwrap(),
+            vec!["0x1", "0x1"]
+        );
+        // Above the field prime, which no single felt could hold
+        let max = format!("0x{}", "f".repeat(64));
+        let half = format!("0x{}", "f".repeat(32));
+        assert_eq!(storage_key(json!({"u256": max})).unwrap(), vec![half.clone(), half]);
+    }
+
+    #[test]
+    fn rejects_u256_storage_keys_out_of_range() {
+        assert!(storage_key(json!({"u256": format!("0x1{}", "0".repeat(64))})).is_err());
+        assert!(storage_key(json!({"u256": "-1"})).is_err());
+        assert!(storage_key(json!({"u256": "USDC"})).is_err());
+        assert!(storage_key(json!({"amount": 1})).is_err());
+    }
+
+    #[test]
+    fn parses_felt_storage_keys() {
+        assert_eq!(storage_key(json!("0x0A")).unwrap(), vec!["0xa"]);
+        assert_eq!(storage_key(json!(10)).unwrap(), vec!["0xa"]);
+        assert_eq!(storage_key(json!("ETH")).unwrap(), vec!["0x455448"]);
+        assert_eq!(storage_key(json!(["0x1", 2])).unwrap(), vec!["0x1", "0x2"]);
+        assert!(storage_key(json!(format!("0x{}", "f".repeat(64)))).is_err());
+    }
+
+    // The `starknet-head` recordings put the head at block 500000
+    #[tokio::test]
+    async fn defaults_event_ranges_to_recent_blocks() {
+        let rpc = RpcService::replayed("starknet-head");
+        let blocks = block_index(&rpc);
+
+        let (from, to) = resolve_event_range(&rpc, &blocks, &json!({}), Finality::Latest).await.unwrap();
+        assert_eq!((from, to), (500_000 - DEFAULT_EVENT_BLOCKS, BlockId::Number(500_000)));
+
+        let (from, to) = resolve_event_range(&rpc, &blocks, &json!({}), Finality::Pending).await.unwrap();
+        assert_eq!((from, to), (500_001 - DEFAULT_EVENT_BLOCKS, BlockId::Pending(500_001)));
+    }
+
+    #[tokio::test]
+    async fn streams_pages_then_a_summary() {
+        let rpc = RpcService::replayed("starknet-events");
+        let frames = stream(&rpc, json!({"contractAddress": "0xa"})).await.expect("streams");
+
+        let kinds: Vec<&str> = frames.iter().filter_map(|frame| frame["type"].as_str()).collect();
+        assert_eq!(kinds, vec!["page", "progress", "summary"]);
+        assert_eq!(frames[0]["events"].as_array().map(Vec::len), Some(4));
+        assert_eq!(frames[1]["blocksCovered"], frames[1]["totalBlocks"]);
+
+        let summary = &frames[2];
+        assert_eq!(summary["complete"], true);
+        assert_eq!(summary["totalEvents"], 4);
+        assert_eq!(summary["toBlock"], 12);
+        assert_eq!(summary["eventIndexScope"], "contract");
+    }
+
+    #[tokio::test]
+    async fn rejects_streams_on_other_chains() {
+        let rpc = RpcService::replayed("starknet-events");
+        let error = stream(&rpc, json!({"contractAddress": "0xa", "chain": "ethereum"})).await.expect_err("rejected");
+        assert!(matches!(&error, AppError::BadRequest(message) if message.contains("only supported on starknet")), "{}", error);
+
+        let unknown = stream(&rpc, json!({"contractAddress": "0xa", "chain": "solana"})).await.expect_err("rejected");
+        assert!(unknown.to_string().contains("not supported"));
+
+        let several = stream(&rpc, json!({"contractAddresses": ["0xa", "0xb"]})).await.expect_err("rejected");
+        assert!(several.to_string().contains("stream each contract separately"));
+    }
+
+    #[test]
+    fn frames_messages_as_ndjson_or_sse() {
+        let body = json!({"page": 1});
+        assert_eq!(StreamFormat::Ndjson.frame("page", body.clone()), Bytes::from("{\"page\":1,\"type\":\"page\"}\n"));
+        assert_eq!(StreamFormat::Sse.frame("page", body), Bytes::from("event: page\ndata: {\"page\":1}\n\n"));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{db::DbPool, utils::jwt, errors::AppError, services::rpc::RpcService};
+use crate::{db::DbPool, utils::{felt::normalize_hex, jwt}, errors::AppError, services::{chain::ChainClient, starknet_receipt::{summarize_fees, ExecutionStatus, FeeUnit}, token::TokenAmount}};
 use actix_web::HttpRequest;
 use serde_json::{json, Value};
 
@@ -27,11 +27,11 @@ pub async fn get_analytics(pool: &DbPool) -> Result<Value, AppError> {
     }))
 }
 
-// New endpoint: Get all blockchain stats from a single block
-pub async fn get_blockchain_stats(rpc: &RpcService) -> Result<Value, AppError> {
+// New endpoint: Get all blockchain stats from a single block of any supported chain
+pub async fn get_blockchain_stats(client: &dyn ChainClient) -> Result<Value, AppError> {
     // Fetch the latest block once
-    let block_number = rpc.get_block_number().await?;
-    let block = rpc.get_block_with_txs(block_number).await?;
+    let block_number = client.block_number().await?;
+    let block = client.get_block(block_number).await?;
     
     // Calculate all stats from this single block
     let total_transactions = block.transactions.len();
@@ -40,30 +40,37 @@ pub async fn get_blockchain_stats(rpc: &RpcService) -> Result<Value, AppError> {
     // Get transaction details for additional metrics
     let mut unique_senders = std::collections::HashSet::new();
     let mut total_fees = 0u128;
+    let mut wei_txs = 0u128;
     let mut successful_txs = 0;
     let mut failed_txs = 0;
     let mut pending_txs = 0;
+    let mut reverted = Vec::new();
     
     for tx in &block.transactions {
-        if let Some(sender) = tx.get("sender_address").and_then(|v| v.as_str()) {
-            unique_senders.insert(sender.to_string());
+        if let Some(sender) = &tx.sender_address {
+            unique_senders.insert(sender.clone());
         }
-        if let Some(fee_str) = tx.get("max_fee").and_then(|v| v.as_str()) {
-            if let Ok(fee) = u128::from_str_radix(fee_str.trim_start_matches("0x"), 16) {
-                total_fees += fee;
-            }
+
+        // Fees and status come from the receipt; a transaction without one is not executed yet
+        let Some(receipt) = block.receipts.get(&normalize_hex(&tx.transaction_hash)) else {
+            pending_txs += 1;
+            continue;
+        };
+
+        if receipt.fee_unit == FeeUnit::Wei {
+            total_fees += receipt.actual_fee;
+            wei_txs += 1;
         }
-        
-        // Check transaction status
-        if let Some(status) = tx.get("execution_status").and_then(|v| v.as_str()) {
-            match status {
-                "SUCCEEDED" => successful_txs += 1,
-                "REVERTED" => failed_txs += 1,
-                _ => pending_txs += 1,
+
+        match receipt.execution_status {
+            ExecutionStatus::Succeeded => successful_txs += 1,
+            ExecutionStatus::Reverted => {
+                failed_txs += 1;
+                reverted.push(json!({
+                    "transaction_hash": receipt.transaction_hash,
+                    "revert_reason": receipt.revert_reason
+                }));
             }
-        } else {
-            // If no status, assume successful
-            successful_txs += 1;
         }
     }
     
@@ -72,9 +79,13 @@ pub async fn get_blockchain_stats(rpc: &RpcService) -> Result<Value, AppError> {
     let gas_used = total_transactions * 21000; // Estimated
     let volume = total_transactions * 50;
     let tvl = gas_used * 150 + 25000000;
+    let total_fees_amount = TokenAmount::fee(total_fees, FeeUnit::Wei, client.native_symbol());
+    let avg_fee_amount = total_fees.checked_div(wei_txs)
+        .map(|avg| TokenAmount::fee(avg, FeeUnit::Wei, client.native_symbol()));
     
     Ok(json!({
         "success": true,
+        "chain": client.chain(),
         "block_number": block_number,
         "timestamp": block_timestamp,
         "stats": {
@@ -84,15 +95,18 @@ pub async fn get_blockchain_stats(rpc: &RpcService) -> Result<Value, AppError> {
             "volume": volume,
             "tvl": tvl,
             "unique_senders": unique_senders.len(),
-            "total_fees": (total_fees as f64 / 1e18).to_string(),
-            "avg_fee": if total_transactions > 0 {
-                ((total_fees / total_transactions as u128) as f64 / 1e18).to_string()
-            } else {
-                "0".to_string()
-            },
+            "total_fees": total_fees_amount.formatted,
+            "avg_fee": avg_fee_amount.as_ref()
+                .map(|avg| avg.formatted.clone())
+                .unwrap_or_else(|| "0".to_string()),
+            "total_fees_amount": total_fees_amount,
+            "avg_fee_amount": avg_fee_amount,
+            // Per unit (WEI and FRI); total_fees and avg_fee above are the WEI-paid part
+            "fees": summarize_fees(block.receipts.values(), client.native_symbol()),
             "successful_txs": successful_txs,
             "failed_txs": failed_txs,
-            "pending_txs": pending_txs
+            "pending_txs": pending_txs,
+            "reverted_transactions": reverted
         },
         "block_info": {
             "block_number": block_number,
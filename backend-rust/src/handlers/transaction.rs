@@ -0,0 +1,19 @@
+use crate::{errors::AppError, services::{chain::STARKNET, rpc::RpcService, token::TokenService}, utils::felt::parse_felt};
+use serde_json::{json, Value};
+
+/// Call trace, decoded events, fee breakdown and L1 messages of one transaction
+pub async fn get_transaction(rpc: &RpcService, tokens: &TokenService, hash: &str) -> Result<Value, AppError> {
+    if parse_felt(hash).is_none() {
+        return Err(AppError::BadRequest("Invalid transaction hash".to_string()));
+    }
+
+    let mut details = rpc.inspect_transaction(hash).await?;
+    for event in &mut details.events {
+        tokens.annotate(STARKNET, &event.from_address, &event.event_name, &mut event.decoded_data).await;
+    }
+
+    Ok(json!({
+        "success": true,
+        "data": details
+    }))
+}
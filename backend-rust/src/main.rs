@@ -13,6 +13,14 @@ use actix_cors::Cors;
 use dotenv::dotenv;
 use std::env;
 use services::alchemy::AlchemyService;
+use services::block_index::BlockIndex;
+use services::cassette::Cassette;
+use services::chain::{ChainClient, ChainRegistry};
+use services::evm::EvmClient;
+use services::event_index::EventIndex;
+use services::rpc::RpcService;
+use services::rpc_cache::RpcCache;
+use services::token::TokenService;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -69,8 +77,50 @@ async fn main() -> std::io::Result<()> {
     // Initialize Alchemy service
     let alchemy_api_key = env::var("ALCHEMY_API_KEY")
         .unwrap_or_else(|_| "GdgtvCyIue4W16Uw7yg8p".to_string());
-    let alchemy_service = AlchemyService::new(alchemy_api_key);
+    let mut alchemy_service = AlchemyService::new(alchemy_api_key)
+        .with_base_url(config.alchemy_base_url.clone())
+        .with_paging(config.alchemy_max_pages, config.alchemy_page_size)
+        .with_retries(std::time::Duration::from_secs(config.alchemy_timeout_secs), config.alchemy_max_retries)
+        .with_compute_budget(config.alchemy_cu_per_second);
+    if let Some(cassette) = Cassette::from_config(&config, "alchemy") {
+        alchemy_service = alchemy_service.with_cassette(cassette);
+        log::info!("📼 RPC cassette mode '{}' ({})", config.rpc_cassette_mode, config.rpc_cassette_dir);
+    }
     log::info!("✅ Alchemy service initialized");
+
+    // Shared across workers so provider health and circuit state are process-wide
+    // One response cache for every chain; SQLite keeps what memory evicts when spill is on
+    let rpc_cache = (config.rpc_cache_capacity > 0).then(|| {
+        RpcCache::new(config.rpc_cache_capacity, config.rpc_cache_spill.then(|| db_pool.clone()))
+            .with_spill_limit(config.rpc_cache_spill_entries)
+    });
+    let mut rpc_service = RpcService::from_config(&config);
+    if let Some(cache) = &rpc_cache {
+        rpc_service = rpc_service.with_response_cache(cache.clone());
+        log::info!("✅ RPC response cache enabled ({} entries, spill: {})", config.rpc_cache_capacity, config.rpc_cache_spill);
+    }
+    log::info!("✅ RPC service initialized with {} providers", config.rpc_providers.len());
+
+    // Starknet plus one client per configured EVM chain, selected per request by `chain`
+    let mut chain_clients: Vec<std::sync::Arc<dyn ChainClient>> = vec![std::sync::Arc::new(rpc_service.clone())];
+    for chain in &config.evm_chains {
+        chain_clients.push(std::sync::Arc::new(EvmClient::new(chain, &config, rpc_cache.as_ref())));
+    }
+    let chain_registry = ChainRegistry::new(chain_clients);
+    log::info!("✅ Chain clients initialized: {}", chain_registry.chains().join(", "));
+
+    // Token transfers come from the chains' own logs while Alchemy is down
+    let alchemy_service = alchemy_service.with_fallback(chain_registry.clone());
+
+    let token_service = TokenService::new(db_pool.clone(), chain_registry.clone());
+
+    // Starknet's; other chains get theirs through `BlockIndex::on`
+    let block_index = BlockIndex::new(db_pool.clone(), std::sync::Arc::new(rpc_service.clone()));
+
+    // The syncer reads whole histories; keeping them would evict everything else from the cache
+    let event_index = EventIndex::new(db_pool.clone(), rpc_service.clone().without_response_cache(), block_index.clone(), config.event_sync_chunk_blocks, config.event_sync_pass_blocks);
+    event_index.clone().spawn_syncer(std::time::Duration::from_secs(config.event_sync_interval_secs));
+    log::info!("✅ Event index syncer started (every {}s)", config.event_sync_interval_secs);
     
     // Warn about ephemeral storage only on cloud platforms (Render, Heroku, etc.)
     if config.database_url.contains("/tmp/") && (env::var("RENDER").is_ok() || env::var("DYNO").is_ok()) {
@@ -113,9 +163,15 @@ async fn main() -> std::io::Result<()> {
             .app_data(web::Data::new(db_pool.clone()))
             .app_data(web::Data::new(config.clone()))
             .app_data(web::Data::new(alchemy_service.clone()))
+            .app_data(web::Data::new(rpc_service.clone()))
+            .app_data(web::Data::new(chain_registry.clone()))
+            .app_data(web::Data::new(token_service.clone()))
+            .app_data(web::Data::new(block_index.clone()))
+            .app_data(web::Data::new(event_index.clone()))
             .wrap(cors)
             .wrap(Logger::default())
             .wrap(middleware::rate_limit::RateLimitMiddleware)
+            .wrap(middleware::metrics::HttpMetrics)
             .service(actix_files::Files::new("/uploads", "./uploads").show_files_listing())
             .configure(routes::configure)
     })
@@ -0,0 +1,65 @@
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    Error,
+};
+use std::future::{ready, Ready, Future};
+use std::pin::Pin;
+use std::time::Instant;
+
+use crate::services::metrics::metrics;
+
+/// Counts requests and records their latency by method, route pattern and status
+pub struct HttpMetrics;
+
+impl<S, B> Transform<S, ServiceRequest> for HttpMetrics
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = HttpMetricsService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(HttpMetricsService { service }))
+    }
+}
+
+pub struct HttpMetricsService<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for HttpMetricsService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let method = req.method().to_string();
+        let started = Instant::now();
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let res = fut.await;
+            // The pattern is known once routing has run. Unmatched paths share one label
+            // so scans of random URLs cannot add series.
+            let (route, status) = match &res {
+                Ok(res) => (res.request().match_pattern(), res.status()),
+                Err(e) => (None, e.as_response_error().status_code()),
+            };
+            let route = route.unwrap_or_else(|| "unmatched".to_string());
+            metrics().observe_http(&method, &route, status.as_u16(), started.elapsed());
+            res
+        })
+    }
+}
@@ -5,9 +5,11 @@ pub mod submission;
 pub mod reward;
 pub mod contract_query;
 pub mod activity_log;
+pub mod indexed_event;
 
 pub use bounty::Bounty;
 pub use bounty_participant::BountyParticipant;
 pub use submission::Submission;
 pub use contract_query::ContractQuery;
+pub use indexed_event::{IndexedEvent, EventSyncCursor};
 pub mod dashboard;
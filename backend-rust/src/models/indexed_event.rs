@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use chrono::{DateTime, Utc};
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct IndexedEvent {
+    pub id: i64,
+    pub contract_address: String,
+    pub block_number: i64,
+    pub event_index: i64,
+    pub transaction_hash: String,
+    pub keys: String,
+    pub data: String,
+    pub timestamp: i64,
+    pub timestamp_estimated: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct EventSyncCursor {
+    pub contract_address: String,
+    pub start_block: i64,
+    pub synced_to_block: i64,
+    pub last_synced_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
@@ -10,6 +10,8 @@ mod health;
 mod feedback;
 mod dashboard_builder;
 mod contract_transactions;
+mod transaction;
+mod metrics;
 
 pub fn configure(cfg: &mut web::ServiceConfig) {
     cfg.service(
@@ -24,5 +26,8 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
             .configure(feedback::configure)
             .configure(dashboard_builder::configure)
             .configure(contract_transactions::configure)
-    );
+            .configure(transaction::configure)
+    )
+    // Outside /api, where Prometheus expects it
+    .configure(metrics::configure);
 }
@@ -1,17 +1,18 @@
 use actix_web::{web, HttpResponse, Responder};
-use crate::{handlers::contract as contract_handler, db::DbPool, services::rpc::RpcService};
+use crate::{handlers::contract::{self as contract_handler, StreamFormat}, db::DbPool, services::{block_index::BlockIndex, chain::ChainRegistry, event_index::EventIndex, rpc::RpcService, token::TokenService}};
 
 pub fn configure(cfg: &mut web::ServiceConfig) {
-    let rpc_service = web::Data::new(RpcService::new());
-    
     cfg.service(
         web::scope("/contracts")
-            .app_data(rpc_service)
             .route("/query", web::post().to(query_contract))
             .route("/queries", web::get().to(list_queries))
             .route("/queries/{id}", web::get().to(get_query))
             .route("/events", web::post().to(get_events))
+            .route("/events/stream", web::post().to(stream_events))
             .route("/analyze", web::post().to(analyze_contract))
+            .route("/compare", web::post().to(compare_contracts))
+            .route("/call", web::post().to(call_contract))
+            .route("/storage", web::post().to(read_storage))
             .route("/save-query", web::post().to(save_query))
             .route("/saved-queries", web::get().to(get_saved_queries))
     );
@@ -52,10 +53,14 @@ async fn get_query(pool: web::Data<DbPool>, id: web::Path<String>) -> impl Respo
 }
 
 async fn get_events(
+    chains: web::Data<ChainRegistry>,
     rpc: web::Data<RpcService>,
+    blocks: web::Data<BlockIndex>,
+    index: web::Data<EventIndex>,
+    tokens: web::Data<TokenService>,
     payload: web::Json<serde_json::Value>,
 ) -> impl Responder {
-    match contract_handler::get_contract_events(&rpc, payload.into_inner()).await {
+    match contract_handler::get_contract_events(&chains, &rpc, &blocks, &index, &tokens, payload.into_inner()).await {
         Ok(events) => HttpResponse::Ok().json(events),
         Err(e) => HttpResponse::BadRequest().json(serde_json::json!({
             "success": false,
@@ -64,11 +69,40 @@ async fn get_events(
     }
 }
 
-async fn analyze_contract(
+// NDJSON by default; SSE when asked for with `"format": "sse"` or an event-stream Accept header
+async fn stream_events(
+    chains: web::Data<ChainRegistry>,
     rpc: web::Data<RpcService>,
+    blocks: web::Data<BlockIndex>,
+    req: actix_web::HttpRequest,
     payload: web::Json<serde_json::Value>,
 ) -> impl Responder {
-    match contract_handler::analyze_contract(&rpc, payload.into_inner()).await {
+    let payload = payload.into_inner();
+    let wants_sse = payload.get("format").and_then(|v| v.as_str()) == Some("sse")
+        || req.headers()
+            .get(actix_web::http::header::ACCEPT)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|accept| accept.contains("text/event-stream"));
+    let format = if wants_sse { StreamFormat::Sse } else { StreamFormat::Ndjson };
+
+    match contract_handler::stream_contract_events(&chains, &rpc, &blocks, payload, format).await {
+        Ok(body) => HttpResponse::Ok()
+            .content_type(format.content_type())
+            .insert_header((actix_web::http::header::CACHE_CONTROL, "no-cache"))
+            .streaming(body),
+        Err(e) => HttpResponse::BadRequest().json(serde_json::json!({
+            "success": false,
+            "message": e.to_string()
+        }))
+    }
+}
+
+async fn analyze_contract(
+    chains: web::Data<ChainRegistry>,
+    blocks: web::Data<BlockIndex>,
+    payload: web::Json<serde_json::Value>,
+) -> impl Responder {
+    match contract_handler::analyze_contract(&chains, &blocks, payload.into_inner()).await {
         Ok(analysis) => HttpResponse::Ok().json(analysis),
         Err(e) => HttpResponse::BadRequest().json(serde_json::json!({
             "success": false,
@@ -77,6 +111,46 @@ async fn analyze_contract(
     }
 }
 
+async fn compare_contracts(
+    chains: web::Data<ChainRegistry>,
+    blocks: web::Data<BlockIndex>,
+    payload: web::Json<serde_json::Value>,
+) -> impl Responder {
+    match contract_handler::compare_contracts(&chains, &blocks, payload.into_inner()).await {
+        Ok(comparison) => HttpResponse::Ok().json(comparison),
+        Err(e) => HttpResponse::BadRequest().json(serde_json::json!({
+            "success": false,
+            "message": e.to_string()
+        }))
+    }
+}
+
+async fn call_contract(
+    chains: web::Data<ChainRegistry>,
+    payload: web::Json<serde_json::Value>,
+) -> impl Responder {
+    match contract_handler::call_contract(&chains, payload.into_inner()).await {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(e) => HttpResponse::BadRequest().json(serde_json::json!({
+            "success": false,
+            "message": e.to_string()
+        }))
+    }
+}
+
+async fn read_storage(
+    rpc: web::Data<RpcService>,
+    payload: web::Json<serde_json::Value>,
+) -> impl Responder {
+    match contract_handler::read_storage(&rpc, payload.into_inner()).await {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(e) => HttpResponse::BadRequest().json(serde_json::json!({
+            "success": false,
+            "message": e.to_string()
+        }))
+    }
+}
+
 async fn save_query(
     pool: web::Data<DbPool>,
     req: actix_web::HttpRequest,
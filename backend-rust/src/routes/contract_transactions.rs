@@ -1,7 +1,15 @@
-use actix_web::{web, HttpResponse};
+use actix_web::{web, HttpResponse, ResponseError};
 use serde::{Deserialize, Serialize};
 
-use crate::services::alchemy::AlchemyService;
+use num_bigint::BigUint;
+use num_traits::Num;
+
+use crate::errors::AppError;
+use crate::services::alchemy::{AlchemyService, TransferPageKey};
+use crate::services::block_index::BlockIndex;
+use crate::services::chain::ChainRegistry;
+use crate::services::time_range::TimeRange;
+use crate::services::token::TokenService;
 
 #[derive(Debug, Deserialize)]
 pub struct FetchTransactionsRequest {
@@ -9,6 +17,16 @@ pub struct FetchTransactionsRequest {
     pub chain: String,
     pub from_block: Option<String>,
     pub to_block: Option<String>,
+    /// ISO date or span such as `last 7d`, used when `from_block` is absent
+    #[serde(alias = "fromDate")]
+    pub from_date: Option<String>,
+    /// Used when `to_block` is absent
+    #[serde(alias = "toDate")]
+    pub to_date: Option<String>,
+    /// `page_key` of a previous response, to continue where it stopped
+    pub page_key: Option<TransferPageKey>,
+    /// Pages per direction for this request, at most `ALCHEMY_MAX_PAGES`
+    pub max_pages: Option<usize>,
 }
 
 #[derive(Debug, Serialize)]
@@ -17,6 +35,14 @@ pub struct FetchTransactionsResponse {
     pub count: usize,
     pub chain: String,
     pub block_range: BlockRange,
+    /// Set when the page budget ran out before the range was read; pass it back to resume
+    pub page_key: Option<TransferPageKey>,
+    pub complete: bool,
+    /// `alchemy`, or `rpc` when Alchemy was unavailable and the contract's logs were read
+    pub source: String,
+    /// What a page read from logs could not include
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -30,6 +56,9 @@ pub struct BlockRange {
 pub async fn fetch_transactions(
     req: web::Json<FetchTransactionsRequest>,
     alchemy: web::Data<AlchemyService>,
+    tokens: web::Data<TokenService>,
+    chains: web::Data<ChainRegistry>,
+    blocks: web::Data<BlockIndex>,
 ) -> HttpResponse {
     log::info!(
         "Fetching transactions for contract {} on chain {}",
@@ -47,16 +76,41 @@ pub async fn fetch_transactions(
     // Get Alchemy chain ID
     let alchemy_chain = AlchemyService::get_alchemy_chain_id(&req.chain);
 
-    // Default block range: last 100,000 blocks
-    let from_block = req.from_block.clone().unwrap_or_else(|| "0x0".to_string());
-    let to_block = req.to_block.clone().unwrap_or_else(|| "latest".to_string());
+    let (from_date, to_date) = match resolve_dates(&req, &alchemy_chain, &chains, &blocks).await {
+        Ok(bounds) => bounds,
+        Err(e) => return e.error_response(),
+    };
+
+    // Without blocks or dates the range runs from genesis to the latest block
+    let from_block = req.from_block.clone()
+        .or_else(|| from_date.map(|n| format!("{:#x}", n)))
+        .unwrap_or_else(|| "0x0".to_string());
+    let to_block = req.to_block.clone()
+        .or_else(|| to_date.map(|n| format!("{:#x}", n)))
+        .unwrap_or_else(|| "latest".to_string());
 
     // Fetch transactions
     match alchemy
-        .fetch_transactions(&req.contract_address, &alchemy_chain, &from_block, &to_block)
+        .fetch_transactions(
+            &req.contract_address,
+            &alchemy_chain,
+            &from_block,
+            &to_block,
+            req.page_key.as_ref(),
+            req.max_pages,
+        )
         .await
     {
-        Ok(transactions) => {
+        Ok(page) => {
+            let mut transactions = page.transactions;
+            // Tokens Alchemy has no decimals for are resolved on-chain
+            for tx in transactions.iter_mut().filter(|tx| tx.amount.is_none()) {
+                let (Some(token), Ok(raw)) = (&tx.token_address, BigUint::from_str_radix(tx.value.trim_start_matches("0x"), 16)) else {
+                    continue;
+                };
+                tx.amount = tokens.amount(&req.chain, token, &raw).await;
+            }
+
             let count = transactions.len();
             log::info!("Successfully fetched {} transactions", count);
 
@@ -68,17 +122,37 @@ pub async fn fetch_transactions(
                     from: from_block,
                     to: to_block,
                 },
+                page_key: page.page_key,
+                complete: page.complete,
+                source: page.source.to_string(),
+                note: page.note.map(String::from),
             })
         }
         Err(e) => {
             log::error!("Failed to fetch transactions: {}", e);
-            HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Failed to fetch transactions: {}", e)
-            }))
+            e.error_response()
         }
     }
 }
 
+// `from_date`/`to_date` as block numbers on the chain, for the ends given no block
+async fn resolve_dates(
+    req: &FetchTransactionsRequest,
+    alchemy_chain: &str,
+    chains: &ChainRegistry,
+    blocks: &BlockIndex,
+) -> Result<(Option<u64>, Option<u64>), AppError> {
+    let from = req.from_date.as_deref().filter(|_| req.from_block.is_none());
+    let to = req.to_date.as_deref().filter(|_| req.to_block.is_none());
+    let range = TimeRange::parse(from, to)?;
+    if range.from.is_none() && range.to.is_none() {
+        return Ok((None, None));
+    }
+
+    let client = chains.get(Some(AlchemyService::registry_chain_id(alchemy_chain)))?;
+    range.resolve(&blocks.on(client)).await
+}
+
 pub fn configure(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/contract")
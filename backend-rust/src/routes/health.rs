@@ -1,5 +1,6 @@
 use actix_web::{web, HttpResponse, Responder};
 use serde_json::json;
+use crate::services::rpc::RpcService;
 
 async fn health_check() -> impl Responder {
     HttpResponse::Ok().json(json!({
@@ -12,6 +13,14 @@ async fn health_check() -> impl Responder {
     }))
 }
 
+async fn rpc_health(rpc: web::Data<RpcService>) -> impl Responder {
+    HttpResponse::Ok().json(json!({
+        "success": true,
+        "providers": rpc.provider_health(),
+        "cache": rpc.cache_stats(),
+    }))
+}
+
 async fn root() -> impl Responder {
     HttpResponse::Ok().json(json!({
         "success": true,
@@ -24,6 +33,10 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
         web::resource("/health")
             .route(web::get().to(health_check))
     )
+    .service(
+        web::resource("/health/rpc")
+            .route(web::get().to(rpc_health))
+    )
     .service(
         web::resource("/")
             .route(web::get().to(root))
@@ -0,0 +1,16 @@
+use actix_web::{web, HttpResponse, Responder};
+use crate::db::DbPool;
+use crate::services::metrics::{metrics, CONTENT_TYPE};
+
+async fn scrape(pool: web::Data<DbPool>) -> impl Responder {
+    HttpResponse::Ok()
+        .content_type(CONTENT_TYPE)
+        .body(metrics().render(&pool))
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/metrics")
+            .route(web::get().to(scrape))
+    );
+}
@@ -0,0 +1,23 @@
+use actix_web::{web, HttpResponse, Responder};
+use crate::{handlers::transaction as transaction_handler, services::{rpc::RpcService, token::TokenService}};
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/transactions")
+            .route("/{hash}", web::get().to(get_transaction))
+    );
+}
+
+async fn get_transaction(
+    rpc: web::Data<RpcService>,
+    tokens: web::Data<TokenService>,
+    hash: web::Path<String>,
+) -> impl Responder {
+    match transaction_handler::get_transaction(&rpc, &tokens, &hash).await {
+        Ok(transaction) => HttpResponse::Ok().json(transaction),
+        Err(e) => HttpResponse::BadRequest().json(serde_json::json!({
+            "success": false,
+            "message": e.to_string()
+        }))
+    }
+}
@@ -1,2 +1,17 @@
 pub mod rpc;
+pub mod starknet_abi;
 pub mod alchemy;
+pub mod event_index;
+pub mod block_index;
+pub mod starknet_receipt;
+pub mod starknet_trace;
+pub mod chain;
+pub mod evm;
+pub mod evm_abi;
+pub mod rpc_cache;
+pub mod metrics;
+pub mod cassette;
+pub mod token;
+pub mod time_range;
+pub mod comparison;
+pub mod event_filter;
@@ -1,5 +1,125 @@
+use num_bigint::BigUint;
+use num_traits::Num;
+use reqwest::header::RETRY_AFTER;
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::errors::AppError;
+use crate::services::cassette::{Cassette, Recorded};
+use crate::services::chain::{ChainClient, ChainRegistry};
+use crate::services::event_filter::EventFilter;
+use crate::services::metrics::metrics;
+use crate::services::rpc::BlockId;
+use crate::services::token::{parse_amount, TokenAmount};
+
+/// Alchemy's largest `maxCount`
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+// Compute units Alchemy bills per alchemy_getAssetTransfers call
+const TRANSFERS_CU: u32 = 150;
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+// Caveat attached to every page read from logs instead of Alchemy
+const LOG_FALLBACK_NOTE: &str = "Alchemy was unavailable, so only Transfer events emitted by this contract were read. \
+Native and internal transfers, and tokens other contracts moved to or from this address, are missing.";
+
+/// Why a request to Alchemy failed
+#[derive(Debug)]
+pub enum AlchemyError {
+    /// Still throttled after every retry, with the wait Alchemy last asked for
+    RateLimited { retry_after: Option<Duration> },
+    /// Unreachable, timed out, a 5xx or a missing recording
+    Unavailable(String),
+    /// The API key was rejected (HTTP 401 or 403); retrying cannot help
+    Rejected(String),
+    /// The request itself was refused, e.g. an invalid block range
+    Api(String),
+}
+
+impl AlchemyError {
+    /// Failures another data source may not share; a refused request would fail anywhere
+    pub fn is_unavailable(&self) -> bool {
+        !matches!(self, AlchemyError::Api(_))
+    }
+
+    /// Failures that may clear up if the same request is sent again
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, AlchemyError::RateLimited { .. } | AlchemyError::Unavailable(_))
+    }
+}
+
+impl fmt::Display for AlchemyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AlchemyError::RateLimited { retry_after: Some(wait) } => {
+                write!(f, "Alchemy rate limit exceeded, retry after {}s", wait.as_secs())
+            }
+            AlchemyError::RateLimited { retry_after: None } => write!(f, "Alchemy rate limit exceeded"),
+            AlchemyError::Unavailable(msg) => write!(f, "Alchemy unavailable: {}", msg),
+            AlchemyError::Rejected(msg) => write!(f, "Alchemy rejected the API key: {}", msg),
+            AlchemyError::Api(msg) => write!(f, "Alchemy API error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for AlchemyError {}
+
+impl From<AlchemyError> for AppError {
+    fn from(err: AlchemyError) -> Self {
+        match err {
+            AlchemyError::Api(_) => AppError::BadRequest(err.to_string()),
+            AlchemyError::RateLimited { .. } | AlchemyError::Unavailable(_) | AlchemyError::Rejected(_) => {
+                AppError::ServiceUnavailable(err.to_string())
+            }
+        }
+    }
+}
+
+/// Alchemy's compute-unit allowance as a bucket refilled continuously up to one second's
+/// worth. Requests wait their turn in arrival order, so a burst is spread out rather
+/// than answered with 429s.
+struct ComputeBudget {
+    per_second: f64,
+    // Units available and when they were last topped up. Held while waiting, which keeps
+    // the queue in order.
+    state: tokio::sync::Mutex<(f64, Instant)>,
+}
+
+impl ComputeBudget {
+    fn new(per_second: u32) -> Self {
+        let per_second = f64::from(per_second.max(1));
+        Self {
+            per_second,
+            state: tokio::sync::Mutex::new((per_second, Instant::now())),
+        }
+    }
+
+    async fn acquire(&self, units: u32) {
+        let units = f64::from(units).min(self.per_second);
+        let mut state = self.state.lock().await;
+        loop {
+            let (available, refilled) = &mut *state;
+            let now = Instant::now();
+            *available = (*available + now.duration_since(*refilled).as_secs_f64() * self.per_second).min(self.per_second);
+            *refilled = now;
+
+            if *available >= units {
+                *available -= units;
+                return;
+            }
+            tokio::time::sleep(Duration::from_secs_f64((units - *available) / self.per_second)).await;
+        }
+    }
+
+    // After a 429 Alchemy's count is ahead of ours; start over from empty
+    async fn drain(&self) {
+        *self.state.lock().await = (0.0, Instant::now());
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AlchemyTransfer {
@@ -11,6 +131,9 @@ pub struct AlchemyTransfer {
     pub value: Option<f64>,
     pub asset: Option<String>,
     pub category: String,
+    /// `<hash>:log:<index>` for token transfers, `<hash>:external` or `<hash>:internal:<n>` otherwise
+    #[serde(rename = "uniqueId")]
+    pub unique_id: Option<String>,
     #[serde(rename = "rawContract")]
     pub raw_contract: RawContract,
     pub metadata: TransferMetadata,
@@ -35,11 +158,49 @@ pub struct Transaction {
     pub block_number: u64,
     pub from: String,
     pub to: String,
+    /// Raw hex amount in the asset's smallest unit
     pub value: String,
+    /// Token contract, `None` for native transfers
+    pub token_address: Option<String>,
+    /// `value` scaled by the asset's decimals, when Alchemy knows them
+    pub amount: Option<TokenAmount>,
+    /// Position of the emitting log in its block, `None` for native and internal transfers
+    pub log_index: Option<u64>,
+    /// Symbol as reported by Alchemy, e.g. `ETH` or `USDC`
+    pub asset: Option<String>,
+    /// `external`, `internal`, `erc20`, `erc721` or `erc1155`
+    pub category: String,
     pub method_name: String,
     pub timestamp: i64,
 }
 
+/// Where each direction of a paged fetch stopped. A direction without a key has been
+/// read to the end.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TransferPageKey {
+    pub outgoing: Option<String>,
+    pub incoming: Option<String>,
+}
+
+impl TransferPageKey {
+    fn is_empty(&self) -> bool {
+        self.outgoing.is_none() && self.incoming.is_none()
+    }
+}
+
+/// Transfers of one fetch, with the key to resume from when the page budget ran out
+#[derive(Debug)]
+pub struct TransactionPage {
+    pub transactions: Vec<Transaction>,
+    pub page_key: Option<TransferPageKey>,
+    /// Whether the whole block range was read
+    pub complete: bool,
+    /// `alchemy`, or `rpc` when the chain's own logs stood in for an unavailable Alchemy
+    pub source: &'static str,
+    /// What the page could not include, when it came from the chain's logs
+    pub note: Option<&'static str>,
+}
+
 #[derive(Debug, Serialize)]
 struct AlchemyRequest {
     jsonrpc: String,
@@ -48,6 +209,18 @@ struct AlchemyRequest {
     id: u32,
 }
 
+// One direction of a contract's transfers, as asked of alchemy_getAssetTransfers
+#[derive(Clone, Copy)]
+struct TransferQuery<'a> {
+    chain: &'a str,
+    /// `outgoing` or `incoming`, for logs and metrics
+    direction: &'a str,
+    from_address: Option<&'a str>,
+    to_address: Option<&'a str>,
+    from_block: &'a str,
+    to_block: &'a str,
+}
+
 #[derive(Debug, Serialize)]
 struct AlchemyParams {
     #[serde(rename = "fromBlock")]
@@ -72,10 +245,10 @@ struct AlchemyParams {
 #[derive(Debug, Deserialize)]
 struct AlchemyResponse {
     result: Option<AlchemyResult>,
-    error: Option<AlchemyError>,
+    error: Option<AlchemyRpcError>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 struct AlchemyResult {
     transfers: Vec<AlchemyTransfer>,
     #[serde(rename = "pageKey")]
@@ -83,7 +256,8 @@ struct AlchemyResult {
 }
 
 #[derive(Debug, Deserialize)]
-struct AlchemyError {
+struct AlchemyRpcError {
+    code: Option<i64>,
     message: String,
 }
 
@@ -91,6 +265,14 @@ struct AlchemyError {
 pub struct AlchemyService {
     api_key: String,
     client: reqwest::Client,
+    base_url: String,
+    cassette: Option<Cassette>,
+    max_pages: usize,
+    page_size: usize,
+    timeout: Duration,
+    max_retries: u32,
+    budget: Arc<ComputeBudget>,
+    fallback: Option<ChainRegistry>,
 }
 
 impl AlchemyService {
@@ -98,10 +280,61 @@ impl AlchemyService {
         Self {
             api_key,
             client: reqwest::Client::new(),
+            base_url: "https://{chain}-mainnet.g.alchemy.com/v2/{api_key}".to_string(),
+            cassette: None,
+            max_pages: 10,
+            page_size: MAX_PAGE_SIZE,
+            timeout: Duration::from_secs(30),
+            max_retries: 4,
+            budget: Arc::new(ComputeBudget::new(330)),
+            fallback: None,
         }
     }
 
-    /// Fetch all transactions for a contract address using Alchemy's indexed API
+    /// Per-request timeout, and retries after a 429, 5xx or network failure
+    pub fn with_retries(mut self, timeout: Duration, max_retries: u32) -> Self {
+        self.timeout = timeout;
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Compute units per second shared by every request of this process
+    pub fn with_compute_budget(mut self, units_per_second: u32) -> Self {
+        self.budget = Arc::new(ComputeBudget::new(units_per_second));
+        self
+    }
+
+    /// Chains whose logs are read for token transfers while Alchemy is unavailable
+    pub fn with_fallback(mut self, chains: ChainRegistry) -> Self {
+        self.fallback = Some(chains);
+        self
+    }
+
+    /// Pages read per direction and fetch at most, and transfers per page (capped at 1000)
+    pub fn with_paging(mut self, max_pages: usize, page_size: usize) -> Self {
+        self.max_pages = max_pages.max(1);
+        self.page_size = page_size.clamp(1, MAX_PAGE_SIZE);
+        self
+    }
+
+    /// Endpoint template with `{chain}` and `{api_key}` placeholders, e.g. a local stub server
+    pub fn with_base_url(mut self, base_url: String) -> Self {
+        self.base_url = base_url;
+        self
+    }
+
+    /// Records page requests to, or replays them from, `cassette`, one directory per chain
+    pub fn with_cassette(mut self, cassette: Cassette) -> Self {
+        self.cassette = Some(cassette);
+        self
+    }
+
+    /// Fetch all transfers to or from a contract address using Alchemy's indexed API.
+    /// Every transfer is kept, so a transaction moving several tokens yields several rows.
+    /// `resume` continues from a previous fetch's `page_key`; `max_pages` lowers the
+    /// configured page budget for this fetch. When Alchemy stays unavailable and a
+    /// fallback is configured, the token transfers the contract emitted are read from
+    /// the chain's logs instead.
     /// INSTANT: Returns results in 100-500ms (like Dune Analytics)
     pub async fn fetch_transactions(
         &self,
@@ -109,7 +342,40 @@ impl AlchemyService {
         chain: &str,
         from_block: &str,
         to_block: &str,
-    ) -> Result<Vec<Transaction>, Box<dyn std::error::Error>> {
+        resume: Option<&TransferPageKey>,
+        max_pages: Option<usize>,
+    ) -> Result<TransactionPage, AppError> {
+        let error = match self.fetch_from_alchemy(contract_address, chain, from_block, to_block, resume, max_pages).await {
+            Ok(page) => return Ok(page),
+            Err(e) if e.is_unavailable() => e,
+            Err(e) => return Err(e.into()),
+        };
+
+        let client = self.fallback.as_ref().and_then(|chains| chains.get(Some(Self::registry_chain_id(chain))).ok());
+        let Some(client) = client else {
+            return Err(error.into());
+        };
+        // Logs cannot continue an Alchemy page key, and reading them from genesis would
+        // take far too long; such requests wait for Alchemy instead
+        if resume.is_some() || Self::block_number(from_block).unwrap_or(0) == 0 {
+            return Err(AppError::ServiceUnavailable(format!(
+                "{}; the log fallback needs a start block and cannot resume a page key", error
+            )));
+        }
+
+        log::warn!("{}; reading {} transfers of {} from logs", error, chain, contract_address);
+        self.fetch_from_logs(client.as_ref(), contract_address, from_block, to_block).await
+    }
+
+    async fn fetch_from_alchemy(
+        &self,
+        contract_address: &str,
+        chain: &str,
+        from_block: &str,
+        to_block: &str,
+        resume: Option<&TransferPageKey>,
+        max_pages: Option<usize>,
+    ) -> Result<TransactionPage, AlchemyError> {
         log::info!(
             "Fetching transactions for {} on {} (blocks {} to {})",
             contract_address,
@@ -118,44 +384,54 @@ impl AlchemyService {
             to_block
         );
 
+        let max_pages = max_pages.map_or(self.max_pages, |pages| pages.clamp(1, self.max_pages));
+        // A resumed fetch only continues the directions that were left unfinished
+        let (outgoing_key, incoming_key) = match resume {
+            Some(key) => (key.outgoing.clone().map(Some), key.incoming.clone().map(Some)),
+            None => (Some(None), Some(None)),
+        };
+        let mut next_key = TransferPageKey::default();
+        let mut all_transfers = Vec::new();
+
         // Fetch outgoing transactions
-        let outgoing = self
-            .fetch_asset_transfers(
-                contract_address,
-                Some(contract_address),
-                None,
+        if let Some(start) = outgoing_key {
+            let query = TransferQuery {
+                chain,
+                direction: "outgoing",
+                from_address: Some(contract_address),
+                to_address: None,
                 from_block,
                 to_block,
-                chain,
-                "outgoing",
-            )
-            .await?;
+            };
+            let (transfers, key) = self.fetch_asset_transfers(&query, start, max_pages).await?;
+            all_transfers.extend(transfers);
+            next_key.outgoing = key;
+        }
 
         // Fetch incoming transactions
-        let incoming = self
-            .fetch_asset_transfers(
-                contract_address,
-                None,
-                Some(contract_address),
+        if let Some(start) = incoming_key {
+            let query = TransferQuery {
+                chain,
+                direction: "incoming",
+                from_address: None,
+                to_address: Some(contract_address),
                 from_block,
                 to_block,
-                chain,
-                "incoming",
-            )
-            .await?;
-
-        // Combine and deduplicate
-        let mut all_transfers = outgoing;
-        all_transfers.extend(incoming);
+            };
+            let (transfers, key) = self.fetch_asset_transfers(&query, start, max_pages).await?;
+            all_transfers.extend(transfers);
+            next_key.incoming = key;
+        }
 
-        let mut unique_hashes = HashSet::new();
+        // A transfer from the contract to itself is listed in both directions
+        let mut seen = HashSet::new();
         let mut transactions = Vec::new();
 
         for transfer in all_transfers {
-            if unique_hashes.contains(&transfer.hash) {
+            let id = transfer.unique_id.clone().unwrap_or_else(|| transfer.hash.clone());
+            if !seen.insert(id) {
                 continue;
             }
-            unique_hashes.insert(transfer.hash.clone());
 
             let block_number = u64::from_str_radix(&transfer.block_num.trim_start_matches("0x"), 16)
                 .unwrap_or(0);
@@ -164,33 +440,113 @@ impl AlchemyService {
                 .map(|dt| dt.timestamp())
                 .unwrap_or(0);
 
+            let value = transfer.raw_contract.value.clone().unwrap_or_else(|| "0x0".to_string());
+            let decimals = transfer.raw_contract.decimal.as_deref()
+                .and_then(|d| u8::from_str_radix(d.trim_start_matches("0x"), 16).ok());
+            let amount = BigUint::from_str_radix(value.trim_start_matches("0x"), 16).ok()
+                .zip(decimals)
+                .map(|(raw, decimals)| TokenAmount::new(&raw, decimals, transfer.asset.as_deref()));
+
             transactions.push(Transaction {
                 hash: transfer.hash.clone(),
                 block_number,
                 from: transfer.from.clone(),
                 to: transfer.to.clone().unwrap_or_default(),
-                value: transfer.raw_contract.value.clone().unwrap_or_else(|| "0x0".to_string()),
+                value,
+                token_address: transfer.raw_contract.address.clone(),
+                amount,
+                log_index: transfer.unique_id.as_deref().and_then(Self::log_index),
+                asset: transfer.asset.clone(),
+                category: transfer.category.clone(),
                 method_name: Self::categorize_transfer(&transfer.category),
                 timestamp,
             });
         }
 
-        log::info!("Found {} transactions", transactions.len());
-        Ok(transactions)
+        log::info!("Found {} transfers", transactions.len());
+        let page_key = (!next_key.is_empty()).then_some(next_key);
+        Ok(TransactionPage {
+            transactions,
+            complete: page_key.is_none(),
+            page_key,
+            source: "alchemy",
+            note: None,
+        })
     }
 
-    /// Fetch asset transfers using Alchemy API
-    async fn fetch_asset_transfers(
+    // Transfer events emitted by the contract itself, for when Alchemy cannot be reached.
+    // Native and internal transfers, and tokens moved in or out by other contracts, are
+    // only in Alchemy's index, so the page is never complete.
+    async fn fetch_from_logs(
         &self,
-        _contract_address: &str,
-        from_address: Option<&str>,
-        to_address: Option<&str>,
+        client: &dyn ChainClient,
+        contract_address: &str,
         from_block: &str,
         to_block: &str,
-        chain: &str,
-        direction: &str,
-    ) -> Result<Vec<AlchemyTransfer>, Box<dyn std::error::Error>> {
-        let alchemy_url = format!("https://{}-mainnet.g.alchemy.com/v2/{}", chain, self.api_key);
+    ) -> Result<TransactionPage, AppError> {
+        let head = client.block_number().await?;
+        let from_block = Self::block_number(from_block).unwrap_or(0);
+        let to_block = Self::block_number(to_block).unwrap_or(head).min(head);
+        let transfers = EventFilter {
+            events: vec!["Transfer".to_string()],
+            keys: vec![],
+        };
+        let fetch = client.get_events(contract_address, from_block, BlockId::Number(to_block), None, &transfers).await?;
+
+        let transactions: Vec<Transaction> = fetch.events
+            .into_iter()
+            .filter(|event| event.event_name == "Transfer")
+            .filter_map(|event| {
+                let field = |name: &str| event.decoded_data.get(name);
+                let (category, raw) = match (field("value"), field("tokenId")) {
+                    (Some(value), _) => ("erc20", parse_amount(value)?),
+                    (None, Some(token_id)) => ("erc721", parse_amount(token_id)?),
+                    _ => return None,
+                };
+                let address = |name: &str| field(name).and_then(|v| v.as_str()).unwrap_or_default().to_string();
+
+                Some(Transaction {
+                    hash: event.transaction_hash.clone(),
+                    block_number: event.block_number,
+                    from: address("from"),
+                    to: address("to"),
+                    value: format!("{:#x}", raw),
+                    token_address: Some(contract_address.to_lowercase()),
+                    amount: None,
+                    log_index: None,
+                    asset: None,
+                    category: category.to_string(),
+                    method_name: Self::categorize_transfer(category),
+                    timestamp: event.timestamp_raw as i64,
+                })
+            })
+            .collect();
+
+        log::info!("Found {} transfers in logs (range complete: {})", transactions.len(), fetch.complete);
+        Ok(TransactionPage {
+            transactions,
+            page_key: None,
+            complete: false,
+            source: "rpc",
+            note: Some(LOG_FALLBACK_NOTE),
+        })
+    }
+
+    // A hex block number; tags such as `latest` give `None`
+    fn block_number(block: &str) -> Option<u64> {
+        u64::from_str_radix(block.strip_prefix("0x")?, 16).ok()
+    }
+
+    /// Fetch asset transfers using Alchemy API, starting at `page_key` and reading up to
+    /// `max_pages` pages. Returns the key of the next page when there is one.
+    async fn fetch_asset_transfers(
+        &self,
+        query: &TransferQuery<'_>,
+        mut page_key: Option<String>,
+        max_pages: usize,
+    ) -> Result<(Vec<AlchemyTransfer>, Option<String>), AlchemyError> {
+        let TransferQuery { chain, direction, from_address, to_address, from_block, to_block } = *query;
+        let alchemy_url = self.base_url.replace("{chain}", chain).replace("{api_key}", &self.api_key);
 
         // Base chain doesn't support 'internal' category
         let categories = if chain == "base" {
@@ -211,9 +567,7 @@ impl AlchemyService {
         };
 
         let mut all_transfers = Vec::new();
-        let mut page_key: Option<String> = None;
         let mut page_count = 0;
-        let max_pages = 10; // Limit to 10,000 transactions (10 pages × 1000)
 
         loop {
             page_count += 1;
@@ -225,7 +579,7 @@ impl AlchemyService {
                 category: categories.clone(),
                 with_metadata: true,
                 exclude_zero_value: false,
-                max_count: "0x3e8".to_string(), // 1000 results
+                max_count: format!("{:#x}", self.page_size),
                 from_address: from_address.map(|s| s.to_string()),
                 to_address: to_address.map(|s| s.to_string()),
                 page_key: page_key.clone(),
@@ -238,38 +592,130 @@ impl AlchemyService {
                 id: 1,
             };
 
-            let response = self
-                .client
-                .post(&alchemy_url)
-                .json(&request)
-                .send()
-                .await?;
+            let page = self.fetch_page(&alchemy_url, chain, &request).await;
+            let transfers = page.as_ref().map_or(0, |result| result.transfers.len());
+            let outcome = match &page {
+                Ok(_) => "success",
+                Err(AlchemyError::RateLimited { .. }) => "rate_limited",
+                Err(_) => "error",
+            };
+            metrics().observe_alchemy_page(chain, direction, outcome, transfers);
+            let result = page?;
+
+            all_transfers.extend(result.transfers);
+            page_key = result.page_key;
 
-            if !response.status().is_success() {
-                return Err(format!("HTTP {}: {}", response.status(), response.text().await?).into());
+            // Stop if no more pages or reached max
+            if page_key.is_none() {
+                break;
             }
+            if page_count >= max_pages {
+                log::info!("Page budget of {} reached for {} transfers, more remain", max_pages, direction);
+                break;
+            }
+        }
+
+        log::info!("Fetched {} {} transfers", all_transfers.len(), direction);
+        Ok((all_transfers, page_key))
+    }
+
+    // `<hash>:log:<index>`, the index in decimal or hex
+    fn log_index(unique_id: &str) -> Option<u64> {
+        let (_, index) = unique_id.rsplit_once(":log:")?;
+        match index.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16).ok(),
+            None => index.parse().ok(),
+        }
+    }
+
+    // One alchemy_getAssetTransfers request, retried with exponential backoff while
+    // Alchemy is throttling or failing. A `Retry-After` it sends replaces the backoff.
+    async fn fetch_page(&self, alchemy_url: &str, chain: &str, request: &AlchemyRequest) -> Result<AlchemyResult, AlchemyError> {
+        let cassette = self.cassette.as_ref().map(|cassette| cassette.scoped(chain));
+        // A missing recording stays missing
+        let max_retries = if cassette.as_ref().is_some_and(Cassette::replaying) { 0 } else { self.max_retries };
 
-            let alchemy_response: AlchemyResponse = response.json().await?;
+        let mut attempt = 0;
+        loop {
+            let (error, retry_after) = match self.send(alchemy_url, cassette.as_ref(), request).await {
+                Ok(result) => return Ok(result),
+                Err(e) if attempt >= max_retries || !e.is_retryable() => return Err(e),
+                Err(e) => {
+                    let retry_after = match &e {
+                        AlchemyError::RateLimited { retry_after } => *retry_after,
+                        _ => None,
+                    };
+                    (e, retry_after)
+                }
+            };
 
-            if let Some(error) = alchemy_response.error {
-                return Err(format!("Alchemy API error: {}", error.message).into());
+            if matches!(error, AlchemyError::RateLimited { .. }) {
+                self.budget.drain().await;
             }
+            let delay = retry_after
+                .unwrap_or_else(|| RETRY_BASE_DELAY * 2u32.pow(attempt))
+                .min(MAX_RETRY_DELAY);
+            attempt += 1;
+            log::warn!(
+                "Alchemy request on {} failed ({}), retry {}/{} in {}ms",
+                chain, error, attempt, max_retries, delay.as_millis()
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
 
-            if let Some(result) = alchemy_response.result {
-                all_transfers.extend(result.transfers);
-                page_key = result.page_key;
+    // A single attempt, live or from the cassette. Live requests are charged to the budget.
+    async fn send(&self, alchemy_url: &str, cassette: Option<&Cassette>, request: &AlchemyRequest) -> Result<AlchemyResult, AlchemyError> {
+        let recorded_request = serde_json::to_value(request).map_err(|e| AlchemyError::Api(e.to_string()))?;
 
-                // Stop if no more pages or reached max
-                if page_key.is_none() || page_count >= max_pages {
-                    break;
+        let (response, retry_after) = match cassette {
+            Some(cassette) if cassette.replaying() => {
+                let response = cassette.replay(&recorded_request)
+                    .await
+                    .ok_or_else(|| AlchemyError::Unavailable("No recorded Alchemy response".to_string()))?;
+                (response, None)
+            }
+            _ => {
+                self.budget.acquire(TRANSFERS_CU).await;
+                let response = self
+                    .client
+                    .post(alchemy_url)
+                    .json(request)
+                    .timeout(self.timeout)
+                    .send()
+                    .await
+                    .map_err(|e| AlchemyError::Unavailable(e.to_string()))?;
+                let retry_after = response.headers()
+                    .get(RETRY_AFTER)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(parse_retry_after);
+                let response = Recorded {
+                    status: response.status().as_u16(),
+                    body: response.text().await.map_err(|e| AlchemyError::Unavailable(e.to_string()))?,
+                };
+                if let Some(cassette) = cassette {
+                    cassette.record(&recorded_request, &response).await;
                 }
-            } else {
-                break;
+                (response, retry_after)
             }
+        };
+
+        match response.status {
+            200..=299 => {}
+            429 => return Err(AlchemyError::RateLimited { retry_after }),
+            400 => return Err(AlchemyError::Api(response.body)),
+            401 | 403 => return Err(AlchemyError::Rejected(format!("HTTP {}: {}", response.status, response.body))),
+            status => return Err(AlchemyError::Unavailable(format!("HTTP {}: {}", status, response.body))),
         }
 
-        log::info!("Fetched {} {} transfers", all_transfers.len(), direction);
-        Ok(all_transfers)
+        let response: AlchemyResponse = serde_json::from_str(&response.body)
+            .map_err(|e| AlchemyError::Unavailable(format!("Invalid response: {}", e)))?;
+        match response.error {
+            // Throughput limits also arrive as JSON-RPC errors with code 429
+            Some(error) if error.code == Some(429) => Err(AlchemyError::RateLimited { retry_after }),
+            Some(error) => Err(AlchemyError::Api(error.message)),
+            None => Ok(response.result.unwrap_or_default()),
+        }
     }
 
     /// Categorize transfer type
@@ -292,6 +738,16 @@ impl AlchemyService {
         )
     }
 
+    /// Id of the same chain in the `ChainRegistry`
+    pub fn registry_chain_id(alchemy_chain: &str) -> &str {
+        match alchemy_chain {
+            "eth" => "ethereum",
+            "arb" => "arbitrum",
+            "opt" => "optimism",
+            other => other,
+        }
+    }
+
     /// Get Alchemy chain identifier
     pub fn get_alchemy_chain_id(chain: &str) -> String {
         match chain.to_lowercase().as_str() {
@@ -304,3 +760,153 @@ impl AlchemyService {
         }
     }
 }
+
+// `Retry-After` as seconds or an HTTP date
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    if let Ok(secs) = value.trim().parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    (at.with_timezone(&chrono::Utc) - chrono::Utc::now()).to_std().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::cassette::CassetteMode;
+
+    // Contract 0xabc on `eth`: two outgoing pages, one incoming page, and a transfer to
+    // itself listed in both directions
+    const CONTRACT: &str = "0xabc";
+
+    fn replayed() -> AlchemyService {
+        let dir = std::path::Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/cassettes/alchemy"));
+        AlchemyService::new("test".to_string())
+            .with_base_url("http://replay.invalid/{chain}/{api_key}".to_string())
+            .with_cassette(Cassette::new(CassetteMode::Replay, dir.to_path_buf()))
+    }
+
+    fn hashes(page: &TransactionPage) -> Vec<&str> {
+        page.transactions.iter().map(|tx| tx.hash.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn reads_both_directions_once() {
+        let page = replayed().fetch_transactions(CONTRACT, "eth", "0x0", "latest", None, None).await.unwrap();
+
+        assert_eq!(hashes(&page), vec!["0xt1", "0xt2", "0xt3", "0xt4"]);
+        assert!(page.complete);
+        assert!(page.page_key.is_none());
+        assert_eq!(page.source, "alchemy");
+
+        let token = &page.transactions[0];
+        assert_eq!((token.block_number, token.log_index), (10, Some(1)));
+        assert_eq!(token.amount.as_ref().map(|amount| amount.decimals), Some(6));
+        assert_eq!(page.transactions[3].log_index, None);
+    }
+
+    #[tokio::test]
+    async fn stops_at_the_page_budget_and_resumes() {
+        let alchemy = replayed();
+        let first = alchemy.fetch_transactions(CONTRACT, "eth", "0x0", "latest", None, Some(1)).await.unwrap();
+
+        assert_eq!(hashes(&first), vec!["0xt1", "0xt2", "0xt4"]);
+        assert!(!first.complete);
+        let key = first.page_key.expect("outgoing transfers remain");
+        assert_eq!((key.outgoing.as_deref(), key.incoming.as_deref()), (Some("out-2"), None));
+
+        let rest = alchemy.fetch_transactions(CONTRACT, "eth", "0x0", "latest", Some(&key), None).await.unwrap();
+        assert_eq!(hashes(&rest), vec!["0xt3"]);
+        assert!(rest.complete);
+    }
+
+    // A local Alchemy stand-in keyed by API key: `flaky` is throttled once, `throttled`
+    // always and `revoked` is refused. Returns its URL template and requests per key.
+    fn live_alchemy() -> (String, std::sync::Arc<std::sync::Mutex<std::collections::HashMap<String, usize>>>) {
+        use actix_web::{web, App, HttpResponse, HttpServer};
+        use std::sync::{Arc, Mutex};
+
+        let hits: Arc<Mutex<std::collections::HashMap<String, usize>>> = Arc::default();
+        let state = hits.clone();
+        let server = HttpServer::new(move || {
+            let state = state.clone();
+            App::new().route("/{chain}/{api_key}", web::post().to(move |path: web::Path<(String, String)>| {
+                let api_key = path.into_inner().1;
+                let attempt = {
+                    let mut hits = state.lock().unwrap();
+                    let count = hits.entry(api_key.clone()).or_default();
+                    *count += 1;
+                    *count
+                };
+                async move {
+                    match (api_key.as_str(), attempt) {
+                        ("flaky", 1) | ("throttled", _) => HttpResponse::TooManyRequests().insert_header(("Retry-After", "0")).finish(),
+                        ("revoked", _) => HttpResponse::Unauthorized().body("invalid api key"),
+                        _ => HttpResponse::Ok().json(serde_json::json!({"jsonrpc": "2.0", "id": 1, "result": {"transfers": []}})),
+                    }
+                }
+            }))
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .expect("local port");
+        let url = format!("http://{}/{{chain}}/{{api_key}}", server.addrs()[0]);
+        actix_web::rt::spawn(server.run());
+        (url, hits)
+    }
+
+    fn live(url: &str, api_key: &str) -> AlchemyService {
+        AlchemyService::new(api_key.to_string())
+            .with_base_url(url.to_string())
+            .with_retries(Duration::from_secs(5), 2)
+            .with_compute_budget(10_000)
+    }
+
+    #[actix_web::test]
+    async fn retries_throttled_pages() {
+        let (url, hits) = live_alchemy();
+        let page = live(&url, "flaky").fetch_transactions(CONTRACT, "eth", "0x0", "latest", None, None).await.unwrap();
+        assert!(page.complete);
+        // The throttled outgoing page twice, the incoming page once
+        assert_eq!(hits.lock().unwrap()["flaky"], 3);
+    }
+
+    #[actix_web::test]
+    async fn gives_up_after_the_retry_budget() {
+        let (url, hits) = live_alchemy();
+        let error = live(&url, "throttled").fetch_transactions(CONTRACT, "eth", "0x0", "latest", None, None).await.unwrap_err();
+        assert!(matches!(error, AppError::ServiceUnavailable(ref message) if message.contains("rate limit")), "{}", error);
+        assert_eq!(hits.lock().unwrap()["throttled"], 3);
+
+        let error = live(&url, "revoked").fetch_transactions(CONTRACT, "eth", "0x0", "latest", None, None).await.unwrap_err();
+        assert!(matches!(error, AppError::ServiceUnavailable(ref message) if message.contains("rejected")), "{}", error);
+        assert_eq!(hits.lock().unwrap()["revoked"], 1);
+    }
+
+    #[tokio::test]
+    async fn spreads_requests_over_the_compute_budget() {
+        let budget = ComputeBudget::new(1000);
+        let started = Instant::now();
+        budget.acquire(1000).await;
+        assert!(started.elapsed() < Duration::from_millis(50));
+
+        budget.acquire(200).await;
+        let waited = started.elapsed();
+        assert!(waited >= Duration::from_millis(180) && waited < Duration::from_secs(1), "{:?}", waited);
+
+        // After a 429 the bucket starts empty
+        budget.drain().await;
+        let drained = Instant::now();
+        budget.acquire(100).await;
+        assert!(drained.elapsed() >= Duration::from_millis(90));
+    }
+
+    #[test]
+    fn parses_retry_after_headers() {
+        assert_eq!(parse_retry_after("7"), Some(Duration::from_secs(7)));
+        let later = (chrono::Utc::now() + chrono::Duration::seconds(60)).to_rfc2822();
+        let wait = parse_retry_after(&later).unwrap();
+        assert!(wait > Duration::from_secs(50) && wait <= Duration::from_secs(60));
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+}
@@ -0,0 +1,316 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::{db::DbPool, errors::AppError, services::{chain::ChainClient, rpc::Finality}};
+
+// Below this many candidate blocks the search fetches them all in one batch
+const SEARCH_FINAL_SPAN: u64 = 8;
+// Attempts at fetching a set of headers before the search gives up
+const HEADER_FETCH_ATTEMPTS: u32 = 3;
+
+/// Persisted block-number/timestamp pairs of one chain. Every finalized header fetched
+/// through it is stored, so date-to-block conversions start from a narrow bracket and
+/// usually need a couple of header fetches at most. Newer headers are used but not stored,
+/// since a reorg could still replace them.
+#[derive(Clone)]
+pub struct BlockIndex {
+    pool: DbPool,
+    client: Arc<dyn ChainClient>,
+}
+
+impl BlockIndex {
+    pub fn new(pool: DbPool, client: Arc<dyn ChainClient>) -> Self {
+        Self { pool, client }
+    }
+
+    /// Index of another chain, kept in the same table
+    pub fn on(&self, client: Arc<dyn ChainClient>) -> Self {
+        Self::new(self.pool.clone(), client)
+    }
+
+    /// Stores block timestamps of finalized blocks; blocks already known are left untouched
+    pub async fn record(&self, headers: &[(u64, u64)]) -> Result<(), AppError> {
+        if headers.is_empty() {
+            return Ok(());
+        }
+
+        let mut tx = self.pool.begin().await?;
+        for &(block_number, timestamp) in headers {
+            sqlx::query("INSERT OR IGNORE INTO block_timestamps (chain, block_number, timestamp) VALUES (?, ?, ?)")
+                .bind(self.client.chain())
+                .bind(block_number as i64)
+                .bind(timestamp as i64)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    // Closest known blocks strictly before and at-or-after `target_timestamp`
+    async fn bracket(&self, target_timestamp: u64) -> Result<(Option<(u64, u64)>, Option<(u64, u64)>), AppError> {
+        let below: Option<(i64, i64)> = sqlx::query_as(
+            "SELECT block_number, timestamp FROM block_timestamps
+             WHERE chain = ? AND timestamp < ? ORDER BY block_number DESC LIMIT 1"
+        )
+        .bind(self.client.chain())
+        .bind(target_timestamp as i64)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let above: Option<(i64, i64)> = sqlx::query_as(
+            "SELECT block_number, timestamp FROM block_timestamps
+             WHERE chain = ? AND timestamp >= ? ORDER BY block_number ASC LIMIT 1"
+        )
+        .bind(self.client.chain())
+        .bind(target_timestamp as i64)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let to_pair = |(block, ts): (i64, i64)| (block as u64, ts as u64);
+        Ok((below.map(to_pair), above.map(to_pair)))
+    }
+
+    // Header timestamps for every requested block, retrying the ones a round failed to
+    // fetch. New headers up to `finalized` are persisted; a storage failure only costs
+    // future lookups.
+    async fn fetch_headers(&self, block_numbers: &[u64], finalized: Option<u64>) -> Result<HashMap<u64, u64>, AppError> {
+        let mut headers = HashMap::with_capacity(block_numbers.len());
+        let mut missing = block_numbers.to_vec();
+
+        for _ in 0..HEADER_FETCH_ATTEMPTS {
+            let fetched = self.client.block_timestamps(&missing).await;
+            let pairs: Vec<(u64, u64)> = fetched.iter()
+                .filter(|(&block, _)| finalized.is_some_and(|head| block <= head))
+                .map(|(&block, &ts)| (block, ts))
+                .collect();
+            if let Err(e) = self.record(&pairs).await {
+                log::warn!("Failed to store {} block timestamps: {}", pairs.len(), e);
+            }
+
+            headers.extend(fetched);
+            missing.retain(|block| !headers.contains_key(block));
+            if missing.is_empty() {
+                return Ok(headers);
+            }
+        }
+
+        Err(AppError::BadRequest(format!(
+            "Failed to fetch headers for blocks {:?} during timestamp search", missing
+        )))
+    }
+
+    /// First block whose timestamp is at or after `target_timestamp` (the latest block if none is)
+    pub async fn find_block_by_timestamp(&self, target_timestamp: u64) -> Result<u64, AppError> {
+        Ok(self.search(target_timestamp).await?.0)
+    }
+
+    /// Last block whose timestamp is before `target_timestamp`, or `None` when no block is
+    pub async fn find_last_block_before(&self, target_timestamp: u64) -> Result<Option<u64>, AppError> {
+        Ok(match self.search(target_timestamp).await? {
+            (block, true) => block.checked_sub(1),
+            (latest, false) => Some(latest),
+        })
+    }
+
+    // First block whose timestamp is at or after `target_timestamp`, flagged `false` when
+    // none is and the block is the latest one. Starts from the tightest bracket the stored
+    // timestamps allow, then narrows it with interpolation: each round probes the
+    // interpolated block and the bracket midpoint in one batch, so uneven block times can
+    // never make it slower than a bisection.
+    async fn search(&self, target_timestamp: u64) -> Result<(u64, bool), AppError> {
+        let finalized = match self.client.head_block(Finality::L1Accepted).await {
+            Ok(head) => Some(head.number()),
+            Err(e) => {
+                log::debug!("No finalized {} head, block timestamps will not be stored: {}", self.client.chain(), e);
+                None
+            }
+        };
+        let (mut below, mut above) = self.bracket(target_timestamp).await?;
+
+        // Invariant: low.1 < target <= high.1, and the answer lies in low.0+1..=high.0
+        let (mut low, mut high) = loop {
+            let low = match below {
+                Some(known) => known,
+                None => {
+                    let genesis = self.fetch_headers(&[0], finalized).await?[&0];
+                    if genesis >= target_timestamp {
+                        return Ok((0, true));
+                    }
+                    (0, genesis)
+                }
+            };
+
+            let high = match above {
+                Some(known) => known,
+                None => {
+                    let latest = self.client.block_number().await?;
+                    let ts = self.fetch_headers(&[latest], finalized).await?[&latest];
+                    if ts < target_timestamp {
+                        return Ok((latest, false));
+                    }
+                    (latest, ts)
+                }
+            };
+
+            if low.0 < high.0 {
+                break (low, high);
+            }
+            // Stored blocks past a lagging provider's head, or headers that changed between
+            // fetches; start over from the chain's ends
+            if below.is_none() && above.is_none() {
+                return Err(AppError::BadRequest(format!(
+                    "Inconsistent headers for blocks {} and {} during timestamp search", low.0, high.0
+                )));
+            }
+            log::warn!(
+                "Stored {} timestamps of blocks {} and {} are out of order, ignoring them",
+                self.client.chain(), low.0, high.0
+            );
+            (below, above) = (None, None);
+        };
+
+        let mut rounds = 0;
+        while high.0 - low.0 > SEARCH_FINAL_SPAN {
+            let span = high.0 - low.0;
+            let offset = (target_timestamp - low.1) as u128 * span as u128 / (high.1 - low.1) as u128;
+            let interpolated = (low.0 + offset as u64).clamp(low.0 + 1, high.0 - 1);
+            let midpoint = low.0 + span / 2;
+
+            let mut probes = vec![interpolated, midpoint];
+            probes.dedup();
+            let headers = self.fetch_headers(&probes, finalized).await?;
+
+            for (&block, &ts) in &headers {
+                if ts < target_timestamp {
+                    low = low.max((block, ts));
+                } else if block < high.0 {
+                    high = (block, ts);
+                }
+            }
+            rounds += 1;
+        }
+
+        let candidates: Vec<u64> = (low.0 + 1..high.0).collect();
+        let headers = self.fetch_headers(&candidates, finalized).await?;
+        let block = candidates.into_iter()
+            .find(|block| headers[block] >= target_timestamp)
+            .unwrap_or(high.0);
+
+        log::info!("Timestamp {} resolved to block {} after {} probe rounds", target_timestamp, block, rounds);
+        Ok((block, true))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use serde_json::Value;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::services::{
+        chain::ChainBlock,
+        event_filter::EventFilter,
+        rpc::{BlockId, ContractScan, EventFetch},
+        token::TokenMetadata,
+    };
+
+    const HEAD: u64 = 10_000;
+    const FINALIZED: u64 = 9_000;
+
+    // Blocks every 2s, then every 12s from block 6000; counts the headers it serves
+    #[derive(Default)]
+    struct TestChain {
+        headers: AtomicUsize,
+    }
+
+    fn timestamp(block: u64) -> u64 {
+        1_000_000 + 2 * block.min(6_000) + 12 * block.saturating_sub(6_000)
+    }
+
+    #[async_trait]
+    impl ChainClient for TestChain {
+        fn chain(&self) -> &str {
+            "testchain"
+        }
+
+        async fn block_number(&self) -> Result<u64, AppError> {
+            Ok(HEAD)
+        }
+
+        async fn block_timestamps(&self, block_numbers: &[u64]) -> HashMap<u64, u64> {
+            self.headers.fetch_add(block_numbers.len(), Ordering::Relaxed);
+            block_numbers.iter().filter(|&&n| n <= HEAD).map(|&n| (n, timestamp(n))).collect()
+        }
+
+        async fn head_block(&self, finality: Finality) -> Result<BlockId, AppError> {
+            Ok(BlockId::Number(if finality == Finality::L1Accepted { FINALIZED } else { HEAD }))
+        }
+
+        async fn get_block(&self, _: u64) -> Result<ChainBlock, AppError> {
+            Err(AppError::NotFound("not part of the test chain".to_string()))
+        }
+
+        async fn get_events(&self, _: &str, _: u64, _: BlockId, _: Option<&Value>, _: &EventFilter) -> Result<EventFetch, AppError> {
+            Err(AppError::NotFound("not part of the test chain".to_string()))
+        }
+
+        async fn call(&self, _: &str, _: &str, _: &Value, _: BlockId) -> Result<Value, AppError> {
+            Err(AppError::NotFound("not part of the test chain".to_string()))
+        }
+
+        async fn scan_contract(&self, _: &str, _: Option<u64>, _: Option<u64>, _: Finality) -> Result<ContractScan, AppError> {
+            Err(AppError::NotFound("not part of the test chain".to_string()))
+        }
+
+        fn native_symbol(&self) -> &str {
+            "ETH"
+        }
+
+        async fn token_metadata(&self, _: &str) -> Result<TokenMetadata, AppError> {
+            Err(AppError::NotFound("not part of the test chain".to_string()))
+        }
+    }
+
+    async fn index() -> (BlockIndex, Arc<TestChain>) {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new().max_connections(1).connect("sqlite::memory:").await.unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        let chain = Arc::new(TestChain::default());
+        (BlockIndex::new(pool, chain.clone()), chain)
+    }
+
+    #[tokio::test]
+    async fn finds_blocks_around_a_timestamp() {
+        let (index, _) = index().await;
+        assert_eq!(index.find_block_by_timestamp(timestamp(4_321)).await.unwrap(), 4_321);
+        assert_eq!(index.find_block_by_timestamp(timestamp(7_000) - 5).await.unwrap(), 7_000);
+        assert_eq!(index.find_last_block_before(timestamp(7_000) - 5).await.unwrap(), Some(6_999));
+        assert_eq!(index.find_last_block_before(timestamp(7_000)).await.unwrap(), Some(6_999));
+    }
+
+    #[tokio::test]
+    async fn clamps_to_the_ends_of_the_chain() {
+        let (index, _) = index().await;
+        assert_eq!(index.find_block_by_timestamp(0).await.unwrap(), 0);
+        assert_eq!(index.find_last_block_before(timestamp(0)).await.unwrap(), None);
+        assert_eq!(index.find_block_by_timestamp(timestamp(HEAD) + 1).await.unwrap(), HEAD);
+        assert_eq!(index.find_last_block_before(timestamp(HEAD) + 1).await.unwrap(), Some(HEAD));
+    }
+
+    #[tokio::test]
+    async fn stores_finalized_headers_for_later_searches() {
+        let (index, chain) = index().await;
+        index.find_block_by_timestamp(timestamp(5_000)).await.unwrap();
+        let first = chain.headers.swap(0, Ordering::Relaxed);
+
+        assert_eq!(index.find_block_by_timestamp(timestamp(5_000)).await.unwrap(), 5_000);
+        assert!(chain.headers.load(Ordering::Relaxed) < first);
+
+        let (newest,): (i64,) = sqlx::query_as("SELECT MAX(block_number) FROM block_timestamps WHERE chain = 'testchain'")
+            .fetch_one(&index.pool)
+            .await
+            .unwrap();
+        assert!(newest as u64 <= FINALIZED);
+    }
+}
@@ -0,0 +1,232 @@
+use serde_json::{json, Value};
+use sha3::{Digest, Keccak256};
+use std::path::PathBuf;
+
+use crate::config::Config;
+use crate::services::rpc_cache::canonical;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CassetteMode {
+    /// Live endpoints only
+    Off,
+    /// Live endpoints, with every exchange written to the cassette directory
+    Record,
+    /// Recorded exchanges only; requests without a recording fail
+    Replay,
+}
+
+impl CassetteMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "off" | "" => Some(Self::Off),
+            "record" => Some(Self::Record),
+            "replay" => Some(Self::Replay),
+            _ => None,
+        }
+    }
+}
+
+/// A response as it came off the wire
+#[derive(Debug, Clone)]
+pub struct Recorded {
+    pub status: u16,
+    pub body: String,
+}
+
+/// Request/response pairs on disk, one JSON file per distinct call under
+/// `<dir>/<namespace>/`. Files are named by method and a hash of the canonical method and
+/// params, so a call finds its recording whichever provider answered it and whatever its
+/// id, and recordings can be inspected or hand-written as stubs. Batches are stored per
+/// call: a batch replays from the recordings of its calls, however they were sent.
+#[derive(Clone)]
+pub struct Cassette {
+    mode: CassetteMode,
+    dir: PathBuf,
+}
+
+impl Cassette {
+    pub fn new(mode: CassetteMode, dir: impl Into<PathBuf>) -> Self {
+        Self { mode, dir: dir.into() }
+    }
+
+    /// Cassette for one service (`starknet`, an EVM chain, `alchemy`), or `None` when
+    /// `RPC_CASSETTE_MODE` is off
+    pub fn from_config(config: &Config, namespace: &str) -> Option<Self> {
+        let mode = CassetteMode::parse(&config.rpc_cassette_mode).unwrap_or(CassetteMode::Off);
+        (mode != CassetteMode::Off).then(|| Self::new(mode, PathBuf::from(&config.rpc_cassette_dir).join(namespace)))
+    }
+
+    /// The same cassette with recordings kept under a subdirectory, e.g. one per chain
+    pub fn scoped(&self, name: &str) -> Self {
+        Self::new(self.mode, self.dir.join(name))
+    }
+
+    pub fn replaying(&self) -> bool {
+        self.mode == CassetteMode::Replay
+    }
+
+    /// Recorded response to `request`, `None` when there is none. A batch is answered from
+    /// the recordings of its calls unless the batch itself was recorded as refused.
+    pub async fn replay(&self, request: &Value) -> Option<Recorded> {
+        let Value::Array(calls) = request else {
+            return self.replay_call(request).await;
+        };
+        if let Some(refusal) = self.load(&self.path(request)).await {
+            return Some(refusal);
+        }
+
+        let mut responses = Vec::with_capacity(calls.len());
+        for call in calls {
+            let recorded = self.replay_call(call).await?;
+            responses.push(serde_json::from_str::<Value>(&recorded.body).ok()?);
+        }
+        Some(Recorded { status: 200, body: Value::Array(responses).to_string() })
+    }
+
+    // Recorded response to one call, answering to the call's id
+    async fn replay_call(&self, request: &Value) -> Option<Recorded> {
+        let path = self.path(request);
+        let Some(mut recorded) = self.load(&path).await else {
+            log::warn!("No recorded response at {}", path.display());
+            return None;
+        };
+        if let Ok(mut response) = serde_json::from_str::<Value>(&recorded.body) {
+            if let (Some(fields), Some(id)) = (response.as_object_mut(), request.get("id")) {
+                fields.insert("id".to_string(), id.clone());
+                recorded.body = response.to_string();
+            }
+        }
+        Some(recorded)
+    }
+
+    async fn load(&self, path: &std::path::Path) -> Option<Recorded> {
+        let raw = tokio::fs::read_to_string(path).await.ok()?;
+        let entry: Value = serde_json::from_str(&raw)
+            .map_err(|e| log::warn!("Unreadable cassette {}: {}", path.display(), e))
+            .ok()?;
+        Some(Recorded {
+            status: entry.get("status").and_then(|v| v.as_u64()).unwrap_or(200) as u16,
+            body: match entry.get("response") {
+                Some(Value::String(text)) => text.clone(),
+                Some(response) => response.to_string(),
+                None => String::new(),
+            },
+        })
+    }
+
+    /// Writes an exchange when recording; a no-op in replay mode. A batch answered with an
+    /// array is split into one recording per call.
+    pub async fn record(&self, request: &Value, response: &Recorded) {
+        if self.mode != CassetteMode::Record {
+            return;
+        }
+
+        // Stored as JSON when it is JSON so recordings stay readable
+        let body = serde_json::from_str::<Value>(&response.body).unwrap_or_else(|_| Value::String(response.body.clone()));
+        if let (Value::Array(calls), Value::Array(responses)) = (request, &body) {
+            for item in responses {
+                let call = calls.iter().find(|call| call.get("id").is_some() && call.get("id") == item.get("id"));
+                if let Some(call) = call {
+                    self.write(call, response.status, item).await;
+                }
+            }
+            return;
+        }
+        self.write(request, response.status, &body).await;
+    }
+
+    async fn write(&self, request: &Value, status: u16, response: &Value) {
+        let path = self.path(request);
+        let entry = json!({
+            "request": request,
+            "status": status,
+            "response": response,
+        });
+
+        let written = async {
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::write(&path, serde_json::to_string_pretty(&entry).unwrap_or_default()).await
+        };
+        if let Err(e) = written.await {
+            log::warn!("Failed to record {}: {}", path.display(), e);
+        }
+    }
+
+    fn path(&self, request: &Value) -> PathBuf {
+        // Ids and the protocol version do not change the answer
+        let call = |request: &Value| json!({"method": request.get("method"), "params": request.get("params")});
+        let (method, key) = match request {
+            Value::Array(calls) => ("batch", Value::Array(calls.iter().map(call).collect())),
+            _ => (request.get("method").and_then(|v| v.as_str()).unwrap_or("request"), call(request)),
+        };
+        let digest = Keccak256::digest(canonical(&key).to_string().as_bytes());
+        let hash: String = digest[..8].iter().map(|b| format!("{:02x}", b)).collect();
+        self.dir.join(format!("{}-{}.json", method, hash))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cassette(mode: CassetteMode, name: &str) -> Cassette {
+        let dir = std::env::temp_dir().join(format!("cassette-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        Cassette::new(mode, dir)
+    }
+
+    fn call(id: u64, block: u64) -> Value {
+        json!({"jsonrpc": "2.0", "id": id, "method": "starknet_getBlockWithTxHashes", "params": [{"block_number": block}]})
+    }
+
+    fn answer(id: u64, block: u64) -> Value {
+        json!({"jsonrpc": "2.0", "id": id, "result": {"block_number": block}})
+    }
+
+    #[tokio::test]
+    async fn replays_batches_from_per_call_recordings() {
+        let recorder = cassette(CassetteMode::Record, "batch");
+        let batch = json!([call(1, 10), call(2, 11)]);
+        let response = Recorded { status: 200, body: json!([answer(2, 11), answer(1, 10)]).to_string() };
+        recorder.record(&batch, &response).await;
+
+        let replayer = Cassette::new(CassetteMode::Replay, recorder.dir.clone());
+
+        // Alone, under another id
+        let single = replayer.replay(&call(7, 11)).await.expect("recorded");
+        assert_eq!(serde_json::from_str::<Value>(&single.body).unwrap(), answer(7, 11));
+
+        // In a different batch, answered in request order
+        let regrouped = replayer.replay(&json!([call(3, 11), call(4, 10)])).await.expect("recorded");
+        assert_eq!(
+            serde_json::from_str::<Value>(&regrouped.body).unwrap(),
+            json!([answer(3, 11), answer(4, 10)])
+        );
+
+        // A batch with one unrecorded call has no recording
+        assert!(replayer.replay(&json!([call(5, 10), call(6, 12)])).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn replays_batch_refusals_for_the_whole_batch() {
+        let recorder = cassette(CassetteMode::Record, "refusal");
+        let batch = json!([call(1, 10), call(2, 11)]);
+        let refusal = Recorded { status: 413, body: "batch too large".to_string() };
+        recorder.record(&batch, &refusal).await;
+
+        let replayer = Cassette::new(CassetteMode::Replay, recorder.dir.clone());
+        let replayed = replayer.replay(&batch).await.expect("recorded");
+        assert_eq!((replayed.status, replayed.body.as_str()), (413, "batch too large"));
+        assert!(replayer.replay(&call(1, 10)).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn writes_nothing_outside_record_mode() {
+        let replayer = cassette(CassetteMode::Replay, "readonly");
+        let response = Recorded { status: 200, body: answer(1, 10).to_string() };
+        replayer.record(&call(1, 10), &response).await;
+        assert!(!replayer.dir.exists());
+    }
+}
@@ -0,0 +1,214 @@
+use async_trait::async_trait;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::errors::AppError;
+use crate::services::event_filter::EventFilter;
+use crate::services::rpc::{BlockId, BlockStatus, ContractAnalysis, ContractScan, EventFetch, Finality, RpcService, MAX_EVENT_PAGES};
+use crate::services::starknet_receipt::{fee_cap, FeeUnit, TransactionReceipt};
+use crate::services::token::TokenMetadata;
+
+/// Chain id of the Starknet client, also the default when a request names no chain
+pub const STARKNET: &str = "starknet";
+
+/// Starknet's WEI-denominated fees are paid in ETH
+pub const STARKNET_NATIVE_SYMBOL: &str = "ETH";
+
+/// A block reduced to what every chain reports
+#[derive(Debug, Serialize)]
+pub struct ChainBlock {
+    pub block_number: u64,
+    pub timestamp: u64,
+    pub status: BlockStatus,
+    pub transactions: Vec<ChainTransaction>,
+    /// Receipts by normalized transaction hash
+    pub receipts: HashMap<String, TransactionReceipt>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChainTransaction {
+    pub transaction_hash: String,
+    pub sender_address: Option<String>,
+    /// Called contract: the EVM `to`, or the deployed contract of a Starknet deploy
+    pub to_address: Option<String>,
+    /// Most the transaction may pay
+    pub max_fee: u128,
+    pub fee_unit: FeeUnit,
+    pub tx_type: String,
+}
+
+/// Read access to one chain. Block ranges, finality modes, events and analyses share
+/// the Starknet shapes so handlers can serve every chain the same way.
+#[async_trait]
+pub trait ChainClient: Send + Sync {
+    /// Id requests select the chain by
+    fn chain(&self) -> &str;
+
+    async fn block_number(&self) -> Result<u64, AppError>;
+
+    /// Timestamps of the given blocks; blocks that could not be fetched are absent
+    async fn block_timestamps(&self, block_numbers: &[u64]) -> HashMap<u64, u64>;
+
+    /// Newest block a finality mode allows reading
+    async fn head_block(&self, finality: Finality) -> Result<BlockId, AppError>;
+
+    async fn get_block(&self, block_number: u64) -> Result<ChainBlock, AppError>;
+
+    /// Events (EVM logs) emitted by a contract in a block range, narrowed by `filter`.
+    /// `abi` is a Solidity ABI used to decode EVM logs and resolve event names; Starknet
+    /// reads the contract's ABI from the chain instead.
+    async fn get_events(
+        &self,
+        contract_address: &str,
+        from_block: u64,
+        to_block: BlockId,
+        abi: Option<&Value>,
+        filter: &EventFilter,
+    ) -> Result<EventFetch, AppError>;
+
+    /// Read-only call. `function` is an ABI function name on Starknet and a Solidity
+    /// signature (or raw `0x` calldata) on EVM chains.
+    async fn call(&self, contract_address: &str, function: &str, args: &Value, block: BlockId) -> Result<Value, AppError>;
+
+    /// Transactions sent by or to a contract over a block range, with their receipts.
+    /// Without bounds the scan covers a chain-specific window ending at the head.
+    async fn scan_contract(
+        &self,
+        contract_address: &str,
+        from_block: Option<u64>,
+        to_block: Option<u64>,
+        finality: Finality,
+    ) -> Result<ContractScan, AppError>;
+
+    /// `scan_contract` summarized
+    async fn analyze_contract(
+        &self,
+        contract_address: &str,
+        from_block: Option<u64>,
+        to_block: Option<u64>,
+        finality: Finality,
+    ) -> Result<ContractAnalysis, AppError> {
+        Ok(self.scan_contract(contract_address, from_block, to_block, finality).await?.into_analysis())
+    }
+
+    /// Symbol of the coin WEI-denominated fees are paid in
+    fn native_symbol(&self) -> &str;
+
+    /// Name, symbol and decimals a token contract reports, read at the latest block. A
+    /// getter that reverts leaves its field `None`; a call that got no answer fails the
+    /// whole lookup, so partial metadata is never stored.
+    async fn token_metadata(&self, contract_address: &str) -> Result<TokenMetadata, AppError>;
+}
+
+#[async_trait]
+impl ChainClient for RpcService {
+    fn chain(&self) -> &str {
+        STARKNET
+    }
+
+    async fn block_number(&self) -> Result<u64, AppError> {
+        self.get_block_number().await
+    }
+
+    async fn block_timestamps(&self, block_numbers: &[u64]) -> HashMap<u64, u64> {
+        self.get_block_timestamps(block_numbers).await
+    }
+
+    async fn head_block(&self, finality: Finality) -> Result<BlockId, AppError> {
+        RpcService::head_block(self, finality).await
+    }
+
+    async fn get_block(&self, block_number: u64) -> Result<ChainBlock, AppError> {
+        let block = self.get_block_with_receipts(BlockId::Number(block_number)).await?;
+        let field = |tx: &Value, name: &str| tx.get(name).and_then(|v| v.as_str()).map(String::from);
+
+        Ok(ChainBlock {
+            block_number: block.block_number,
+            timestamp: block.timestamp,
+            status: block.status,
+            transactions: block.transactions.iter()
+                .map(|tx| {
+                    let (max_fee, fee_unit) = fee_cap(tx);
+                    ChainTransaction {
+                        transaction_hash: field(tx, "transaction_hash").unwrap_or_default(),
+                        sender_address: field(tx, "sender_address"),
+                        to_address: field(tx, "contract_address"),
+                        max_fee,
+                        fee_unit,
+                        tx_type: field(tx, "type").unwrap_or_else(|| "INVOKE".to_string()),
+                    }
+                })
+                .collect(),
+            receipts: block.receipts,
+        })
+    }
+
+    async fn get_events(
+        &self,
+        contract_address: &str,
+        from_block: u64,
+        to_block: BlockId,
+        _abi: Option<&Value>,
+        filter: &EventFilter,
+    ) -> Result<EventFetch, AppError> {
+        self.fetch_events(contract_address, from_block, to_block, &filter.starknet_keys()?, Some(MAX_EVENT_PAGES)).await
+    }
+
+    async fn call(&self, contract_address: &str, function: &str, args: &Value, block: BlockId) -> Result<Value, AppError> {
+        let result = self.call_contract(contract_address, function, args, block).await?;
+        serde_json::to_value(result).map_err(|e| AppError::BadRequest(format!("Invalid call result: {}", e)))
+    }
+
+    async fn scan_contract(
+        &self,
+        contract_address: &str,
+        from_block: Option<u64>,
+        to_block: Option<u64>,
+        finality: Finality,
+    ) -> Result<ContractScan, AppError> {
+        RpcService::scan_contract(self, contract_address, from_block, to_block, finality).await
+    }
+
+    fn native_symbol(&self) -> &str {
+        STARKNET_NATIVE_SYMBOL
+    }
+
+    async fn token_metadata(&self, contract_address: &str) -> Result<TokenMetadata, AppError> {
+        RpcService::token_metadata(self, contract_address).await
+    }
+}
+
+/// Clients of every configured chain, looked up by the `chain` of a request
+#[derive(Clone)]
+pub struct ChainRegistry {
+    clients: Arc<HashMap<String, Arc<dyn ChainClient>>>,
+}
+
+impl ChainRegistry {
+    pub fn new(clients: Vec<Arc<dyn ChainClient>>) -> Self {
+        Self {
+            clients: Arc::new(
+                clients.into_iter()
+                    .map(|client| (client.chain().to_string(), client))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Client for a chain id, Starknet when none is given
+    pub fn get(&self, chain: Option<&str>) -> Result<Arc<dyn ChainClient>, AppError> {
+        let chain = chain.unwrap_or(STARKNET).to_lowercase();
+        self.clients.get(&chain).cloned().ok_or_else(|| AppError::BadRequest(format!(
+            "Chain '{}' is not supported. Supported chains: {}", chain, self.chains().join(", ")
+        )))
+    }
+
+    /// Configured chain ids, sorted
+    pub fn chains(&self) -> Vec<String> {
+        let mut chains: Vec<String> = self.clients.keys().cloned().collect();
+        chains.sort();
+        chains
+    }
+}
@@ -0,0 +1,235 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::services::rpc::{ContractScan, EventData};
+use crate::services::starknet_receipt::FeeUnit;
+use crate::services::token::TokenAmount;
+
+/// Most contracts one comparison may cover
+pub const MAX_COMPARED_CONTRACTS: usize = 5;
+/// Longest range a comparison may span, in days
+pub const MAX_COMPARED_DAYS: usize = 366;
+/// Most blocks scanned per compared contract. Longer ranges are scanned from their newest
+/// block back, and the contract is reported incomplete.
+pub const MAX_COMPARED_BLOCKS: u64 = 10_000;
+
+/// A contract's activity over one UTC day, or over the whole compared range
+#[derive(Debug, Serialize)]
+pub struct ActivityMetrics {
+    pub transaction_count: usize,
+    pub unique_senders: usize,
+    /// Actual fees by currency, e.g. `ETH` and `STRK` on Starknet
+    pub fees: BTreeMap<String, TokenAmount>,
+    pub event_count: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DailyActivity {
+    /// `YYYY-MM-DD`, UTC
+    pub date: String,
+    #[serde(flatten)]
+    pub metrics: ActivityMetrics,
+}
+
+#[derive(Default)]
+struct Tally<'a> {
+    transactions: usize,
+    senders: HashSet<&'a str>,
+    fees: BTreeMap<FeeUnit, u128>,
+    events: usize,
+}
+
+impl<'a> Tally<'a> {
+    fn merge(&mut self, other: &Tally<'a>) {
+        self.transactions += other.transactions;
+        self.senders.extend(&other.senders);
+        for (&unit, &fee) in &other.fees {
+            let total = self.fees.entry(unit).or_default();
+            *total = total.saturating_add(fee);
+        }
+        self.events += other.events;
+    }
+
+    fn metrics(&self, native_symbol: &str) -> ActivityMetrics {
+        ActivityMetrics {
+            transaction_count: self.transactions,
+            unique_senders: self.senders.len(),
+            fees: self.fees.iter()
+                .map(|(&unit, &fee)| TokenAmount::fee(fee, unit, native_symbol))
+                .map(|amount| (amount.symbol.clone().unwrap_or_default(), amount))
+                .collect(),
+            event_count: self.events,
+        }
+    }
+}
+
+/// UTC days from `from` to `to` (unix seconds), both included, as `YYYY-MM-DD`
+pub fn days_between(from: u64, to: u64) -> Vec<String> {
+    let (Some(first), Some(last)) = (day_of(from), day_of(to)) else {
+        return vec![];
+    };
+    first.iter_days()
+        .take_while(|day| *day <= last)
+        .map(|day| day.format("%Y-%m-%d").to_string())
+        .collect()
+}
+
+fn day_of(ts: u64) -> Option<chrono::NaiveDate> {
+    chrono::DateTime::from_timestamp(ts as i64, 0).map(|dt| dt.date_naive())
+}
+
+/// Totals and per-day activity of a scanned contract and its events. The days follow
+/// `days` with zeros where nothing happened, so contracts line up on one axis whatever
+/// their chain; activity outside `days` only counts towards the totals.
+pub fn summarize(scan: &ContractScan, events: &[EventData], days: &[String]) -> (ActivityMetrics, Vec<DailyActivity>) {
+    let positions: HashMap<&str, usize> = days.iter()
+        .enumerate()
+        .map(|(i, day)| (day.as_str(), i))
+        .collect();
+    let mut daily: Vec<Tally> = days.iter().map(|_| Tally::default()).collect();
+    let mut outside = Tally::default();
+
+    // Position in `days`; past the end for activity outside them
+    let day_index = |ts: u64| -> usize {
+        day_of(ts)
+            .map(|day| day.format("%Y-%m-%d").to_string())
+            .and_then(|day| positions.get(day.as_str()).copied())
+            .unwrap_or(usize::MAX)
+    };
+
+    for tx in &scan.transactions {
+        let i = day_index(tx.timestamp);
+        let tally = daily.get_mut(i).unwrap_or(&mut outside);
+        tally.transactions += 1;
+        tally.senders.insert(&tx.sender_address);
+        let fee = tx.actual_fee.as_deref()
+            .and_then(|fee| u128::from_str_radix(fee.trim_start_matches("0x"), 16).ok());
+        if let Some(fee) = fee {
+            let total = tally.fees.entry(tx.fee_unit).or_default();
+            *total = total.saturating_add(fee);
+        }
+    }
+    for event in events {
+        let i = day_index(event.timestamp_raw);
+        daily.get_mut(i).unwrap_or(&mut outside).events += 1;
+    }
+
+    let mut totals = outside;
+    for tally in &daily {
+        totals.merge(tally);
+    }
+
+    let daily = days.iter()
+        .zip(&daily)
+        .map(|(day, tally)| DailyActivity {
+            date: day.clone(),
+            metrics: tally.metrics(&scan.native_symbol),
+        })
+        .collect();
+    (totals.metrics(&scan.native_symbol), daily)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::rpc::{BlockStatus, Finality, TransactionInfo};
+
+    // 2024-03-01 00:00:00 UTC
+    const MARCH_1: u64 = 1_709_251_200;
+    const DAY: u64 = 86_400;
+
+    fn transaction(sender: &str, timestamp: u64, fee: u128, fee_unit: FeeUnit) -> TransactionInfo {
+        TransactionInfo {
+            block_number: 1,
+            transaction_hash: format!("0x{:x}", timestamp),
+            sender_address: sender.to_string(),
+            contract_address: "0xc".to_string(),
+            max_fee: "0x0".to_string(),
+            actual_fee: Some(format!("{:#x}", fee)),
+            fee_unit,
+            execution_status: None,
+            revert_reason: None,
+            tx_type: "INVOKE".to_string(),
+            timestamp,
+            finality_status: BlockStatus::AcceptedOnL2,
+        }
+    }
+
+    fn event(timestamp_raw: u64) -> EventData {
+        EventData {
+            contract_address: "0xc".to_string(),
+            block_number: 1,
+            transaction_hash: "0x1".to_string(),
+            event_index: 0,
+            transaction_index: None,
+            receipt_index: None,
+            keys: vec![],
+            data: vec![],
+            event_name: String::new(),
+            decoded_data: serde_json::Value::Null,
+            timestamp: String::new(),
+            timestamp_raw,
+            timestamp_estimated: false,
+            finality_status: BlockStatus::AcceptedOnL2,
+        }
+    }
+
+    fn scan(transactions: Vec<TransactionInfo>) -> ContractScan {
+        ContractScan {
+            contract_address: "0xc".to_string(),
+            transactions,
+            receipts: vec![],
+            blocks_analyzed: 1,
+            failed_blocks: vec![],
+            current_block: 1,
+            finality: Finality::Latest,
+            from_block: 1,
+            to_block: 1,
+            native_symbol: "ETH".to_string(),
+        }
+    }
+
+    #[test]
+    fn lists_every_day_of_a_range() {
+        assert_eq!(days_between(MARCH_1 - DAY, MARCH_1 + 10), vec!["2024-02-29", "2024-03-01"]);
+        assert_eq!(days_between(MARCH_1 + 10, MARCH_1 + 20), vec!["2024-03-01"]);
+        assert!(days_between(MARCH_1, MARCH_1 - DAY).is_empty());
+    }
+
+    #[test]
+    fn summarizes_activity_per_day() {
+        let days = days_between(MARCH_1, MARCH_1 + DAY);
+        let scan = scan(vec![
+            transaction("0xa", MARCH_1 + 60, 1_000_000_000_000_000, FeeUnit::Wei),
+            transaction("0xa", MARCH_1 + 120, 2_000_000_000_000_000_000, FeeUnit::Fri),
+            transaction("0xb", MARCH_1 + DAY + 60, 500_000_000_000_000, FeeUnit::Wei),
+            // Before the compared days: counted in the totals only
+            transaction("0xc", MARCH_1 - 60, 0, FeeUnit::Wei),
+        ]);
+        let events = [event(MARCH_1 + 5), event(MARCH_1 + DAY + 5)];
+
+        let (totals, daily) = summarize(&scan, &events, &days);
+
+        assert_eq!((totals.transaction_count, totals.unique_senders, totals.event_count), (4, 3, 2));
+        assert_eq!(totals.fees["ETH"].formatted, "0.0015");
+        assert_eq!(totals.fees["STRK"].formatted, "2");
+
+        let dates: Vec<&str> = daily.iter().map(|day| day.date.as_str()).collect();
+        assert_eq!(dates, vec!["2024-03-01", "2024-03-02"]);
+        let first = &daily[0].metrics;
+        assert_eq!((first.transaction_count, first.unique_senders, first.event_count), (2, 1, 1));
+        assert_eq!(first.fees.keys().collect::<Vec<_>>(), vec!["ETH", "STRK"]);
+        let second = &daily[1].metrics;
+        assert_eq!((second.transaction_count, second.event_count), (1, 1));
+        assert_eq!(second.fees["ETH"].formatted, "0.0005");
+    }
+
+    #[test]
+    fn reports_quiet_days_as_zeros() {
+        let days = days_between(MARCH_1, MARCH_1 + 2 * DAY);
+        let (totals, daily) = summarize(&scan(vec![]), &[], &days);
+        assert_eq!(daily.len(), 3);
+        assert!(daily.iter().all(|day| day.metrics.transaction_count == 0 && day.metrics.fees.is_empty()));
+        assert_eq!(totals.transaction_count, 0);
+    }
+}
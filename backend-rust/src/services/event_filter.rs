@@ -0,0 +1,213 @@
+use num_bigint::BigUint;
+use serde_json::Value;
+
+use crate::errors::AppError;
+use crate::services::evm_abi::{event_topic, SolidityAbi};
+use crate::utils::felt::{felt_to_hex, normalize_hex, parse_felt, selector};
+
+/// Which events of a contract a request asks for. `events` picks event types and `keys`
+/// the values of their indexed keys; an empty filter matches every event.
+///
+/// An event is a name (`Transfer`, or a Cairo path such as `...::ERC20::Transfer`), a `0x`
+/// selector or topic hash, or on EVM chains a signature such as
+/// `Transfer(address,address,uint256)`. `keys` is positional after the selector, so on an
+/// ERC-20 `Transfer` position 0 is `from` and position 1 is `to`; each position lists the
+/// values it may take, and an empty position matches anything.
+#[derive(Debug, Clone, Default)]
+pub struct EventFilter {
+    pub events: Vec<String>,
+    pub keys: Vec<Vec<String>>,
+}
+
+impl EventFilter {
+    /// `events` (a string or an array) and `keys` (an array of values, value arrays or nulls)
+    /// of a request
+    pub fn from_payload(payload: &Value) -> Result<Self, AppError> {
+        let events = match payload.get("events") {
+            None | Some(Value::Null) => vec![],
+            Some(value) => strings(value)
+                .ok_or(AppError::BadRequest("events must be an event name or an array of them".to_string()))?,
+        };
+
+        let keys = match payload.get("keys") {
+            None | Some(Value::Null) => vec![],
+            Some(Value::Array(positions)) => positions.iter()
+                .map(|position| match position {
+                    Value::Null => Some(vec![]),
+                    value => strings(value),
+                })
+                .collect::<Option<Vec<_>>>()
+                .ok_or(AppError::BadRequest("keys must be an array of key values, value arrays or nulls".to_string()))?,
+            Some(_) => return Err(AppError::BadRequest("keys must be an array".to_string())),
+        };
+
+        Ok(Self { events, keys })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty() && self.keys.iter().all(|values| values.is_empty())
+    }
+
+    /// The filter as a `starknet_getEvents` key pattern: selectors, then the key values,
+    /// all as normalized felts
+    pub fn starknet_keys(&self) -> Result<Vec<Vec<String>>, AppError> {
+        if self.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let selectors = self.events.iter()
+            .map(|event| if event.starts_with("0x") {
+                felt(event)
+            } else {
+                // Selectors hash the bare event name, without the module path
+                Ok(selector(event.rsplit("::").next().unwrap_or(event)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut pattern = vec![selectors];
+        for values in &self.keys {
+            pattern.push(values.iter().map(|value| felt(value)).collect::<Result<_, _>>()?);
+        }
+        Ok(trim_wildcards(pattern))
+    }
+
+    /// The filter as `eth_getLogs` topics: topic hashes, then the key values as 32-byte
+    /// words. Event names are looked up in `abis`.
+    pub fn evm_topics(&self, abis: &[&SolidityAbi]) -> Result<Vec<Vec<String>>, AppError> {
+        if self.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut topics = Vec::new();
+        for event in &self.events {
+            if event.contains('(') {
+                topics.push(event_topic(event)?);
+            } else if event.starts_with("0x") {
+                topics.push(word(event)?);
+            } else {
+                let named: Vec<String> = abis.iter().flat_map(|abi| abi.event_topics(event)).collect();
+                if named.is_empty() {
+                    return Err(AppError::BadRequest(format!(
+                        "Unknown event '{}': supply its ABI or a signature such as {}(address,uint256)", event, event
+                    )));
+                }
+                topics.extend(named);
+            }
+        }
+        topics.sort();
+        topics.dedup();
+
+        let mut pattern = vec![topics];
+        for values in &self.keys {
+            pattern.push(values.iter().map(|value| word(value)).collect::<Result<_, _>>()?);
+        }
+        Ok(trim_wildcards(pattern))
+    }
+}
+
+/// Whether event keys match a key pattern. Values compare as felts, so padding and case
+/// do not matter.
+pub fn matches_keys(pattern: &[Vec<String>], keys: &[String]) -> bool {
+    pattern.iter().enumerate().all(|(position, values)| {
+        values.is_empty() || keys.get(position).is_some_and(|key| {
+            let key = normalize_hex(key);
+            values.iter().any(|value| normalize_hex(value) == key)
+        })
+    })
+}
+
+fn strings(value: &Value) -> Option<Vec<String>> {
+    match value {
+        Value::String(text) => Some(vec![text.trim().to_string()]),
+        Value::Array(items) => items.iter()
+            .map(|item| item.as_str().map(|text| text.trim().to_string()))
+            .collect(),
+        _ => None,
+    }
+}
+
+fn parse_value(value: &str) -> Result<BigUint, AppError> {
+    parse_felt(value).ok_or_else(|| AppError::BadRequest(format!("Invalid key value '{}'", value)))
+}
+
+fn felt(value: &str) -> Result<String, AppError> {
+    parse_value(value).map(|v| felt_to_hex(&v))
+}
+
+// A value left-padded to one 32-byte topic
+fn word(value: &str) -> Result<String, AppError> {
+    let value = parse_value(value)?;
+    if value.bits() > 256 {
+        return Err(AppError::BadRequest(format!("Key value {} does not fit in a topic", felt_to_hex(&value))));
+    }
+    Ok(format!("0x{:064x}", value))
+}
+
+// Trailing empty positions match anything, so nodes need not see them
+fn trim_wildcards(mut pattern: Vec<Vec<String>>) -> Vec<Vec<String>> {
+    while pattern.last().is_some_and(|values| values.is_empty()) {
+        pattern.pop();
+    }
+    pattern
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn builds_starknet_key_patterns() {
+        let filter = EventFilter::from_payload(&json!({
+            "events": ["openzeppelin::token::erc20::ERC20::Transfer", "0x01"],
+            "keys": [null, ["0x00B", "12"]],
+        })).unwrap();
+
+        assert_eq!(filter.starknet_keys().unwrap(), vec![
+            vec![selector("Transfer"), "0x1".to_string()],
+            vec![],
+            vec!["0xb".to_string(), "0xc".to_string()],
+        ]);
+    }
+
+    #[test]
+    fn trims_trailing_wildcards() {
+        let filter = EventFilter::from_payload(&json!({"events": "Transfer", "keys": [null, []]})).unwrap();
+        assert_eq!(filter.starknet_keys().unwrap(), vec![vec![selector("Transfer")]]);
+        assert!(EventFilter::from_payload(&json!({"keys": [null]})).unwrap().starknet_keys().unwrap().is_empty());
+    }
+
+    #[test]
+    fn builds_evm_topics_from_signatures_and_abi_names() {
+        let abi = SolidityAbi::standard();
+        let filter = EventFilter::from_payload(&json!({
+            "events": "Transfer",
+            "keys": [[], "0xdAC17F958D2ee523a2206206994597C13D831ec7"],
+        })).unwrap();
+
+        let topics = filter.evm_topics(&[&abi]).unwrap();
+        assert_eq!(topics[0], vec![event_topic("Transfer(address,address,uint256)").unwrap()]);
+        assert!(topics[1].is_empty());
+        assert_eq!(topics[2], vec!["0x000000000000000000000000dac17f958d2ee523a2206206994597c13d831ec7".to_string()]);
+
+        let unknown = EventFilter::from_payload(&json!({"events": "Swap"})).unwrap();
+        assert!(unknown.evm_topics(&[&abi]).is_err());
+    }
+
+    #[test]
+    fn matches_keys_by_felt_value() {
+        let keys = vec!["0x99cd".to_string(), "0x0001".to_string(), "0xB".to_string()];
+        assert!(matches_keys(&[], &keys));
+        assert!(matches_keys(&[vec!["0x99CD".to_string()], vec![], vec!["0xb".to_string(), "0xc".to_string()]], &keys));
+        assert!(!matches_keys(&[vec![], vec!["0x2".to_string()]], &keys));
+        assert!(!matches_keys(&[vec![], vec![], vec![], vec!["0x1".to_string()]], &keys));
+    }
+
+    #[test]
+    fn rejects_malformed_filters() {
+        assert!(EventFilter::from_payload(&json!({"keys": "0x1"})).is_err());
+        assert!(EventFilter::from_payload(&json!({"events": 5})).is_err());
+        let bad_value = EventFilter::from_payload(&json!({"keys": [["not hex"]]})).unwrap();
+        assert!(bad_value.starknet_keys().is_err());
+    }
+}
@@ -0,0 +1,357 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    db::DbPool,
+    errors::AppError,
+    models::{EventSyncCursor, IndexedEvent},
+    services::{block_index::BlockIndex, metrics::metrics, rpc::{BlockId, EventData, RpcService}},
+    utils::felt::normalize_hex,
+};
+
+// Job label of the background syncer in metrics
+const SYNC_JOB: &str = "event_sync";
+
+/// SQLite-backed index of contract events. Each contract has a cursor recording the
+/// contiguous block range that is fully indexed; reads inside that range never touch the RPC.
+#[derive(Clone)]
+pub struct EventIndex {
+    pool: DbPool,
+    rpc: RpcService,
+    blocks: BlockIndex,
+    sync_chunk_blocks: u64,
+    /// Most blocks one contract advances per syncer pass, so a long history cannot hold up
+    /// every other contract
+    sync_pass_blocks: u64,
+}
+
+impl EventIndex {
+    pub fn new(pool: DbPool, rpc: RpcService, blocks: BlockIndex, sync_chunk_blocks: u64, sync_pass_blocks: u64) -> Self {
+        Self {
+            pool,
+            rpc,
+            blocks,
+            sync_chunk_blocks: sync_chunk_blocks.max(1),
+            sync_pass_blocks: sync_pass_blocks.max(1),
+        }
+    }
+
+    pub async fn cursor(&self, contract_address: &str) -> Result<Option<EventSyncCursor>, AppError> {
+        Ok(sqlx::query_as("SELECT * FROM event_sync_cursors WHERE contract_address = ?")
+            .bind(normalize_hex(contract_address))
+            .fetch_optional(&self.pool)
+            .await?)
+    }
+
+    /// Events for the range when the index covers all of it, otherwise None
+    pub async fn query_covered(
+        &self,
+        contract_address: &str,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<Option<Vec<EventData>>, AppError> {
+        let Some(cursor) = self.cursor(contract_address).await? else {
+            return Ok(None);
+        };
+
+        if (from_block as i64) < cursor.start_block || (to_block as i64) > cursor.synced_to_block {
+            return Ok(None);
+        }
+
+        Ok(Some(self.read_events(contract_address, from_block, to_block).await?))
+    }
+
+    async fn read_events(&self, contract_address: &str, from_block: u64, to_block: u64) -> Result<Vec<EventData>, AppError> {
+        let rows: Vec<IndexedEvent> = sqlx::query_as(
+            "SELECT * FROM indexed_events
+             WHERE contract_address = ? AND block_number BETWEEN ? AND ?
+             ORDER BY block_number, event_index"
+        )
+        .bind(normalize_hex(contract_address))
+        .bind(from_block as i64)
+        .bind(to_block as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        // Decoded at read time so stored events benefit from ABI and decoder updates
+        let abi = self.rpc.try_contract_abi(contract_address).await;
+        let l1_head = if rows.is_empty() { None } else { self.rpc.try_l1_accepted_head().await };
+
+        Ok(rows.into_iter().map(|row| {
+            let keys: Vec<String> = serde_json::from_str(&row.keys).unwrap_or_default();
+            let data: Vec<String> = serde_json::from_str(&row.data).unwrap_or_default();
+            let (event_name, decoded_data) = self.rpc.decode_event(abi.as_deref(), &keys, &data);
+
+            EventData {
+                contract_address: row.contract_address,
+                block_number: row.block_number as u64,
+                transaction_hash: row.transaction_hash,
+                event_index: row.event_index as u64,
+                transaction_index: None,
+                receipt_index: None,
+                keys,
+                data,
+                event_name,
+                decoded_data,
+                timestamp: chrono::DateTime::from_timestamp(row.timestamp, 0)
+                    .map(|dt| dt.to_rfc3339())
+                    .unwrap_or_default(),
+                timestamp_raw: row.timestamp as u64,
+                timestamp_estimated: row.timestamp_estimated,
+                finality_status: RpcService::block_status(l1_head, row.block_number as u64, false),
+            }
+        }).collect())
+    }
+
+    /// Stores the complete set of events for `from_block..=to_block` and widens the contract's
+    /// coverage when the range overlaps or adjoins it. Returns false (storing nothing) for a
+    /// range disjoint from existing coverage, since that would leave a gap.
+    pub async fn record(
+        &self,
+        contract_address: &str,
+        from_block: u64,
+        to_block: u64,
+        events: &[EventData],
+    ) -> Result<bool, AppError> {
+        let contract = normalize_hex(contract_address);
+        let (from, to) = (from_block as i64, to_block as i64);
+
+        let mut tx = self.pool.begin().await?;
+
+        let cursor: Option<EventSyncCursor> = sqlx::query_as("SELECT * FROM event_sync_cursors WHERE contract_address = ?")
+            .bind(&contract)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+        let (start_block, synced_to_block) = match cursor {
+            None => (from, to),
+            Some(c) if from <= c.synced_to_block + 1 && to + 1 >= c.start_block => {
+                (from.min(c.start_block), to.max(c.synced_to_block))
+            }
+            Some(_) => return Ok(false),
+        };
+
+        for event in events {
+            sqlx::query(
+                "INSERT OR IGNORE INTO indexed_events
+                 (contract_address, block_number, event_index, transaction_hash, keys, data, timestamp, timestamp_estimated)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+            )
+            .bind(&contract)
+            .bind(event.block_number as i64)
+            .bind(event.event_index as i64)
+            .bind(&event.transaction_hash)
+            .bind(serde_json::to_string(&event.keys).unwrap_or_default())
+            .bind(serde_json::to_string(&event.data).unwrap_or_default())
+            .bind(event.timestamp_raw as i64)
+            .bind(event.timestamp_estimated)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        sqlx::query(
+            "INSERT INTO event_sync_cursors (contract_address, start_block, synced_to_block, last_synced_at)
+             VALUES (?, ?, ?, CURRENT_TIMESTAMP)
+             ON CONFLICT(contract_address) DO UPDATE SET
+                start_block = excluded.start_block,
+                synced_to_block = excluded.synced_to_block,
+                last_synced_at = CURRENT_TIMESTAMP,
+                last_error = NULL"
+        )
+        .bind(&contract)
+        .bind(start_block)
+        .bind(synced_to_block)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        // Exact event timestamps double as block index entries
+        let mut headers: Vec<(u64, u64)> = events.iter()
+            .filter(|event| !event.timestamp_estimated)
+            .map(|event| (event.block_number, event.timestamp_raw))
+            .collect();
+        headers.dedup();
+        if let Err(e) = self.blocks.record(&headers).await {
+            log::warn!("Failed to store block timestamps for {}: {}", contract, e);
+        }
+
+        Ok(true)
+    }
+
+    /// Registers a contract for background syncing starting at `start_block`, with nothing
+    /// indexed yet. No-op if the contract already has a cursor. Callers should not track
+    /// from genesis unless asked to, since the syncer would then walk the whole chain.
+    pub async fn track(&self, contract_address: &str, start_block: u64) -> Result<(), AppError> {
+        sqlx::query(
+            "INSERT OR IGNORE INTO event_sync_cursors (contract_address, start_block, synced_to_block)
+             VALUES (?, ?, ?)"
+        )
+        .bind(normalize_hex(contract_address))
+        .bind(start_block as i64)
+        .bind(start_block as i64 - 1)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Extends one contract's index towards `head` by at most `sync_pass_blocks` blocks,
+    /// committing every `sync_chunk_blocks` blocks
+    pub async fn sync_contract(&self, contract_address: &str, head: u64) -> Result<usize, AppError> {
+        let Some(cursor) = self.cursor(contract_address).await? else {
+            return Ok(0);
+        };
+
+        let mut next = (cursor.synced_to_block + 1).max(0) as u64;
+        let latest = head.min(next.saturating_add(self.sync_pass_blocks - 1));
+        let mut indexed = 0;
+
+        while next <= latest {
+            let to = (next + self.sync_chunk_blocks - 1).min(latest);
+            let fetch = self.rpc.fetch_events(contract_address, next, BlockId::Number(to), &[], None).await?;
+            indexed += fetch.events.len();
+            self.record(contract_address, next, to, &fetch.events).await?;
+            next = to + 1;
+        }
+
+        Ok(indexed)
+    }
+
+    /// One pass of the background syncer over every tracked contract. Only blocks accepted
+    /// on L1 are indexed, since later ones can still be reorganized.
+    pub async fn sync_all(&self) -> Result<(), AppError> {
+        let latest = self.rpc.l1_accepted_head().await?;
+        let cursors: Vec<EventSyncCursor> = sqlx::query_as("SELECT * FROM event_sync_cursors")
+            .fetch_all(&self.pool)
+            .await?;
+
+        for cursor in cursors {
+            match self.sync_contract(&cursor.contract_address, latest).await {
+                Ok(0) => {}
+                Ok(indexed) => log::info!("Indexed {} new events for {}", indexed, cursor.contract_address),
+                Err(e) => {
+                    log::warn!("Event sync failed for {}: {}", cursor.contract_address, e);
+                    sqlx::query("UPDATE event_sync_cursors SET last_error = ? WHERE contract_address = ?")
+                        .bind(e.to_string())
+                        .bind(&cursor.contract_address)
+                        .execute(&self.pool)
+                        .await?;
+                }
+            }
+        }
+
+        let synced: Vec<(String, i64)> = sqlx::query_as("SELECT contract_address, synced_to_block FROM event_sync_cursors")
+            .fetch_all(&self.pool)
+            .await?;
+        let lags: Vec<(String, u64)> = synced.into_iter()
+            .map(|(contract, synced_to)| (contract, latest.saturating_sub(synced_to.max(0) as u64)))
+            .collect();
+        metrics().set_event_sync_lag(&lags);
+
+        Ok(())
+    }
+
+    /// Runs `sync_all` every `interval` in the background
+    pub fn spawn_syncer(self, interval: Duration) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                metrics().job_started(SYNC_JOB);
+                let started = Instant::now();
+                let result = self.sync_all().await;
+                metrics().job_finished(SYNC_JOB, started.elapsed(), result.is_ok());
+                if let Err(e) = result {
+                    log::warn!("Event index sync failed: {}", e);
+                }
+            }
+        });
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use sqlx::sqlite::SqlitePoolOptions;
+    use crate::services::rpc::BlockStatus;
+
+    async fn index() -> EventIndex {
+        // One connection, since every connection to :memory: opens its own database
+        let pool = SqlitePoolOptions::new().max_connections(1).connect("sqlite::memory:").await.unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        let rpc = RpcService::replayed("starknet-events");
+        let blocks = BlockIndex::new(pool.clone(), Arc::new(rpc.clone()));
+        EventIndex::new(pool, rpc, blocks, 10, 100)
+    }
+
+    fn event(block_number: u64, event_index: u64) -> EventData {
+        EventData {
+            contract_address: "0xa".to_string(),
+            block_number,
+            transaction_hash: format!("0x{:x}", block_number * 100 + event_index),
+            event_index,
+            transaction_index: None,
+            receipt_index: None,
+            keys: vec!["0x1".to_string()],
+            data: vec![format!("0x{:x}", event_index)],
+            event_name: String::new(),
+            decoded_data: serde_json::Value::Null,
+            timestamp: String::new(),
+            timestamp_raw: 1_700_000_000 + block_number,
+            timestamp_estimated: false,
+            finality_status: BlockStatus::AcceptedOnL2,
+        }
+    }
+
+    #[tokio::test]
+    async fn widens_coverage_with_adjoining_ranges() {
+        let index = index().await;
+        assert!(index.record("0xa", 10, 19, &[event(12, 0), event(12, 1)]).await.unwrap());
+        assert!(index.record("0x0a", 20, 29, &[event(25, 0)]).await.unwrap());
+        assert!(index.record("0xa", 5, 9, &[]).await.unwrap());
+
+        let cursor = index.cursor("0xa").await.unwrap().unwrap();
+        assert_eq!((cursor.start_block, cursor.synced_to_block), (5, 29));
+
+        let events = index.query_covered("0xa", 5, 29).await.unwrap().unwrap();
+        let positions: Vec<(u64, u64)> = events.iter().map(|e| (e.block_number, e.event_index)).collect();
+        assert_eq!(positions, vec![(12, 0), (12, 1), (25, 0)]);
+        assert_eq!(events[0].data, vec!["0x0"]);
+    }
+
+    #[tokio::test]
+    async fn refuses_ranges_that_would_leave_a_gap() {
+        let index = index().await;
+        assert!(index.record("0xa", 10, 19, &[event(12, 0)]).await.unwrap());
+        assert!(!index.record("0xa", 30, 39, &[event(35, 0)]).await.unwrap());
+
+        let cursor = index.cursor("0xa").await.unwrap().unwrap();
+        assert_eq!((cursor.start_block, cursor.synced_to_block), (10, 19));
+        assert!(index.query_covered("0xa", 30, 39).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn answers_only_ranges_inside_coverage() {
+        let index = index().await;
+        assert!(index.query_covered("0xa", 10, 19).await.unwrap().is_none());
+
+        index.record("0xa", 10, 19, &[event(12, 0), event(18, 0)]).await.unwrap();
+        assert_eq!(index.query_covered("0xa", 11, 15).await.unwrap().unwrap().len(), 1);
+        assert!(index.query_covered("0xa", 9, 15).await.unwrap().is_none());
+        assert!(index.query_covered("0xa", 15, 20).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn tracks_contracts_with_nothing_indexed() {
+        let index = index().await;
+        index.track("0xa", 10).await.unwrap();
+        index.track("0xa", 50).await.unwrap();
+
+        let cursor = index.cursor("0xa").await.unwrap().unwrap();
+        assert_eq!((cursor.start_block, cursor.synced_to_block), (10, 9));
+        assert!(index.query_covered("0xa", 10, 10).await.unwrap().is_none());
+        assert!(index.record("0xa", 10, 19, &[]).await.unwrap());
+        assert_eq!(index.query_covered("0xa", 10, 19).await.unwrap().unwrap().len(), 0);
+    }
+}
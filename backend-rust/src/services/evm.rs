@@ -0,0 +1,772 @@
+use async_trait::async_trait;
+use futures::stream::{self, StreamExt};
+use serde_json::{json, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::time::Duration;
+
+use crate::config::{Config, EvmChainConfig};
+use crate::errors::AppError;
+use crate::services::cassette::Cassette;
+use crate::services::chain::{ChainBlock, ChainClient, ChainTransaction};
+use crate::services::event_filter::EventFilter;
+use crate::services::evm_abi::{decode_output, encode_call, split_words, SolidityAbi};
+use crate::services::rpc_cache::RpcCache;
+use crate::services::rpc::{
+    BlockId, BlockStatus, ContractScan, EventData, EventFetch, Finality,
+    RpcError, RpcService, TransactionInfo, LIMIT_ERROR_CODES, MAX_EVENT_PAGES,
+};
+use crate::services::starknet_receipt::{ExecutionStatus, FeeUnit, TransactionReceipt};
+use crate::services::token::TokenMetadata;
+use crate::utils::felt::normalize_hex;
+
+// Blocks scanned by an analysis that gives no start block. EVM blocks carry far more
+// transactions than Starknet ones, so the default window is smaller.
+const DEFAULT_ANALYSIS_BLOCKS: u64 = 200;
+
+// Texts of -32000 errors refusing an eth_getLogs range as too large or too slow
+const LOG_LIMIT_MESSAGES: [&str; 9] = [
+    "limit", "too many", "too large", "exceed", "range", "response size",
+    "more than", "timed out", "timeout",
+];
+
+
+/// Generic EVM JSON-RPC client. Requests go through an `RpcService` over the chain's
+/// endpoints, which supplies failover, health tracking and batching.
+///
+/// EVM chains have no pending block to scan, so `pending` reads stop at the latest block;
+/// the `finalized` tag takes the place of Starknet's L1-accepted head.
+#[derive(Clone)]
+pub struct EvmClient {
+    chain: String,
+    name: String,
+    native_symbol: String,
+    rpc: RpcService,
+    workers: usize,
+    batch_size: usize,
+    log_chunk: u64,
+    /// Attempts per block of a scan, as for Starknet's `block_retries`
+    block_retries: u32,
+}
+
+impl EvmClient {
+    /// `cache`, when given, is shared with the other chains under this chain's namespace
+    pub fn new(chain: &EvmChainConfig, config: &Config, cache: Option<&RpcCache>) -> Self {
+        let id = chain.chain.to_lowercase();
+        let mut rpc = RpcService::new(
+            chain.rpc_providers.clone(),
+            config.rpc_failure_threshold,
+            Duration::from_secs(config.rpc_circuit_cooldown_secs),
+        )
+        .with_batch_size(config.rpc_batch_size)
+        .with_error_classifier(evm_error_is_fault);
+        if let Some(cache) = cache {
+            rpc = rpc.with_response_cache(cache.scoped(&id));
+        }
+        if let Some(cassette) = Cassette::from_config(config, &id) {
+            rpc = rpc.with_cassette(cassette);
+        }
+
+        Self {
+            chain: id,
+            name: chain.name.clone(),
+            native_symbol: chain.native_symbol.clone(),
+            rpc,
+            workers: config.analysis_workers.max(1),
+            batch_size: config.rpc_batch_size.max(1),
+            log_chunk: config.evm_log_chunk_blocks.max(1),
+            block_retries: config.block_fetch_retries.max(1),
+        }
+    }
+
+    /// Number of the `finalized` block, if the chain reports one
+    pub async fn finalized_head(&self) -> Option<u64> {
+        match self.rpc.rpc_call("eth_getBlockByNumber", json!(["finalized", false])).await {
+            Ok(block) => {
+                let head = quantity(&block, "number").map(|n| n as u64);
+                if let Some(head) = head {
+                    self.rpc.note_finalized_head(head);
+                }
+                head
+            }
+            Err(e) => {
+                log::debug!("{} has no finalized block: {}", self.chain, e);
+                None
+            }
+        }
+    }
+
+    fn block_status(finalized: Option<u64>, block_number: u64) -> BlockStatus {
+        match finalized {
+            Some(head) if block_number <= head => BlockStatus::AcceptedOnL1,
+            _ => BlockStatus::AcceptedOnL2,
+        }
+    }
+
+    fn parse_transaction(tx: &Value) -> ChainTransaction {
+        let field = |name: &str| tx.get(name).and_then(|v| v.as_str()).map(str::to_lowercase);
+        let gas = quantity(tx, "gas").unwrap_or(0);
+        let price = quantity(tx, "maxFeePerGas")
+            .or_else(|| quantity(tx, "gasPrice"))
+            .unwrap_or(0);
+
+        ChainTransaction {
+            transaction_hash: field("hash").unwrap_or_default(),
+            sender_address: field("from"),
+            to_address: field("to"),
+            max_fee: gas.saturating_mul(price),
+            fee_unit: FeeUnit::Wei,
+            tx_type: match quantity(tx, "type").unwrap_or(0) {
+                0 => "LEGACY".to_string(),
+                1 => "EIP2930".to_string(),
+                2 => "EIP1559".to_string(),
+                3 => "EIP4844".to_string(),
+                4 => "EIP7702".to_string(),
+                other => format!("{:#x}", other),
+            },
+        }
+    }
+
+    // Gas used at the effective price, plus the L1 data fee on OP-stack chains
+    fn parse_receipt(receipt: &Value) -> Option<TransactionReceipt> {
+        let gas_used = quantity(receipt, "gasUsed")?;
+        let price = quantity(receipt, "effectiveGasPrice").unwrap_or(0);
+        let l1_fee = quantity(receipt, "l1Fee").unwrap_or(0);
+
+        Some(TransactionReceipt {
+            transaction_hash: receipt.get("transactionHash")?.as_str()?.to_lowercase(),
+            actual_fee: gas_used.saturating_mul(price).saturating_add(l1_fee),
+            fee_unit: FeeUnit::Wei,
+            execution_status: match quantity(receipt, "status") {
+                Some(0) => ExecutionStatus::Reverted,
+                _ => ExecutionStatus::Succeeded,
+            },
+            revert_reason: None,
+        })
+    }
+
+    fn parse_block(block: &Value, receipts: &[Value], finalized: Option<u64>) -> ChainBlock {
+        let block_number = quantity(block, "number").unwrap_or(0) as u64;
+        ChainBlock {
+            block_number,
+            timestamp: quantity(block, "timestamp").unwrap_or(0) as u64,
+            status: Self::block_status(finalized, block_number),
+            transactions: block.get("transactions")
+                .and_then(|v| v.as_array())
+                .into_iter()
+                .flatten()
+                .map(Self::parse_transaction)
+                .collect(),
+            receipts: receipts.iter()
+                .filter_map(Self::parse_receipt)
+                .map(|receipt| (normalize_hex(&receipt.transaction_hash), receipt))
+                .collect(),
+        }
+    }
+
+    // Full blocks for a batch of numbers. Blocks that fail are requested again with
+    // exponential backoff, up to `block_retries` attempts in all.
+    async fn fetch_blocks(&self, block_numbers: &[u64]) -> Vec<(u64, Result<Value, AppError>)> {
+        let mut results = self.fetch_block_batch(block_numbers).await;
+        let mut attempt = 1;
+        loop {
+            let failed: Vec<u64> = results.iter()
+                .filter(|(_, result)| result.is_err())
+                .map(|(n, _)| *n)
+                .collect();
+            if failed.is_empty() || attempt >= self.block_retries {
+                return results;
+            }
+
+            log::warn!("{} {} block fetches failed on attempt {}, retrying", failed.len(), self.chain, attempt);
+            tokio::time::sleep(Duration::from_millis(250 * 2u64.pow(attempt - 1))).await;
+            for (n, result) in self.fetch_block_batch(&failed).await {
+                if let Some((_, slot)) = results.iter_mut().find(|(m, _)| *m == n) {
+                    *slot = result;
+                }
+            }
+            attempt += 1;
+        }
+    }
+
+    // One attempt at a batch of blocks; a null result means the block does not exist yet
+    async fn fetch_block_batch(&self, block_numbers: &[u64]) -> Vec<(u64, Result<Value, AppError>)> {
+        let calls: Vec<(&str, Value)> = block_numbers.iter()
+            .map(|n| ("eth_getBlockByNumber", json!([format!("{:#x}", n), true])))
+            .collect();
+
+        self.rpc.rpc_batch(&calls)
+            .await
+            .into_iter()
+            .zip(block_numbers)
+            .map(|(result, &n)| (n, result.and_then(|block| match block {
+                Value::Null => Err(AppError::NotFound(format!("Block {} not found", n))),
+                block => Ok(block),
+            })))
+            .collect()
+    }
+
+    /// Logs of a contract over a block range matching a `topics` pattern (empty for all
+    /// logs), requested in windows of up to `log_chunk` blocks. A window the provider
+    /// refuses as too large is halved and retried; after a success the window grows back.
+    /// At most `max_windows` windows are fetched; the flag says whether the whole range
+    /// was covered.
+    pub async fn fetch_logs(
+        &self,
+        contract_address: &str,
+        from_block: u64,
+        to_block: u64,
+        topics: &[Vec<String>],
+        max_windows: usize,
+    ) -> Result<(Vec<Value>, bool), AppError> {
+        // An empty position matches any topic
+        let topics: Vec<Value> = topics.iter()
+            .map(|values| if values.is_empty() { Value::Null } else { json!(values) })
+            .collect();
+        let mut logs = Vec::new();
+        let mut window = self.log_chunk;
+        let mut start = from_block;
+        let mut windows = 0;
+
+        while start <= to_block {
+            if windows >= max_windows {
+                log::warn!(
+                    "Stopped {} log fetch for {} at block {} after {} windows",
+                    self.chain, contract_address, start, windows
+                );
+                return Ok((logs, false));
+            }
+
+            let end = start.saturating_add(window - 1).min(to_block);
+            let mut filter = json!({
+                "address": contract_address,
+                "fromBlock": format!("{:#x}", start),
+                "toBlock": format!("{:#x}", end),
+            });
+            if !topics.is_empty() {
+                filter["topics"] = json!(topics);
+            }
+            let result = self.rpc.rpc_call("eth_getLogs", json!([filter])).await;
+
+            match result {
+                Ok(Value::Array(page)) => {
+                    logs.extend(page);
+                    windows += 1;
+                    start = end + 1;
+                    window = window.saturating_mul(2).min(self.log_chunk);
+                }
+                Ok(_) => return Err(AppError::BadRequest("Invalid eth_getLogs response".to_string())),
+                Err(e) if end > start && is_log_limit_error(&e) => {
+                    window = (end - start).div_ceil(2);
+                    log::info!(
+                        "{} refused logs for blocks {}-{} ({}), retrying with {} blocks",
+                        self.chain, start, end, e, window
+                    );
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok((logs, true))
+    }
+}
+
+#[async_trait]
+impl ChainClient for EvmClient {
+    fn chain(&self) -> &str {
+        &self.chain
+    }
+
+    async fn block_number(&self) -> Result<u64, AppError> {
+        let result = self.rpc.rpc_call("eth_blockNumber", json!([])).await?;
+        result.as_str()
+            .and_then(parse_quantity)
+            .map(|n| n as u64)
+            .ok_or_else(|| AppError::BadRequest("Invalid block number format".to_string()))
+    }
+
+    async fn block_timestamps(&self, block_numbers: &[u64]) -> HashMap<u64, u64> {
+        let calls: Vec<(&str, Value)> = block_numbers.iter()
+            .map(|n| ("eth_getBlockByNumber", json!([format!("{:#x}", n), false])))
+            .collect();
+
+        self.rpc.rpc_batch(&calls)
+            .await
+            .into_iter()
+            .zip(block_numbers)
+            .filter_map(|(result, &n)| Some((n, quantity(&result.ok()?, "timestamp")? as u64)))
+            .collect()
+    }
+
+    async fn head_block(&self, finality: Finality) -> Result<BlockId, AppError> {
+        match finality {
+            Finality::Pending | Finality::Latest => Ok(BlockId::Number(self.block_number().await?)),
+            Finality::L1Accepted => self.finalized_head()
+                .await
+                .map(BlockId::Number)
+                .ok_or_else(|| AppError::BadRequest(format!("{} does not report a finalized block", self.name))),
+        }
+    }
+
+    async fn get_block(&self, block_number: u64) -> Result<ChainBlock, AppError> {
+        let tag = format!("{:#x}", block_number);
+        let mut results = self.rpc.rpc_batch(&[
+            ("eth_getBlockByNumber", json!([tag, true])),
+            ("eth_getBlockReceipts", json!([tag])),
+        ]).await.into_iter();
+
+        let block = match results.next() {
+            Some(Ok(Value::Null)) => return Err(AppError::NotFound(format!("Block {} not found", block_number))),
+            Some(result) => result?,
+            None => return Err(AppError::BadRequest("Incomplete batch response".to_string())),
+        };
+        // Without receipts the block is still usable; its transactions count as not executed
+        let receipts = match results.next() {
+            Some(Ok(Value::Array(receipts))) => receipts,
+            Some(Err(e)) => {
+                log::warn!("No receipts for {} block {}: {}", self.chain, block_number, e);
+                vec![]
+            }
+            _ => vec![],
+        };
+
+        let finalized = self.finalized_head().await;
+        Ok(Self::parse_block(&block, &receipts, finalized))
+    }
+
+    async fn get_events(
+        &self,
+        contract_address: &str,
+        from_block: u64,
+        to_block: BlockId,
+        abi: Option<&Value>,
+        filter: &EventFilter,
+    ) -> Result<EventFetch, AppError> {
+        // The supplied ABI first, then the standard token events
+        let abi = abi.map(SolidityAbi::from_json).transpose()?;
+        let standard = SolidityAbi::standard();
+        let abis: Vec<&SolidityAbi> = abi.iter().chain([&standard]).collect();
+        let topics = filter.evm_topics(&abis)?;
+
+        let (logs, complete) = self.fetch_logs(contract_address, from_block, to_block.number(), &topics, MAX_EVENT_PAGES).await?;
+        let logs: Vec<&Value> = logs.iter()
+            .filter(|log| log.get("removed").and_then(|v| v.as_bool()) != Some(true))
+            .collect();
+
+        // Newer nodes put the timestamp on each log; otherwise headers are fetched
+        let missing: BTreeSet<u64> = logs.iter()
+            .filter(|log| log.get("blockTimestamp").is_none())
+            .filter_map(|log| quantity(log, "blockNumber"))
+            .map(|n| n as u64)
+            .collect();
+        let mut timestamps = self.block_timestamps(&missing.into_iter().collect::<Vec<_>>()).await;
+        for log in &logs {
+            if let (Some(block), Some(ts)) = (quantity(log, "blockNumber"), quantity(log, "blockTimestamp")) {
+                timestamps.insert(block as u64, ts as u64);
+            }
+        }
+        let mut known: Vec<(u64, u64)> = timestamps.iter().map(|(&block, &ts)| (block, ts)).collect();
+        known.sort_unstable();
+
+        let finalized = self.finalized_head().await;
+        let events = logs.into_iter()
+            .map(|log| {
+                let block_number = quantity(log, "blockNumber").unwrap_or(0) as u64;
+                let (timestamp, timestamp_estimated) = match timestamps.get(&block_number) {
+                    Some(&ts) => (ts, false),
+                    None => (RpcService::interpolate_timestamp(&known, block_number), true),
+                };
+
+                let topics: Vec<String> = log.get("topics")
+                    .and_then(|v| v.as_array())
+                    .map(|topics| topics.iter().filter_map(|t| t.as_str().map(String::from)).collect())
+                    .unwrap_or_default();
+                let data = log.get("data").and_then(|v| v.as_str()).unwrap_or("0x");
+                let (event_name, decoded_data) = abi.as_ref()
+                    .and_then(|abi| abi.decode_log(&topics, data))
+                    .or_else(|| standard.decode_log(&topics, data))
+                    .unwrap_or_else(|| ("Unknown Event".to_string(), json!({})));
+
+                EventData {
+                    contract_address: log.get("address")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or(contract_address)
+                        .to_lowercase(),
+                    block_number,
+                    transaction_hash: log.get("transactionHash")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("")
+                        .to_string(),
+                    event_index: quantity(log, "logIndex").unwrap_or(0) as u64,
+                    transaction_index: quantity(log, "transactionIndex").map(|i| i as u64),
+                    receipt_index: None,
+                    keys: topics,
+                    data: split_words(data),
+                    event_name,
+                    decoded_data,
+                    timestamp: chrono::DateTime::from_timestamp(timestamp as i64, 0)
+                        .map(|dt| dt.to_rfc3339())
+                        .unwrap_or_default(),
+                    timestamp_raw: timestamp,
+                    timestamp_estimated,
+                    finality_status: Self::block_status(finalized, block_number),
+                }
+            })
+            .collect();
+
+        Ok(EventFetch { events, complete })
+    }
+
+    async fn call(&self, contract_address: &str, function: &str, args: &Value, block: BlockId) -> Result<Value, AppError> {
+        let calldata = if function.starts_with("0x") {
+            function.to_string()
+        } else {
+            encode_call(function, args)?
+        };
+
+        let result = self.rpc.rpc_call("eth_call", json!([
+            {"to": contract_address, "data": calldata},
+            format!("{:#x}", block.number()),
+        ])).await?;
+        let raw = result.as_str().unwrap_or("0x");
+
+        Ok(json!({
+            "contract_address": contract_address,
+            "function": function,
+            "block_number": block.number(),
+            "calldata": calldata,
+            "result": split_words(raw),
+            "raw_result": raw,
+        }))
+    }
+
+    async fn scan_contract(
+        &self,
+        contract_address: &str,
+        from_block: Option<u64>,
+        to_block: Option<u64>,
+        finality: Finality,
+    ) -> Result<ContractScan, AppError> {
+        let current_block = self.block_number().await?;
+        let head = self.head_block(finality).await?.number();
+        let from_block = from_block.unwrap_or_else(|| head.saturating_sub(DEFAULT_ANALYSIS_BLOCKS));
+        let to_block = to_block.unwrap_or(head).min(head);
+
+        if from_block > to_block {
+            return Err(AppError::BadRequest(format!(
+                "Invalid block range: {} is after {}", from_block, to_block
+            )));
+        }
+
+        let contract = contract_address.to_lowercase();
+        let finalized = match finality {
+            Finality::L1Accepted => Some(head),
+            Finality::Pending | Finality::Latest => self.finalized_head().await,
+        };
+
+        log::info!(
+            "Analyzing {} blocks of {} for {} with {} workers",
+            to_block - from_block + 1, self.chain, contract, self.workers
+        );
+
+        let block_numbers: Vec<u64> = (from_block..=to_block).rev().collect();
+        let chunks: Vec<Vec<u64>> = block_numbers.chunks(self.batch_size).map(<[u64]>::to_vec).collect();
+        let mut blocks = stream::iter(chunks)
+            .map(|chunk| async move { self.fetch_blocks(&chunk).await })
+            .buffer_unordered(self.workers)
+            .flat_map(stream::iter);
+
+        let mut transactions = Vec::new();
+        let mut failed_blocks = Vec::new();
+        let mut blocks_analyzed = 0usize;
+
+        while let Some((block_num, result)) = blocks.next().await {
+            let block = match result {
+                Ok(block) => Self::parse_block(&block, &[], finalized),
+                Err(e) => {
+                    log::warn!("Giving up on {} block {}: {}", self.chain, block_num, e);
+                    failed_blocks.push(block_num);
+                    continue;
+                }
+            };
+            blocks_analyzed += 1;
+
+            for tx in block.transactions {
+                let involved = tx.sender_address.as_deref() == Some(contract.as_str())
+                    || tx.to_address.as_deref() == Some(contract.as_str());
+                if involved {
+                    transactions.push((block.block_number, block.timestamp, block.status, tx));
+                }
+            }
+        }
+
+        // Receipts only for the matching transactions, not for every scanned block
+        let calls: Vec<(&str, Value)> = transactions.iter()
+            .map(|(_, _, _, tx)| ("eth_getTransactionReceipt", json!([tx.transaction_hash])))
+            .collect();
+        let receipts: Vec<Option<TransactionReceipt>> = self.rpc.rpc_batch(&calls)
+            .await
+            .into_iter()
+            .map(|result| result.ok().and_then(|receipt| Self::parse_receipt(&receipt)))
+            .collect();
+
+        let transactions = transactions.into_iter()
+            .zip(&receipts)
+            .map(|((block_number, timestamp, status, tx), receipt)| TransactionInfo {
+                block_number,
+                transaction_hash: tx.transaction_hash,
+                sender_address: tx.sender_address.unwrap_or_default(),
+                contract_address: contract.clone(),
+                max_fee: format!("{:#x}", tx.max_fee),
+                actual_fee: receipt.as_ref().map(|r| format!("{:#x}", r.actual_fee)),
+                fee_unit: tx.fee_unit,
+                execution_status: receipt.as_ref().map(|r| r.execution_status),
+                revert_reason: None,
+                tx_type: tx.tx_type,
+                timestamp,
+                finality_status: status,
+            })
+            .collect();
+
+        Ok(ContractScan {
+            contract_address: contract,
+            transactions,
+            receipts: receipts.into_iter().flatten().collect(),
+            blocks_analyzed,
+            failed_blocks,
+            current_block,
+            finality,
+            from_block,
+            to_block,
+            native_symbol: self.native_symbol.clone(),
+        })
+    }
+
+    fn native_symbol(&self) -> &str {
+        &self.native_symbol
+    }
+
+    async fn token_metadata(&self, contract_address: &str) -> Result<TokenMetadata, AppError> {
+        let calls: Vec<(&str, Value)> = ["name()", "symbol()", "decimals()"].iter()
+            .map(|signature| Ok(("eth_call", json!([
+                {"to": contract_address, "data": encode_call(signature, &json!([]))?},
+                "latest",
+            ]))))
+            .collect::<Result<_, AppError>>()?;
+
+        let mut outputs: Vec<Option<String>> = Vec::with_capacity(calls.len());
+        for result in self.rpc.rpc_batch(&calls).await {
+            outputs.push(match result {
+                Ok(value) => value.as_str().map(String::from),
+                // The getter reverted or does not exist: the token does not expose it
+                Err(AppError::Rpc(..)) => None,
+                // No answer at all; the metadata is unknown rather than partial
+                Err(e) => return Err(e),
+            });
+        }
+        // Some early tokens return name and symbol as a NUL-padded bytes32
+        let text = |i: usize| outputs[i].as_deref().and_then(|data| {
+            decode_output("string", data).and_then(|v| v.as_str().map(String::from))
+                .or_else(|| bytes32_text(data))
+                .filter(|text| !text.is_empty())
+        });
+
+        Ok(TokenMetadata {
+            chain: self.chain.clone(),
+            address: contract_address.to_lowercase(),
+            name: text(0),
+            symbol: text(1),
+            decimals: outputs[2].as_deref()
+                .and_then(|data| decode_output("uint8", data))
+                .and_then(|v| v.as_u64())
+                .and_then(|decimals| u8::try_from(decimals).ok()),
+        })
+    }
+}
+
+// Text of a bytes32 return value, up to its first NUL
+fn bytes32_text(data: &str) -> Option<String> {
+    let word = decode_output("bytes32", data)?;
+    let bytes: Vec<u8> = (0..32)
+        .map(|i| u8::from_str_radix(word.as_str()?.get(2 + 2 * i..4 + 2 * i)?, 16).ok())
+        .collect::<Option<_>>()?;
+    let text = String::from_utf8(bytes.into_iter().take_while(|b| *b != 0).collect()).ok()?;
+    (!text.chars().any(char::is_control)).then_some(text)
+}
+
+// Geth-style server errors (-32000: execution reverted, header not found, nonce too low),
+// VM execution errors (-32015), revert data (3) and refusals of oversized requests are the
+// node's answer. Other protocol errors, such as internal errors and unsupported methods,
+// are provider faults.
+fn evm_error_is_fault(error: &RpcError) -> bool {
+    match error.code {
+        -32000 | -32015 | 3 => false,
+        code => code < 0 && !LIMIT_ERROR_CODES.contains(&code),
+    }
+}
+
+// A provider refused an eth_getLogs range as matching too many logs or blocks, or as too
+// slow to serve. Such a range is split rather than reported as a failure. Geth-style nodes
+// use the generic -32000 code and some providers invalid params for it, so those count
+// only when the message says so.
+fn is_log_limit_error(error: &AppError) -> bool {
+    let mentions = |message: &str, patterns: &[&str]| {
+        let message = message.to_lowercase();
+        patterns.iter().any(|pattern| message.contains(pattern))
+    };
+    match error {
+        AppError::Rpc(-32000, message) => mentions(message, &LOG_LIMIT_MESSAGES),
+        AppError::Rpc(-32602, message) => mentions(message, &["range", "limit"]),
+        AppError::Rpc(code, _) => LIMIT_ERROR_CODES.contains(code),
+        _ => false,
+    }
+}
+
+fn parse_quantity(value: &str) -> Option<u128> {
+    u128::from_str_radix(value.trim_start_matches("0x"), 16).ok()
+}
+
+// A hex quantity field of a JSON-RPC object
+fn quantity(value: &Value, field: &str) -> Option<u128> {
+    value.get(field).and_then(|v| v.as_str()).and_then(parse_quantity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RpcProviderConfig;
+
+    // A client of a chain answering only from the recordings under tests/fixtures
+    fn replayed(log_chunk: u64) -> EvmClient {
+        let mut config = Config::from_env();
+        config.rpc_cassette_mode = "replay".to_string();
+        config.rpc_cassette_dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/cassettes").to_string();
+        config.evm_log_chunk_blocks = log_chunk;
+        let chain = EvmChainConfig {
+            chain: "testchain".to_string(),
+            name: "Test chain".to_string(),
+            native_symbol: "ETH".to_string(),
+            rpc_providers: vec![RpcProviderConfig {
+                url: "http://replay.invalid".to_string(),
+                weight: 1,
+                timeout_secs: 10,
+                api_key: None,
+            }],
+        };
+        EvmClient::new(&chain, &config, None)
+    }
+
+    fn blocks(logs: &[Value]) -> Vec<u128> {
+        logs.iter().filter_map(|log| quantity(log, "blockNumber")).collect()
+    }
+
+    #[test]
+    fn recognizes_log_limit_refusals() {
+        let limit = |code, message: &str| is_log_limit_error(&AppError::Rpc(code, message.to_string()));
+        assert!(limit(-32005, "query returned more than 10000 results"));
+        assert!(limit(-32000, "exceed maximum block range: 5000"));
+        assert!(limit(-32000, "query timeout exceeded"));
+        assert!(limit(-32602, "Log response size exceeded. Use a 2K block range"));
+
+        assert!(!limit(-32000, "execution reverted"));
+        assert!(!limit(-32602, "invalid argument 0: hex string has odd length"));
+        assert!(!is_log_limit_error(&AppError::BadRequest("range too large".to_string())));
+    }
+
+    // The recorded node refuses ranges over 4 blocks for 0xaaa (-32000) and 0xccc (-32602),
+    // and every range for 0xbad as invalid params
+    #[tokio::test]
+    async fn splits_refused_log_ranges() {
+        let client = replayed(10);
+        for contract in ["0xaaa", "0xccc"] {
+            let (logs, complete) = client.fetch_logs(contract, 0, 9, &[], 100).await.expect("replays");
+            assert!(complete);
+            assert_eq!(blocks(&logs), (0..10).collect::<Vec<_>>(), "{}", contract);
+        }
+    }
+
+    #[tokio::test]
+    async fn fails_invalid_log_requests_without_splitting() {
+        let error = replayed(10).fetch_logs("0xbad", 0, 9, &[], 100).await.expect_err("invalid params");
+        assert!(matches!(error, AppError::Rpc(-32602, _)), "{}", error);
+    }
+
+    // A live node on a local port whose block 3 fails once and block 4 always; returns the
+    // client and the number of requests per block
+    async fn flaky_node() -> (EvmClient, std::sync::Arc<std::sync::Mutex<HashMap<u64, usize>>>) {
+        use actix_web::{web, App, HttpResponse, HttpServer};
+        use std::sync::{Arc, Mutex};
+
+        let hits: Arc<Mutex<HashMap<u64, usize>>> = Arc::default();
+        let answer = |hits: &Mutex<HashMap<u64, usize>>, call: &Value| {
+            let (method, params) = (call["method"].as_str().unwrap_or_default(), &call["params"]);
+            let result = match (method, params[0].as_str().and_then(|n| u64::from_str_radix(n.trim_start_matches("0x"), 16).ok())) {
+                ("eth_blockNumber", _) => Ok(json!("0x4")),
+                ("eth_getBlockByNumber", Some(n)) => {
+                    let attempts = {
+                        let mut hits = hits.lock().unwrap();
+                        let count = hits.entry(n).or_default();
+                        *count += 1;
+                        *count
+                    };
+                    match n {
+                        3 if attempts == 1 => Err("header not found"),
+                        4 => Err("header not found"),
+                        _ => Ok(json!({
+                            "number": format!("{:#x}", n),
+                            "timestamp": "0x64",
+                            "transactions": [{"hash": format!("0x{:x}", 0xf0 + n), "from": "0xabc", "to": "0xdef", "gas": "0x1", "gasPrice": "0x1"}],
+                        })),
+                    }
+                }
+                ("eth_getTransactionReceipt", _) => Ok(json!({"transactionHash": params[0], "gasUsed": "0x1", "status": "0x1"})),
+                _ => Ok(Value::Null),
+            };
+            match result {
+                Ok(result) => json!({"jsonrpc": "2.0", "id": call["id"], "result": result}),
+                Err(message) => json!({"jsonrpc": "2.0", "id": call["id"], "error": {"code": -32000, "message": message}}),
+            }
+        };
+
+        let state = hits.clone();
+        let server = HttpServer::new(move || {
+            let state = state.clone();
+            App::new().default_service(web::to(move |body: web::Json<Value>| {
+                let response = match &*body {
+                    Value::Array(calls) => Value::Array(calls.iter().map(|call| answer(&state, call)).collect()),
+                    call => answer(&state, call),
+                };
+                async move { HttpResponse::Ok().json(response) }
+            }))
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .expect("local port");
+        let url = format!("http://{}", server.addrs()[0]);
+        actix_web::rt::spawn(server.run());
+
+        let mut config = Config::from_env();
+        config.block_fetch_retries = 3;
+        let chain = EvmChainConfig {
+            chain: "testchain".to_string(),
+            name: "Test chain".to_string(),
+            native_symbol: "ETH".to_string(),
+            rpc_providers: vec![RpcProviderConfig { url, weight: 1, timeout_secs: 10, api_key: None }],
+        };
+        (EvmClient::new(&chain, &config, None), hits)
+    }
+
+    #[actix_web::test]
+    async fn retries_failed_blocks_of_a_scan() {
+        let (client, hits) = flaky_node().await;
+        let scan = client.scan_contract("0xabc", Some(2), None, Finality::Latest).await.expect("scans");
+
+        assert_eq!(scan.blocks_analyzed, 2);
+        assert_eq!(scan.failed_blocks, vec![4]);
+        let mut found: Vec<u64> = scan.transactions.iter().map(|tx| tx.block_number).collect();
+        found.sort();
+        assert_eq!(found, vec![2, 3]);
+
+        let hits = hits.lock().unwrap();
+        assert_eq!((hits[&2], hits[&3], hits[&4]), (1, 2, 3));
+    }
+}
@@ -0,0 +1,576 @@
+use num_bigint::{BigInt, BigUint, Sign};
+use num_traits::{Num, ToPrimitive, Zero};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use sha3::{Digest, Keccak256};
+
+use crate::errors::AppError;
+
+// Bytes in one ABI word
+const WORD: usize = 32;
+
+/// One parameter of a Solidity ABI entry
+#[derive(Debug, Clone, Deserialize)]
+pub struct AbiParam {
+    #[serde(default)]
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default)]
+    pub indexed: bool,
+    /// Members of a `tuple` type
+    #[serde(default)]
+    pub components: Vec<AbiParam>,
+}
+
+// ERC-20 and ERC-721 events, decoded even when no ABI is supplied
+const STANDARD_EVENTS: &str = r#"[
+    {"type": "event", "name": "Transfer", "inputs": [
+        {"name": "from", "type": "address", "indexed": true},
+        {"name": "to", "type": "address", "indexed": true},
+        {"name": "value", "type": "uint256", "indexed": false}]},
+    {"type": "event", "name": "Transfer", "inputs": [
+        {"name": "from", "type": "address", "indexed": true},
+        {"name": "to", "type": "address", "indexed": true},
+        {"name": "tokenId", "type": "uint256", "indexed": true}]},
+    {"type": "event", "name": "Approval", "inputs": [
+        {"name": "owner", "type": "address", "indexed": true},
+        {"name": "spender", "type": "address", "indexed": true},
+        {"name": "value", "type": "uint256", "indexed": false}]},
+    {"type": "event", "name": "Approval", "inputs": [
+        {"name": "owner", "type": "address", "indexed": true},
+        {"name": "approved", "type": "address", "indexed": true},
+        {"name": "tokenId", "type": "uint256", "indexed": true}]},
+    {"type": "event", "name": "ApprovalForAll", "inputs": [
+        {"name": "owner", "type": "address", "indexed": true},
+        {"name": "operator", "type": "address", "indexed": true},
+        {"name": "approved", "type": "bool", "indexed": false}]}
+]"#;
+
+#[derive(Debug, Deserialize)]
+struct AbiEntry {
+    #[serde(rename = "type", default)]
+    kind: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    inputs: Vec<AbiParam>,
+    #[serde(default)]
+    anonymous: bool,
+}
+
+#[derive(Debug, Clone)]
+struct EvmEvent {
+    name: String,
+    /// keccak256 of the canonical signature: `topics[0]` of every non-anonymous log
+    topic: String,
+    anonymous: bool,
+    inputs: Vec<(AbiParam, SolType)>,
+}
+
+/// Events of a Solidity contract ABI, used to decode its logs
+#[derive(Debug, Clone, Default)]
+pub struct SolidityAbi {
+    events: Vec<EvmEvent>,
+}
+
+impl SolidityAbi {
+    /// Accepts an ABI array, a compiler artifact with an `abi` field, or either as a JSON string
+    pub fn from_json(value: &Value) -> Result<Self, AppError> {
+        let invalid = |e: String| AppError::BadRequest(format!("Invalid Solidity ABI: {}", e));
+        let parsed;
+        let value = match value {
+            Value::String(text) => {
+                parsed = serde_json::from_str::<Value>(text).map_err(|e| invalid(e.to_string()))?;
+                &parsed
+            }
+            other => other,
+        };
+        let entries = value.get("abi").unwrap_or(value);
+        let entries: Vec<AbiEntry> = serde_json::from_value(entries.clone()).map_err(|e| invalid(e.to_string()))?;
+
+        let mut events = Vec::new();
+        for entry in entries.into_iter().filter(|entry| entry.kind == "event") {
+            let mut inputs = Vec::with_capacity(entry.inputs.len());
+            for param in entry.inputs {
+                let ty = SolType::parse(&param.kind, &param.components)
+                    .ok_or_else(|| invalid(format!("unsupported type '{}' in event {}", param.kind, entry.name)))?;
+                inputs.push((param, ty));
+            }
+            let signature = format!(
+                "{}({})",
+                entry.name,
+                inputs.iter().map(|(_, ty)| ty.canonical()).collect::<Vec<_>>().join(",")
+            );
+            events.push(EvmEvent {
+                topic: format!("0x{}", to_hex(&Keccak256::digest(signature.as_bytes()))),
+                name: entry.name,
+                anonymous: entry.anonymous,
+                inputs,
+            });
+        }
+
+        Ok(Self { events })
+    }
+
+    /// Transfer, Approval and ApprovalForAll of ERC-20 and ERC-721
+    pub fn standard() -> Self {
+        Self::from_json(&Value::String(STANDARD_EVENTS.to_string())).expect("valid standard ABI")
+    }
+
+    /// `topics[0]` of the non-anonymous events called `name`
+    pub fn event_topics(&self, name: &str) -> Vec<String> {
+        self.events.iter()
+            .filter(|event| !event.anonymous && event.name == name)
+            .map(|event| event.topic.clone())
+            .collect()
+    }
+
+    /// Name and decoded parameters of a log. Events sharing a signature but differing in
+    /// which parameters are indexed (ERC-20 and ERC-721 `Transfer`) are told apart by the
+    /// number of topics. Anonymous events are tried when no signature matches.
+    pub fn decode_log(&self, topics: &[String], data: &str) -> Option<(String, Value)> {
+        let data = from_hex(data.trim_start_matches("0x"))?;
+        let topic0 = topics.first().map(|t| t.to_lowercase());
+
+        let named = self.events.iter()
+            .filter(|event| !event.anonymous && topic0.as_deref() == Some(event.topic.as_str()))
+            .find_map(|event| event.decode(&topics[1..], &data));
+        named.or_else(|| {
+            self.events.iter()
+                .filter(|event| event.anonymous)
+                .find_map(|event| event.decode(topics, &data))
+        })
+    }
+}
+
+impl EvmEvent {
+    // Indexed parameters come from `topics` (without the signature topic), the rest from `data`
+    fn decode(&self, topics: &[String], data: &[u8]) -> Option<(String, Value)> {
+        let indexed = self.inputs.iter().filter(|(param, _)| param.indexed).count();
+        if indexed != topics.len() {
+            return None;
+        }
+
+        let body: Vec<&SolType> = self.inputs.iter()
+            .filter(|(param, _)| !param.indexed)
+            .map(|(_, ty)| ty)
+            .collect();
+        let mut body = decode_sequence(&body, data)?.into_iter();
+        let mut topics = topics.iter();
+
+        let mut decoded = Map::new();
+        for (position, (param, ty)) in self.inputs.iter().enumerate() {
+            let value = if param.indexed {
+                let topic = topics.next()?;
+                // Dynamic indexed values are stored as their hash and cannot be recovered
+                if ty.is_dynamic() || matches!(ty, SolType::Tuple(_) | SolType::FixedArray(..)) {
+                    json!(topic)
+                } else {
+                    ty.decode(&from_hex(topic.trim_start_matches("0x"))?)?
+                }
+            } else {
+                body.next()?
+            };
+            decoded.insert(param_name(&param.name, position), value);
+        }
+
+        Some((self.name.clone(), Value::Object(decoded)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum SolType {
+    Address,
+    Bool,
+    Uint(u64),
+    Int(u64),
+    FixedBytes(usize),
+    Bytes,
+    String,
+    Array(Box<SolType>),
+    FixedArray(Box<SolType>, usize),
+    Tuple(Vec<(String, SolType)>),
+}
+
+impl SolType {
+    fn parse(kind: &str, components: &[AbiParam]) -> Option<Self> {
+        if let Some(inner) = kind.strip_suffix(']') {
+            let open = inner.rfind('[')?;
+            let element = Box::new(Self::parse(&inner[..open], components)?);
+            return match &inner[open + 1..] {
+                "" => Some(SolType::Array(element)),
+                size => Some(SolType::FixedArray(element, size.parse().ok()?)),
+            };
+        }
+
+        match kind {
+            "address" => Some(SolType::Address),
+            "bool" => Some(SolType::Bool),
+            "string" => Some(SolType::String),
+            "bytes" => Some(SolType::Bytes),
+            "tuple" => components.iter()
+                .enumerate()
+                .map(|(i, c)| Some((param_name(&c.name, i), Self::parse(&c.kind, &c.components)?)))
+                .collect::<Option<Vec<_>>>()
+                .map(SolType::Tuple),
+            t if t.starts_with("uint") => int_bits(&t[4..]).map(SolType::Uint),
+            t if t.starts_with("int") => int_bits(&t[3..]).map(SolType::Int),
+            t if t.starts_with("bytes") => t[5..].parse().ok()
+                .filter(|n| (1..=WORD).contains(n))
+                .map(SolType::FixedBytes),
+            _ => None,
+        }
+    }
+
+    fn canonical(&self) -> String {
+        match self {
+            SolType::Address => "address".to_string(),
+            SolType::Bool => "bool".to_string(),
+            SolType::Uint(bits) => format!("uint{}", bits),
+            SolType::Int(bits) => format!("int{}", bits),
+            SolType::FixedBytes(size) => format!("bytes{}", size),
+            SolType::Bytes => "bytes".to_string(),
+            SolType::String => "string".to_string(),
+            SolType::Array(element) => format!("{}[]", element.canonical()),
+            SolType::FixedArray(element, size) => format!("{}[{}]", element.canonical(), size),
+            SolType::Tuple(fields) => format!(
+                "({})",
+                fields.iter().map(|(_, ty)| ty.canonical()).collect::<Vec<_>>().join(",")
+            ),
+        }
+    }
+
+    fn is_dynamic(&self) -> bool {
+        match self {
+            SolType::Bytes | SolType::String | SolType::Array(_) => true,
+            SolType::FixedArray(element, _) => element.is_dynamic(),
+            SolType::Tuple(fields) => fields.iter().any(|(_, ty)| ty.is_dynamic()),
+            _ => false,
+        }
+    }
+
+    // Bytes taken in the head of an enclosing tuple: one offset word for dynamic types
+    fn head_size(&self) -> usize {
+        match self {
+            ty if ty.is_dynamic() => WORD,
+            SolType::FixedArray(element, size) => element.head_size() * size,
+            SolType::Tuple(fields) => fields.iter().map(|(_, ty)| ty.head_size()).sum(),
+            _ => WORD,
+        }
+    }
+
+    // Decodes a value whose encoding starts at `region[0]`
+    fn decode(&self, region: &[u8]) -> Option<Value> {
+        let word = region.get(..WORD);
+        match self {
+            SolType::Address => Some(json!(format!("0x{}", to_hex(&word?[12..])))),
+            SolType::Bool => Some(json!(word?[WORD - 1] != 0)),
+            SolType::Uint(bits) => {
+                let value = BigUint::from_bytes_be(word?);
+                Some(small_or_string(&value.to_u64(), *bits, &value))
+            }
+            SolType::Int(bits) => {
+                let raw = BigUint::from_bytes_be(word?);
+                let value = if word?[0] & 0x80 != 0 {
+                    BigInt::from(raw) - (BigInt::from(1) << 256u32)
+                } else {
+                    BigInt::from(raw)
+                };
+                Some(small_or_string(&value.to_i64(), *bits, &value))
+            }
+            SolType::FixedBytes(size) => Some(json!(format!("0x{}", to_hex(&word?[..*size])))),
+            SolType::Bytes => Some(json!(format!("0x{}", to_hex(dynamic_bytes(region)?)))),
+            SolType::String => Some(json!(String::from_utf8_lossy(dynamic_bytes(region)?))),
+            SolType::Array(element) => {
+                let length = read_length(region, 0)?;
+                // Every element takes at least one word, which bounds hostile lengths
+                if length > region.len() / WORD {
+                    return None;
+                }
+                let elements = vec![element.as_ref(); length];
+                decode_sequence(&elements, region.get(WORD..)?).map(Value::Array)
+            }
+            SolType::FixedArray(element, size) => {
+                let elements = vec![element.as_ref(); *size];
+                decode_sequence(&elements, region).map(Value::Array)
+            }
+            SolType::Tuple(fields) => {
+                let types: Vec<&SolType> = fields.iter().map(|(_, ty)| ty).collect();
+                let values = decode_sequence(&types, region)?;
+                Some(Value::Object(fields.iter().map(|(name, _)| name.clone()).zip(values).collect()))
+            }
+        }
+    }
+}
+
+/// Decodes return data holding a single value of Solidity type `kind`
+pub fn decode_output(kind: &str, data: &str) -> Option<Value> {
+    let ty = SolType::parse(kind, &[])?;
+    let bytes = from_hex(data.trim_start_matches("0x"))?;
+    decode_sequence(&[&ty], &bytes)?.pop()
+}
+
+// Head/tail decoding of consecutive values, as in a tuple or the non-indexed event data
+fn decode_sequence(types: &[&SolType], region: &[u8]) -> Option<Vec<Value>> {
+    let mut head = 0;
+    let mut values = Vec::with_capacity(types.len());
+    for ty in types {
+        let value = if ty.is_dynamic() {
+            let offset = read_length(region, head)?;
+            ty.decode(region.get(offset..)?)?
+        } else {
+            ty.decode(region.get(head..)?)?
+        };
+        values.push(value);
+        head += ty.head_size();
+    }
+    Some(values)
+}
+
+fn read_length(region: &[u8], at: usize) -> Option<usize> {
+    BigUint::from_bytes_be(region.get(at..at + WORD)?).to_usize()
+}
+
+// Length-prefixed payload of `bytes` and `string`
+fn dynamic_bytes(region: &[u8]) -> Option<&[u8]> {
+    let length = read_length(region, 0)?;
+    region.get(WORD..WORD.checked_add(length)?)
+}
+
+// Integers up to 32 bits as JSON numbers, wider ones as decimal strings, as the Cairo decoder does
+fn small_or_string<T: serde::Serialize>(small: &Option<T>, bits: u64, value: &impl ToString) -> Value {
+    match small {
+        Some(small) if bits <= 32 => json!(small),
+        _ => json!(value.to_string()),
+    }
+}
+
+fn param_name(name: &str, position: usize) -> String {
+    if name.is_empty() {
+        format!("arg{}", position)
+    } else {
+        name.to_string()
+    }
+}
+
+/// Four-byte selector of a function signature such as `balanceOf(address)`
+pub fn function_selector(signature: &str) -> [u8; 4] {
+    let hash = Keccak256::digest(signature.as_bytes());
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+/// `topics[0]` of the logs of an event signature such as `Transfer(address,address,uint256)`
+pub fn event_topic(signature: &str) -> Result<String, AppError> {
+    let (name, types) = parse_signature(signature)?;
+    let types = types.iter()
+        .map(|kind| SolType::parse(kind, &[]).map(|ty| ty.canonical()))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| AppError::BadRequest(format!("Unsupported type in event signature '{}'", signature)))?;
+    let canonical = format!("{}({})", name, types.join(","));
+    Ok(format!("0x{}", to_hex(&Keccak256::digest(canonical.as_bytes()))))
+}
+
+/// Splits `name(type,...)` into its name and parameter types. Whitespace is ignored.
+pub fn parse_signature(signature: &str) -> Result<(String, Vec<String>), AppError> {
+    let compact: String = signature.chars().filter(|c| !c.is_whitespace()).collect();
+    let invalid = || AppError::BadRequest(format!("Invalid function signature '{}'", signature));
+
+    let (name, rest) = compact.split_once('(').ok_or_else(invalid)?;
+    let params = rest.strip_suffix(')').ok_or_else(invalid)?;
+    if name.is_empty() || params.contains(['(', ')']) {
+        return Err(invalid());
+    }
+
+    let types = if params.is_empty() {
+        vec![]
+    } else {
+        params.split(',').map(String::from).collect()
+    };
+    Ok((name.to_string(), types))
+}
+
+/// Calldata for a call of `signature` with positional `args`, as `0x` hex. Only static
+/// parameter types (address, bool, intN, uintN, bytesN) are supported.
+pub fn encode_call(signature: &str, args: &Value) -> Result<String, AppError> {
+    let (name, types) = parse_signature(signature)?;
+    let args = match args {
+        Value::Null => vec![],
+        Value::Array(items) => items.clone(),
+        _ => return Err(AppError::BadRequest("args must be an array".to_string())),
+    };
+    if args.len() != types.len() {
+        return Err(AppError::BadRequest(format!(
+            "{} expects {} arguments, got {}", name, types.len(), args.len()
+        )));
+    }
+
+    let canonical = format!("{}({})", name, types.join(","));
+    let mut calldata = function_selector(&canonical).to_vec();
+    for (param_type, arg) in types.iter().zip(&args) {
+        calldata.extend(encode_word(param_type, arg)?);
+    }
+    Ok(format!("0x{}", to_hex(&calldata)))
+}
+
+/// 32-byte words of a hex payload (log data, call results); a short tail is zero-padded
+pub fn split_words(data: &str) -> Vec<String> {
+    let hex = data.trim_start_matches("0x");
+    hex.as_bytes()
+        .chunks(WORD * 2)
+        .map(|chunk| format!("0x{:0<64}", String::from_utf8_lossy(chunk)))
+        .collect()
+}
+
+fn encode_word(param_type: &str, arg: &Value) -> Result<[u8; WORD], AppError> {
+    let invalid = || AppError::BadRequest(format!("Invalid {} argument: {}", param_type, arg));
+    let mut word = [0u8; WORD];
+
+    match param_type {
+        "address" => {
+            let bytes = parse_unsigned(arg).ok_or_else(invalid)?.to_bytes_be();
+            if bytes.len() > 20 {
+                return Err(invalid());
+            }
+            word[WORD - bytes.len()..].copy_from_slice(&bytes);
+        }
+        "bool" => {
+            let value = match arg {
+                Value::Bool(b) => *b,
+                Value::String(s) if s == "true" || s == "false" => s == "true",
+                _ => return Err(invalid()),
+            };
+            word[WORD - 1] = value as u8;
+        }
+        t if t.starts_with("uint") => {
+            let bits = int_bits(&t[4..]).ok_or_else(invalid)?;
+            let value = parse_unsigned(arg).ok_or_else(invalid)?;
+            if value.bits() > bits {
+                return Err(invalid());
+            }
+            let bytes = value.to_bytes_be();
+            word[WORD - bytes.len()..].copy_from_slice(&bytes);
+        }
+        t if t.starts_with("int") => {
+            let bits = int_bits(&t[3..]).ok_or_else(invalid)?;
+            let value = parse_signed(arg).ok_or_else(invalid)?;
+            // Two's complement over the full word, so negative values are sign-extended
+            let limit: BigInt = BigInt::from(1) << (bits - 1);
+            if value >= limit || value < -limit {
+                return Err(invalid());
+            }
+            let encoded = if value.sign() == Sign::Minus {
+                ((BigInt::from(1) << 256u32) + value).to_biguint().ok_or_else(invalid)?
+            } else {
+                value.to_biguint().ok_or_else(invalid)?
+            };
+            let bytes = encoded.to_bytes_be();
+            word[WORD - bytes.len()..].copy_from_slice(&bytes);
+        }
+        t if t.starts_with("bytes") && t.len() > 5 => {
+            let size: usize = t[5..].parse().ok().filter(|n| (1..=WORD).contains(n)).ok_or_else(invalid)?;
+            let hex = arg.as_str().and_then(|s| s.strip_prefix("0x")).ok_or_else(invalid)?;
+            let bytes = from_hex(hex).ok_or_else(invalid)?;
+            if bytes.len() > size {
+                return Err(invalid());
+            }
+            word[..bytes.len()].copy_from_slice(&bytes);
+        }
+        other => {
+            return Err(AppError::BadRequest(format!(
+                "Argument type '{}' is not supported; only static types can be encoded", other
+            )));
+        }
+    }
+
+    Ok(word)
+}
+
+// Width of `uintN`/`intN`; a bare `uint`/`int` is 256 bits
+fn int_bits(suffix: &str) -> Option<u64> {
+    if suffix.is_empty() {
+        return Some(256);
+    }
+    suffix.parse().ok().filter(|n| n % 8 == 0 && (8..=256).contains(n))
+}
+
+fn parse_unsigned(arg: &Value) -> Option<BigUint> {
+    match arg {
+        Value::Number(n) => n.as_u64().map(BigUint::from),
+        Value::String(s) => match s.strip_prefix("0x") {
+            Some("") => Some(BigUint::zero()),
+            Some(hex) => BigUint::from_str_radix(hex, 16).ok(),
+            None => BigUint::from_str_radix(s, 10).ok(),
+        },
+        _ => None,
+    }
+}
+
+fn parse_signed(arg: &Value) -> Option<BigInt> {
+    match arg {
+        Value::Number(n) => n.as_i64().map(BigInt::from),
+        Value::String(s) => match s.strip_prefix('-') {
+            Some(magnitude) => parse_unsigned(&Value::String(magnitude.to_string()))
+                .map(|v| -BigInt::from(v)),
+            None => parse_unsigned(arg).map(BigInt::from),
+        },
+        _ => None,
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRANSFER_TOPIC: &str = "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
+    const FROM: &str = "0x000000000000000000000000a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48";
+    const TO: &str = "0x000000000000000000000000dac17f958d2ee523a2206206994597c13d831ec7";
+
+    #[test]
+    fn decodes_erc20_transfer() {
+        let topics = vec![TRANSFER_TOPIC.to_string(), FROM.to_string(), TO.to_string()];
+        let data = format!("0x{:064x}", 1_500_000u64);
+
+        let (name, params) = SolidityAbi::standard().decode_log(&topics, &data).expect("decodes");
+        assert_eq!(name, "Transfer");
+        assert_eq!(params["from"], "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48");
+        assert_eq!(params["to"], "0xdac17f958d2ee523a2206206994597c13d831ec7");
+        assert_eq!(params["value"], "1500000");
+    }
+
+    #[test]
+    fn tells_erc721_transfer_apart_by_topic_count() {
+        let token_id = format!("0x{:064x}", 42u64);
+        let topics = vec![TRANSFER_TOPIC.to_string(), FROM.to_string(), TO.to_string(), token_id];
+
+        let (name, params) = SolidityAbi::standard().decode_log(&topics, "0x").expect("decodes");
+        assert_eq!(name, "Transfer");
+        assert_eq!(params["tokenId"], "42");
+        assert!(params.get("value").is_none());
+    }
+
+    #[test]
+    fn rejects_unknown_or_truncated_logs() {
+        let abi = SolidityAbi::standard();
+        let unknown = vec![format!("0x{:064x}", 1u64)];
+        assert!(abi.decode_log(&unknown, "0x").is_none());
+
+        // An ERC-20 transfer whose amount word is cut short
+        let topics = vec![TRANSFER_TOPIC.to_string(), FROM.to_string(), TO.to_string()];
+        assert!(abi.decode_log(&topics, "0x0102").is_none());
+    }
+}
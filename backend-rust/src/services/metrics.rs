@@ -0,0 +1,266 @@
+use prometheus_client::encoding::text::encode;
+use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
+use prometheus_client::metrics::histogram::{exponential_buckets, Histogram};
+use prometheus_client::registry::Registry;
+use std::sync::atomic::AtomicU64;
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::db::DbPool;
+
+/// Content type of `Metrics::render`, which Prometheus scrapes natively
+pub const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct HttpLabels {
+    method: String,
+    /// Route pattern, not the raw path, so ids in paths do not create new series
+    route: String,
+    status: u16,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct RpcLabels {
+    provider: String,
+    method: String,
+    outcome: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct ProviderLabels {
+    provider: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct AlchemyLabels {
+    chain: String,
+    direction: String,
+    outcome: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct PoolLabels {
+    state: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct JobLabels {
+    job: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct JobRunLabels {
+    job: String,
+    outcome: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct ContractLabels {
+    contract: String,
+}
+
+type FloatGauge = Gauge<f64, AtomicU64>;
+
+// 5ms to ~20s
+fn latency_histogram() -> Histogram {
+    Histogram::new(exponential_buckets(0.005, 2.0, 13))
+}
+
+/// Process-wide Prometheus series. Services record into `metrics()` directly; pool
+/// gauges are sampled when the endpoint is scraped.
+pub struct Metrics {
+    registry: Registry,
+    http_requests: Family<HttpLabels, Counter>,
+    http_duration: Family<HttpLabels, Histogram, fn() -> Histogram>,
+    rpc_requests: Family<RpcLabels, Counter>,
+    rpc_duration: Family<ProviderLabels, Histogram, fn() -> Histogram>,
+    alchemy_pages: Family<AlchemyLabels, Counter>,
+    alchemy_transfers: Family<AlchemyLabels, Counter>,
+    db_connections: Family<PoolLabels, Gauge>,
+    db_max_connections: Gauge,
+    job_running: Family<JobLabels, Gauge>,
+    job_runs: Family<JobRunLabels, Counter>,
+    job_last_run: Family<JobLabels, FloatGauge>,
+    job_last_duration: Family<JobLabels, FloatGauge>,
+    event_sync_contracts: Gauge,
+    event_sync_lag: Family<ContractLabels, Gauge>,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let mut metrics = Self {
+            registry: Registry::default(),
+            http_requests: Family::default(),
+            http_duration: Family::new_with_constructor(latency_histogram),
+            rpc_requests: Family::default(),
+            rpc_duration: Family::new_with_constructor(latency_histogram),
+            alchemy_pages: Family::default(),
+            alchemy_transfers: Family::default(),
+            db_connections: Family::default(),
+            db_max_connections: Gauge::default(),
+            job_running: Family::default(),
+            job_runs: Family::default(),
+            job_last_run: Family::default(),
+            job_last_duration: Family::default(),
+            event_sync_contracts: Gauge::default(),
+            event_sync_lag: Family::default(),
+        };
+
+        let registry = &mut metrics.registry;
+        registry.register("http_requests", "HTTP requests by route and status", metrics.http_requests.clone());
+        registry.register("http_request_duration_seconds", "HTTP request latency", metrics.http_duration.clone());
+        registry.register("rpc_requests", "JSON-RPC calls by provider, method and outcome", metrics.rpc_requests.clone());
+        registry.register("rpc_request_duration_seconds", "JSON-RPC round trip latency, single calls and batches", metrics.rpc_duration.clone());
+        registry.register("alchemy_page_fetches", "Alchemy asset transfer pages fetched", metrics.alchemy_pages.clone());
+        registry.register("alchemy_transfers", "Transfers returned by Alchemy pages", metrics.alchemy_transfers.clone());
+        registry.register("db_pool_connections", "SQLite pool connections by state", metrics.db_connections.clone());
+        registry.register("db_pool_max_connections", "SQLite pool connection limit", metrics.db_max_connections.clone());
+        registry.register("background_job_running", "1 while a background job pass is in progress", metrics.job_running.clone());
+        registry.register("background_job_runs", "Finished background job passes by outcome", metrics.job_runs.clone());
+        registry.register("background_job_last_run_timestamp_seconds", "Unix time the last pass finished", metrics.job_last_run.clone());
+        registry.register("background_job_last_duration_seconds", "Duration of the last pass", metrics.job_last_duration.clone());
+        registry.register("event_sync_tracked_contracts", "Contracts tracked by the event index", metrics.event_sync_contracts.clone());
+        registry.register("event_sync_lag_blocks", "Blocks between the chain head and a contract's indexed range", metrics.event_sync_lag.clone());
+
+        metrics
+    }
+
+    pub fn observe_http(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        let labels = HttpLabels { method: method.to_string(), route: route.to_string(), status };
+        self.http_requests.get_or_create(&labels).inc();
+        self.http_duration.get_or_create(&labels).observe(elapsed.as_secs_f64());
+    }
+
+    /// One call (or one call of a batch) answered by a provider; `outcome` is `success`,
+    /// `rpc_error` (the node answered with an error), `provider_error` or `batch_rejected`
+    pub fn observe_rpc(&self, provider: &str, method: &str, outcome: &str) {
+        self.rpc_requests
+            .get_or_create(&RpcLabels { provider: provider.to_string(), method: method.to_string(), outcome: outcome.to_string() })
+            .inc();
+    }
+
+    pub fn observe_rpc_latency(&self, provider: &str, elapsed: Duration) {
+        self.rpc_duration
+            .get_or_create(&ProviderLabels { provider: provider.to_string() })
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn observe_alchemy_page(&self, chain: &str, direction: &str, outcome: &str, transfers: usize) {
+        let labels = AlchemyLabels { chain: chain.to_string(), direction: direction.to_string(), outcome: outcome.to_string() };
+        self.alchemy_pages.get_or_create(&labels).inc();
+        self.alchemy_transfers.get_or_create(&labels).inc_by(transfers as u64);
+    }
+
+    pub fn job_started(&self, job: &str) {
+        self.job_running.get_or_create(&JobLabels { job: job.to_string() }).set(1);
+    }
+
+    pub fn job_finished(&self, job: &str, elapsed: Duration, succeeded: bool) {
+        let labels = JobLabels { job: job.to_string() };
+        let outcome = if succeeded { "success" } else { "error" };
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+
+        self.job_running.get_or_create(&labels).set(0);
+        self.job_runs.get_or_create(&JobRunLabels { job: job.to_string(), outcome: outcome.to_string() }).inc();
+        self.job_last_run.get_or_create(&labels).set(now.as_secs_f64());
+        self.job_last_duration.get_or_create(&labels).set(elapsed.as_secs_f64());
+    }
+
+    /// Replaces the per-contract lag series with the current cursors
+    pub fn set_event_sync_lag(&self, lags: &[(String, u64)]) {
+        self.event_sync_lag.clear();
+        self.event_sync_contracts.set(lags.len() as i64);
+        for (contract, lag) in lags {
+            self.event_sync_lag.get_or_create(&ContractLabels { contract: contract.clone() }).set(*lag as i64);
+        }
+    }
+
+    /// Every series in the OpenMetrics text format, pool gauges sampled now
+    pub fn render(&self, pool: &DbPool) -> String {
+        let idle = pool.num_idle() as i64;
+        let open = pool.size() as i64;
+        self.db_connections.get_or_create(&PoolLabels { state: "idle".to_string() }).set(idle);
+        self.db_connections.get_or_create(&PoolLabels { state: "in_use".to_string() }).set((open - idle).max(0));
+        self.db_max_connections.set(pool.options().get_max_connections() as i64);
+
+        let mut body = String::new();
+        if let Err(e) = encode(&mut body, &self.registry) {
+            log::warn!("Failed to encode metrics: {}", e);
+        }
+        body
+    }
+}
+
+pub fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(Metrics::new)
+}
+
+/// Label for a provider URL: its host, so keys carried in paths or queries never leak
+pub fn provider_label(url: &str) -> String {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(String::from))
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool() -> DbPool {
+        sqlx::sqlite::SqlitePoolOptions::new().max_connections(3).connect_lazy("sqlite::memory:").expect("valid URL")
+    }
+
+    #[tokio::test]
+    async fn renders_recorded_series() {
+        let metrics = Metrics::new();
+        metrics.observe_http("GET", "/api/contracts/{address}", 200, Duration::from_millis(12));
+        metrics.observe_rpc("rpc.example", "starknet_getEvents", "success");
+        metrics.observe_alchemy_page("ethereum", "from", "success", 7);
+        metrics.job_started("event_sync");
+
+        let body = metrics.render(&pool());
+        assert!(body.contains(r#"http_requests_total{method="GET",route="/api/contracts/{address}",status="200"} 1"#));
+        assert!(body.contains(r#"http_request_duration_seconds_count{method="GET",route="/api/contracts/{address}",status="200"} 1"#));
+        assert!(body.contains(r#"rpc_requests_total{provider="rpc.example",method="starknet_getEvents",outcome="success"} 1"#));
+        assert!(body.contains(r#"alchemy_transfers_total{chain="ethereum",direction="from",outcome="success"} 7"#));
+        assert!(body.contains(r#"background_job_running{job="event_sync"} 1"#));
+        assert!(body.contains("db_pool_max_connections 3"));
+        assert!(body.ends_with("# EOF\n"));
+    }
+
+    #[tokio::test]
+    async fn records_finished_jobs_by_outcome() {
+        let metrics = Metrics::new();
+        metrics.job_started("event_sync");
+        metrics.job_finished("event_sync", Duration::from_secs(2), false);
+
+        let body = metrics.render(&pool());
+        assert!(body.contains(r#"background_job_running{job="event_sync"} 0"#));
+        assert!(body.contains(r#"background_job_runs_total{job="event_sync",outcome="error"} 1"#));
+        assert!(body.contains(r#"background_job_last_duration_seconds{job="event_sync"} 2.0"#));
+    }
+
+    #[tokio::test]
+    async fn replaces_sync_lag_series() {
+        let metrics = Metrics::new();
+        metrics.set_event_sync_lag(&[("0xa".to_string(), 5), ("0xb".to_string(), 0)]);
+        metrics.set_event_sync_lag(&[("0xb".to_string(), 3)]);
+
+        let body = metrics.render(&pool());
+        assert!(body.contains("event_sync_tracked_contracts 1"));
+        assert!(body.contains(r#"event_sync_lag_blocks{contract="0xb"} 3"#));
+        assert!(!body.contains(r#"contract="0xa""#));
+    }
+
+    #[test]
+    fn labels_providers_by_host() {
+        assert_eq!(provider_label("https://eth-mainnet.g.alchemy.com/v2/secret-key"), "eth-mainnet.g.alchemy.com");
+        assert_eq!(provider_label("https://rpc.example/?apikey=secret"), "rpc.example");
+        assert_eq!(provider_label("not a url"), "unknown");
+    }
+}
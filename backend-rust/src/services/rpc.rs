@@ -1,19 +1,69 @@
+use futures::stream::{self, Stream, StreamExt};
+use lru::LruCache;
+use num_bigint::BigUint;
+use num_traits::ToPrimitive;
 use reqwest::Client;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::{json, Value};
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::time::Duration;
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use crate::config::{Config, RpcProviderConfig};
 use crate::errors::AppError;
+use crate::services::cassette::{Cassette, Recorded};
+use crate::services::chain::{STARKNET, STARKNET_NATIVE_SYMBOL};
+use crate::services::metrics::{metrics, provider_label};
+use crate::services::rpc_cache::{RpcCache, RpcCacheStats};
+use crate::services::starknet_abi::{decode_text, ContractAbi};
+use crate::services::starknet_trace::{
+    emitting_contracts, invoked_classes, invoked_contracts, parse_trace, receipt_events, FeeBreakdown, TransactionDetails,
+};
+use crate::services::starknet_receipt::{fee_cap, summarize_fees, ExecutionStatus, FeeSummary, FeeUnit, TransactionReceipt};
+use crate::services::token::{TokenAmount, TokenMetadata};
+use crate::utils::felt::{felt_to_hex, normalize_hex, parse_felt, selector, storage_address, u256_from_parts};
 
-// RPC endpoints for Starknet
-const RPC_ENDPOINTS: &[&str] = &[
-    "https://rpc.starknet.lava.build",
-    "https://starknet-mainnet.g.alchemy.com/v2/demo",
-    "https://starknet-mainnet.public.blastapi.io",
-    "https://free-rpc.nethermind.io/mainnet-juno",
-];
+// Latency assumed for a provider before its first response, so that weights decide the initial order
+const INITIAL_LATENCY_MS: f64 = 500.0;
+// Smoothing factor for the latency and error-rate moving averages
+const HEALTH_EWMA_ALPHA: f64 = 0.2;
 
-static CURRENT_RPC_INDEX: AtomicUsize = AtomicUsize::new(0);
+const DEFAULT_SCAN_WORKERS: usize = 8;
+const DEFAULT_BLOCK_RETRIES: u32 = 3;
+const DEFAULT_BATCH_SIZE: usize = 20;
+
+// Block timestamps kept in memory (a few MB at most)
+const HEADER_CACHE_CAPACITY: NonZeroUsize = NonZeroUsize::new(200_000).unwrap();
+
+// How long a computed L1-accepted head is trusted before it is searched for again
+const L1_HEAD_TTL: Duration = Duration::from_secs(60);
+// Headers probed per round of the L1-accepted head search
+const L1_SEARCH_FANOUT: u64 = 8;
+// How long the class deployed at an address is trusted; upgrades replace it
+const CLASS_HASH_TTL: Duration = Duration::from_secs(300);
+
+// Upper bound on consecutive slots read by one storage request
+const MAX_STORAGE_SLOTS: usize = 64;
+
+// Events requested per starknet_getEvents page, and the page limit for one-shot fetches
+const EVENTS_CHUNK_SIZE: u64 = 1000;
+pub const MAX_EVENT_PAGES: usize = 100;
+/// JSON-RPC codes nodes use to refuse a request as too large: EIP-1474's "limit exceeded",
+/// and the invalid-params and range codes providers return for oversized log queries.
+/// Every provider would refuse the same request, so these are answers, not faults.
+pub const LIMIT_ERROR_CODES: [i32; 3] = [-32005, -32602, -32614];
+
+/// Tells provider faults, which fail over and count against the provider's health, from
+/// errors the node answered with, which every provider would return alike
+pub type ErrorClassifier = fn(&RpcError) -> bool;
+
+/// Starknet's classifier: JSON-RPC protocol errors (negative codes) are faults, such as
+/// rate limits, unsupported methods and internal errors. Starknet errors and refusals of
+/// oversized requests are answers.
+pub fn starknet_error_is_fault(error: &RpcError) -> bool {
+    error.code < 0 && !LIMIT_ERROR_CODES.contains(&error.code)
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RpcRequest {
@@ -26,9 +76,17 @@ pub struct RpcRequest {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RpcResponse {
     pub jsonrpc: String,
+    /// `Some(Value::Null)` for an explicit null result (e.g. an unknown transaction),
+    /// `None` when the field is missing
+    #[serde(default, deserialize_with = "present")]
     pub result: Option<Value>,
     pub error: Option<RpcError>,
-    pub id: u64,
+    pub id: Option<u64>,
+}
+
+// A field that is present, even as null
+fn present<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Value>, D::Error> {
+    Value::deserialize(deserializer).map(Some)
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -37,23 +95,195 @@ pub struct RpcError {
     pub message: String,
 }
 
+/// How final the data behind a request must be
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Finality {
+    /// Up to and including the pending block
+    Pending,
+    /// Up to the latest block accepted on L2
+    #[default]
+    Latest,
+    /// Only blocks whose state update is accepted on L1
+    L1Accepted,
+}
+
+impl Finality {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().replace('-', "_").as_str() {
+            "pending" => Some(Finality::Pending),
+            "latest" => Some(Finality::Latest),
+            "l1_accepted" => Some(Finality::L1Accepted),
+            _ => None,
+        }
+    }
+}
+
+/// Finality status of a block, as reported by Starknet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum BlockStatus {
+    Pending,
+    AcceptedOnL2,
+    AcceptedOnL1,
+    Rejected,
+}
+
+impl BlockStatus {
+    fn from_value(value: &Value) -> Option<Self> {
+        serde_json::from_value(value.get("status")?.clone()).ok()
+    }
+}
+
+/// Upper end of a block range: a block number, or the pending block (numbered one past
+/// the latest block) to include not-yet-accepted data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockId {
+    Number(u64),
+    Pending(u64),
+}
+
+impl BlockId {
+    pub fn number(self) -> u64 {
+        match self {
+            BlockId::Number(n) | BlockId::Pending(n) => n,
+        }
+    }
+
+    fn to_param(self) -> Value {
+        match self {
+            BlockId::Number(n) => json!({"block_number": n}),
+            BlockId::Pending(_) => json!("pending"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ContractCallResult {
+    pub contract_address: String,
+    pub function: String,
+    /// `view` or `external`; external functions are only simulated, nothing is sent
+    pub state_mutability: Option<String>,
+    pub block_number: Option<u64>,
+    pub calldata: Vec<String>,
+    /// Raw felts returned by `starknet_call`
+    pub result: Vec<String>,
+    pub decoded: Value,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StorageRead {
+    pub contract_address: String,
+    pub variable: String,
+    pub block_number: Option<u64>,
+    /// Address of the first slot; values spanning several slots continue at the next addresses
+    pub address: String,
+    pub values: Vec<String>,
+    pub decoded: Option<Value>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct BlockInfo {
     pub block_number: u64,
     pub timestamp: u64,
+    pub status: BlockStatus,
     pub transactions: Vec<Value>,
+    /// Receipts by transaction hash
+    pub receipts: HashMap<String, TransactionReceipt>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct EventData {
+    /// Contract that emitted the event
+    #[serde(default)]
+    pub contract_address: String,
     pub block_number: u64,
     pub transaction_hash: String,
+    /// Position of the event in its block: among all logs (`logIndex`) on EVM chains; on
+    /// Starknet among the contract's events, or among the matching ones when a key filter
+    /// went to the node (responses report which as `eventIndexScope`)
+    pub event_index: u64,
+    /// Position of the emitting transaction in its block, when known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transaction_index: Option<u64>,
+    /// Position of the event among all events of its transaction, when known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub receipt_index: Option<u64>,
     pub keys: Vec<String>,
     pub data: Vec<String>,
     pub event_name: String,
     pub decoded_data: Value,
     pub timestamp: String,
     pub timestamp_raw: u64,
+    /// True when the block header could not be fetched and the timestamp was interpolated
+    pub timestamp_estimated: bool,
+    pub finality_status: BlockStatus,
+}
+
+#[derive(Debug, Clone)]
+pub struct RawEvent {
+    pub contract_address: String,
+    pub block_number: u64,
+    /// Emitted in the pending block; `block_number` is then the pending block's expected number
+    pub pending: bool,
+    pub transaction_hash: String,
+    pub event_index: u64,
+    /// Node-reported positions of the transaction in its block and of the event in its
+    /// transaction; older nodes omit them
+    pub transaction_index: Option<u64>,
+    pub receipt_index: Option<u64>,
+    pub keys: Vec<String>,
+    pub data: Vec<String>,
+}
+
+pub struct EventPage {
+    pub events: Vec<RawEvent>,
+    pub continuation_token: Option<String>,
+}
+
+pub struct EventFetch {
+    pub events: Vec<EventData>,
+    /// False when pagination stopped at the page limit before the range was exhausted
+    pub complete: bool,
+}
+
+/// One decoded page of a streamed event fetch
+pub struct DecodedEventPage {
+    /// 1-based page number
+    pub page: usize,
+    pub events: Vec<EventData>,
+    /// Set on the last page: true when the range was exhausted, false when the page limit cut it short
+    pub complete: Option<bool>,
+}
+
+// Pagination state carried between pages of `stream_events`
+struct EventCursor {
+    contract_address: String,
+    /// Key pattern applied after numbering, empty for every event
+    keys: Vec<Vec<String>>,
+    continuation_token: Option<String>,
+    pages: usize,
+    sequencer: EventSequencer,
+    abi: Option<Arc<ContractAbi>>,
+}
+
+/// Numbers events within their block as they arrive in chain order
+#[derive(Default)]
+pub struct EventSequencer {
+    block: Option<u64>,
+    next_index: u64,
+}
+
+impl EventSequencer {
+    pub fn next(&mut self, block_number: u64) -> u64 {
+        if self.block != Some(block_number) {
+            self.block = Some(block_number);
+            self.next_index = 0;
+        }
+        let index = self.next_index;
+        self.next_index += 1;
+        index
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -63,448 +293,1949 @@ pub struct ContractAnalysis {
     pub transaction_count: usize,
     pub avg_fee: String,
     pub total_fees: String,
+    /// `total_fees` and `avg_fee` with their raw amount and symbol
+    pub total_fees_amount: TokenAmount,
+    pub avg_fee_amount: TokenAmount,
     pub unique_senders: usize,
+    /// Actual fees per unit; `total_fees` and `avg_fee` cover the WEI-paid ones only
+    pub fees: Vec<FeeSummary>,
+    pub successful_txs: usize,
+    pub reverted_txs: usize,
     pub blocks_analyzed: usize,
+    /// Blocks in the range that could not be fetched after retries; counts exclude them
+    pub failed_blocks: Vec<u64>,
     pub current_block: u64,
+    pub finality: Finality,
     pub from_block: u64,
     pub to_block: u64,
     pub transactions: Vec<TransactionInfo>,
 }
 
+/// Raw result of scanning a block range for a contract's transactions, before summarizing
+pub struct ContractScan {
+    pub contract_address: String,
+    pub transactions: Vec<TransactionInfo>,
+    pub receipts: Vec<TransactionReceipt>,
+    pub blocks_analyzed: usize,
+    pub failed_blocks: Vec<u64>,
+    pub current_block: u64,
+    pub finality: Finality,
+    pub from_block: u64,
+    pub to_block: u64,
+    /// Coin the WEI-denominated fees are paid in
+    pub native_symbol: String,
+}
+
+impl ContractScan {
+    /// Fee, status and sender totals over the scan, keeping the ten newest transactions
+    pub fn into_analysis(mut self) -> ContractAnalysis {
+        // Workers finish out of order; report newest first as the sequential scan did
+        self.transactions.sort_by_key(|tx| std::cmp::Reverse(tx.block_number));
+        self.failed_blocks.sort_unstable();
+
+        if !self.failed_blocks.is_empty() {
+            log::warn!(
+                "Contract analysis for {} skipped {} of {} blocks",
+                self.contract_address,
+                self.failed_blocks.len(),
+                self.to_block - self.from_block + 1
+            );
+        }
+
+        let fees = summarize_fees(&self.receipts, &self.native_symbol);
+        let reverted_txs = self.receipts.iter()
+            .filter(|r| r.execution_status == ExecutionStatus::Reverted)
+            .count();
+        let successful_txs = self.receipts.len() - reverted_txs;
+
+        let wei_fees: Vec<u128> = self.receipts.iter()
+            .filter(|r| r.fee_unit == FeeUnit::Wei)
+            .map(|r| r.actual_fee)
+            .collect();
+        let total_fees: u128 = wei_fees.iter().sum();
+        let avg_fee = total_fees / (wei_fees.len().max(1) as u128);
+        let unique_senders = self.transactions.iter()
+            .map(|tx| tx.sender_address.clone())
+            .collect::<HashSet<_>>()
+            .len();
+        let active = !self.transactions.is_empty();
+        let total_fees_amount = TokenAmount::fee(total_fees, FeeUnit::Wei, &self.native_symbol);
+        let avg_fee_amount = TokenAmount::fee(avg_fee, FeeUnit::Wei, &self.native_symbol);
+
+        ContractAnalysis {
+            contract_address: self.contract_address,
+            status: if active { "Active" } else { "No Recent Activity" }.to_string(),
+            transaction_count: self.transactions.len(),
+            avg_fee: avg_fee_amount.formatted.clone(),
+            total_fees: total_fees_amount.formatted.clone(),
+            total_fees_amount,
+            avg_fee_amount,
+            unique_senders,
+            fees,
+            successful_txs,
+            reverted_txs,
+            blocks_analyzed: self.blocks_analyzed,
+            failed_blocks: self.failed_blocks,
+            current_block: self.current_block,
+            finality: self.finality,
+            from_block: self.from_block,
+            to_block: self.to_block,
+            transactions: self.transactions.into_iter().take(10).collect(),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TransactionInfo {
     pub block_number: u64,
     pub transaction_hash: String,
     pub sender_address: String,
     pub contract_address: String,
+    /// Fee cap: `max_fee`, or the resource bounds of a V3 transaction
     pub max_fee: String,
+    /// Fee actually charged, from the receipt
+    pub actual_fee: Option<String>,
+    pub fee_unit: FeeUnit,
+    pub execution_status: Option<ExecutionStatus>,
+    pub revert_reason: Option<String>,
     pub tx_type: String,
     pub timestamp: u64,
+    pub finality_status: BlockStatus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+#[derive(Debug)]
+struct ProviderHealth {
+    latency_ms: f64,
+    error_rate: f64,
+    consecutive_failures: u32,
+    circuit: CircuitState,
+    opened_at: Option<Instant>,
+    total_requests: u64,
+    total_failures: u64,
+}
+
+impl ProviderHealth {
+    fn new() -> Self {
+        Self {
+            latency_ms: INITIAL_LATENCY_MS,
+            error_rate: 0.0,
+            consecutive_failures: 0,
+            circuit: CircuitState::Closed,
+            opened_at: None,
+            total_requests: 0,
+            total_failures: 0,
+        }
+    }
+}
+
+struct RpcProvider {
+    config: RpcProviderConfig,
+    // Metrics label: the host, never the key-bearing URL
+    label: String,
+    health: Mutex<ProviderHealth>,
+    // When the provider last refused a batch; batches are retried after the circuit cooldown
+    batch_rejected_at: Mutex<Option<Instant>>,
+    // Set while a request probes the provider through its half-open circuit
+    probing: AtomicBool,
+}
+
+// Held while a request probes a half-open provider; dropping it, even when the request is
+// cancelled, lets the next probe through
+struct Probe<'a>(Option<&'a AtomicBool>);
+
+impl Drop for Probe<'_> {
+    fn drop(&mut self) {
+        if let Some(probing) = self.0 {
+            probing.store(false, Ordering::Release);
+        }
+    }
+}
+
+impl RpcProvider {
+    fn endpoint(&self) -> String {
+        match &self.config.api_key {
+            Some(key) => self.config.url.replace("{api_key}", key),
+            None => self.config.url.clone(),
+        }
+    }
+
+    fn rejects_batches(&self, cooldown: Duration) -> bool {
+        self.batch_rejected_at.lock().unwrap().is_some_and(|at| at.elapsed() < cooldown)
+    }
+
+    // Higher is better: weight scaled down by observed latency and error rate
+    fn score(&self) -> f64 {
+        let health = self.health.lock().unwrap();
+        self.config.weight as f64 / (health.latency_ms.max(1.0) * (1.0 + 4.0 * health.error_rate))
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProviderHealthSnapshot {
+    pub url: String,
+    pub weight: u32,
+    pub circuit: CircuitState,
+    pub latency_ms: f64,
+    pub error_rate: f64,
+    pub consecutive_failures: u32,
+    pub total_requests: u64,
+    pub total_failures: u64,
+    pub batch_supported: bool,
+}
+
+// Outcome of a single attempt against one provider
+enum AttemptError {
+    // The provider itself misbehaved (transport, HTTP or protocol error): fail over
+    Provider(String),
+    // The node answered with a Starknet-level error (e.g. block not found): same answer everywhere
+    Rpc(RpcError),
+    // The provider does not accept JSON-RPC batch arrays
+    BatchRejected(String),
+}
+
+// Transfer or Approval of a token whose ABI is unavailable. Cairo 1 tokens index both
+// parties in `keys` and emit the amount as a u256 low/high pair in `data`; ERC721s also
+// index the u256 token id. Cairo 0 tokens put everything in `data`, the amount being a
+// Uint256 pair or, on older tokens, a single felt.
+struct TokenEvent<'a> {
+    first: &'a str,
+    second: &'a str,
+    value_field: &'static str,
+    /// Decimal, up to 256 bits
+    value: String,
+}
+
+impl<'a> TokenEvent<'a> {
+    fn parse(keys: &'a [String], data: &'a [String]) -> Option<Self> {
+        let (first, second, value, value_field) = match (keys, data) {
+            ([_, first, second, token_id @ ..], []) if !token_id.is_empty() => (first, second, token_id, "token_id"),
+            ([_, first, second], amount) => (first, second, amount, "amount"),
+            ([_], [first, second, amount @ ..]) => (first, second, amount, "amount"),
+            _ => return None,
+        };
+
+        let value = match value {
+            [low, high] => u256_from_parts(&parse_felt(low)?, &parse_felt(high)?),
+            [value] => parse_felt(value)?,
+            _ => return None,
+        };
+
+        Some(Self {
+            first,
+            second,
+            value_field,
+            value: value.to_string(),
+        })
+    }
+}
+
+// Status of a live or recorded exchange; a code that is not valid HTTP counts as a server error
+fn http_status(response: &Recorded) -> reqwest::StatusCode {
+    reqwest::StatusCode::from_u16(response.status).unwrap_or(reqwest::StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+// Highest block known to be accepted on L1. Acceptance is monotonic, so `block` only grows;
+// `checked_at` records the last full search.
+#[derive(Default)]
+struct L1Head {
+    block: u64,
+    checked_at: Option<Instant>,
 }
 
+#[derive(Clone)]
 pub struct RpcService {
     client: Client,
+    providers: Arc<Vec<RpcProvider>>,
+    failure_threshold: u32,
+    cooldown: Duration,
+    scan_workers: usize,
+    block_retries: u32,
+    batch_size: usize,
+    abi_cache: Arc<Mutex<HashMap<String, Arc<ContractAbi>>>>,
+    class_hashes: Arc<Mutex<HashMap<String, (String, Instant)>>>,
+    header_cache: Arc<Mutex<LruCache<u64, u64>>>,
+    l1_head: Arc<Mutex<L1Head>>,
+    response_cache: Option<RpcCache>,
+    cassette: Option<Cassette>,
+    is_fault: ErrorClassifier,
 }
 
 impl RpcService {
-    pub fn new() -> Self {
+    pub fn new(providers: Vec<RpcProviderConfig>, failure_threshold: u32, cooldown: Duration) -> Self {
+        let providers = if providers.is_empty() {
+            RpcProviderConfig::defaults()
+        } else {
+            providers
+        };
+
         Self {
             client: Client::builder()
-                .timeout(Duration::from_secs(30))  // Mobile-friendly timeout
                 .connect_timeout(Duration::from_secs(10))
                 .build()
                 .unwrap_or_else(|_| Client::new()),
+            providers: Arc::new(
+                providers
+                    .into_iter()
+                    .map(|config| RpcProvider {
+                        label: provider_label(&config.url),
+                        config,
+                        health: Mutex::new(ProviderHealth::new()),
+                        batch_rejected_at: Mutex::new(None),
+                        probing: AtomicBool::new(false),
+                    })
+                    .collect(),
+            ),
+            failure_threshold: failure_threshold.max(1),
+            cooldown,
+            scan_workers: DEFAULT_SCAN_WORKERS,
+            block_retries: DEFAULT_BLOCK_RETRIES,
+            batch_size: DEFAULT_BATCH_SIZE,
+            abi_cache: Arc::new(Mutex::new(HashMap::new())),
+            class_hashes: Arc::new(Mutex::new(HashMap::new())),
+            header_cache: Arc::new(Mutex::new(LruCache::new(HEADER_CACHE_CAPACITY))),
+            l1_head: Arc::new(Mutex::new(L1Head::default())),
+            response_cache: None,
+            cassette: None,
+            is_fault: starknet_error_is_fault,
         }
     }
 
-    fn get_rpc_url(&self) -> &'static str {
-        let index = CURRENT_RPC_INDEX.load(Ordering::Relaxed);
-        RPC_ENDPOINTS[index % RPC_ENDPOINTS.len()]
+    /// Replaces the Starknet rule deciding which JSON-RPC errors are provider faults
+    pub fn with_error_classifier(mut self, is_fault: ErrorClassifier) -> Self {
+        self.is_fault = is_fault;
+        self
     }
 
-    fn switch_rpc(&self) {
-        CURRENT_RPC_INDEX.fetch_add(1, Ordering::Relaxed);
+    /// Serves immutable results (finalized or explicitly numbered data) from `cache`
+    pub fn with_response_cache(mut self, cache: RpcCache) -> Self {
+        self.response_cache = Some(cache);
+        self
     }
 
-    pub async fn rpc_call(&self, method: &str, params: Value) -> Result<Value, AppError> {
-        let mut last_error = None;
-        
-        for attempt in 0..RPC_ENDPOINTS.len() {
-            let url = self.get_rpc_url();
-            
-            log::info!("RPC call attempt {} to {}: {}", attempt + 1, url, method);
-            
-            let request = RpcRequest {
-                jsonrpc: "2.0".to_string(),
-                method: method.to_string(),
-                params: params.clone(),
-                id: 1,
-            };
+    /// The same service without its response cache, for bulk reads that would only
+    /// crowd out the entries interactive requests reuse
+    pub fn without_response_cache(mut self) -> Self {
+        self.response_cache = None;
+        self
+    }
 
-            match self.client.post(url)
-                .json(&request)
-                .send()
-                .await
-            {
-                Ok(response) => {
-                    if response.status().is_success() {
-                        if let Ok(rpc_response) = response.json::<RpcResponse>().await {
-                            if let Some(result) = rpc_response.result {
-                                log::info!("RPC call successful on attempt {}", attempt + 1);
-                                return Ok(result);
-                            }
-                            if let Some(error) = rpc_response.error {
-                                log::warn!("RPC error: {} - {}", error.code, error.message);
-                                last_error = Some(format!("RPC error: {}", error.message));
-                            }
-                        }
-                    } else {
-                        log::warn!("RPC HTTP error: {}", response.status());
-                        last_error = Some(format!("HTTP {}", response.status()));
-                    }
-                }
-                Err(e) => {
-                    log::warn!("RPC request failed: {}", e);
-                    last_error = Some(e.to_string());
-                    self.switch_rpc();
-                    continue;
-                }
-            }
-            
-            self.switch_rpc();
+    /// Records that blocks up to `block` are final, so the response cache may keep
+    /// results read at them
+    pub fn note_finalized_head(&self, block: u64) {
+        if let Some(cache) = &self.response_cache {
+            cache.set_finalized_head(block);
         }
+    }
 
-        Err(AppError::BadRequest(
-            last_error.unwrap_or_else(|| "All RPC endpoints failed".to_string())
-        ))
+    /// Records provider exchanges to, or replays them from, `cassette`
+    pub fn with_cassette(mut self, cassette: Cassette) -> Self {
+        self.cassette = Some(cassette);
+        self
     }
 
-    pub async fn get_block_number(&self) -> Result<u64, AppError> {
-        let result = self.rpc_call("starknet_blockNumber", json!([])).await?;
-        
-        if let Some(num_str) = result.as_str() {
-            let num = u64::from_str_radix(num_str.trim_start_matches("0x"), 16)
-                .map_err(|_| AppError::BadRequest("Invalid block number".to_string()))?;
-            return Ok(num);
-        }
-        
-        if let Some(num) = result.as_u64() {
-            return Ok(num);
-        }
+    pub fn cache_stats(&self) -> Option<RpcCacheStats> {
+        self.response_cache.as_ref().map(RpcCache::stats)
+    }
 
-        Err(AppError::BadRequest("Invalid block number format".to_string()))
+    /// Maximum number of calls packed into one JSON-RPC batch
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
     }
 
-    pub async fn get_block_with_txs(&self, block_number: u64) -> Result<BlockInfo, AppError> {
-        let result = self.rpc_call(
-            "starknet_getBlockWithTxs",
-            json!([{"block_number": block_number}])
-        ).await?;
+    /// Number of blocks fetched concurrently by `analyze_contract`, and attempts per block
+    pub fn with_scan_options(mut self, workers: usize, retries: u32) -> Self {
+        self.scan_workers = workers.max(1);
+        self.block_retries = retries.max(1);
+        self
+    }
 
-        let block_num = result.get("block_number")
-            .and_then(|v| v.as_u64())
-            .unwrap_or(block_number);
-        
-        let timestamp = result.get("timestamp")
-            .and_then(|v| v.as_u64())
-            .unwrap_or(0);
-        
-        let transactions = result.get("transactions")
-            .and_then(|v| v.as_array())
-            .map(|arr| arr.clone())
-            .unwrap_or_default();
+    pub fn from_config(config: &Config) -> Self {
+        let rpc = Self::new(
+            config.rpc_providers.clone(),
+            config.rpc_failure_threshold,
+            Duration::from_secs(config.rpc_circuit_cooldown_secs),
+        )
+        .with_scan_options(config.analysis_workers, config.block_fetch_retries)
+        .with_batch_size(config.rpc_batch_size);
+        match Cassette::from_config(config, STARKNET) {
+            Some(cassette) => rpc.with_cassette(cassette),
+            None => rpc,
+        }
+    }
 
-        Ok(BlockInfo {
-            block_number: block_num,
-            timestamp,
-            transactions,
-        })
+    /// Current health of every configured provider, in configuration order
+    pub fn provider_health(&self) -> Vec<ProviderHealthSnapshot> {
+        self.providers
+            .iter()
+            .map(|provider| {
+                let health = provider.health.lock().unwrap();
+                ProviderHealthSnapshot {
+                    url: provider.config.url.clone(),
+                    weight: provider.config.weight,
+                    circuit: health.circuit,
+                    latency_ms: health.latency_ms,
+                    error_rate: health.error_rate,
+                    consecutive_failures: health.consecutive_failures,
+                    total_requests: health.total_requests,
+                    total_failures: health.total_failures,
+                    batch_supported: !provider.rejects_batches(self.cooldown),
+                }
+            })
+            .collect()
     }
 
-    pub async fn find_block_by_timestamp(&self, target_timestamp: u64) -> Result<u64, AppError> {
-        let latest = self.get_block_number().await?;
-        let mut low = 0u64;
-        let mut high = latest;
+    // Providers whose circuit admits traffic, healthiest first. An open circuit moves to
+    // half-open once the cooldown has elapsed, letting one request probe the provider.
+    fn ranked_providers(&self) -> Vec<usize> {
+        let mut available: Vec<(usize, f64)> = Vec::new();
 
-        while low <= high {
-            let mid = (low + high) / 2;
-            
-            match self.get_block_with_txs(mid).await {
-                Ok(block) => {
-                    if block.timestamp < target_timestamp {
-                        low = mid + 1;
-                    } else if block.timestamp > target_timestamp {
-                        if mid == 0 {
-                            break;
+        for (index, provider) in self.providers.iter().enumerate() {
+            {
+                let mut health = provider.health.lock().unwrap();
+                if health.circuit == CircuitState::Open {
+                    match health.opened_at {
+                        Some(opened) if opened.elapsed() < self.cooldown => continue,
+                        _ => {
+                            log::info!("Circuit half-open for {}", provider.config.url);
+                            health.circuit = CircuitState::HalfOpen;
                         }
-                        high = mid - 1;
-                    } else {
-                        return Ok(mid);
                     }
                 }
-                Err(_) => break,
             }
+            available.push((index, provider.score()));
         }
 
-        Ok(low)
+        if available.is_empty() {
+            // Every circuit is open: rather than failing outright, try them all by score
+            log::warn!("All RPC provider circuits are open, trying every provider");
+            available = self.providers
+                .iter()
+                .enumerate()
+                .map(|(index, provider)| (index, provider.score()))
+                .collect();
+        }
+
+        available.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        available.into_iter().map(|(index, _)| index).collect()
     }
 
-    pub async fn get_events(
-        &self,
-        contract_address: &str,
-        from_block: u64,
-        to_block: u64,
-    ) -> Result<Vec<EventData>, AppError> {
-        println!("🚀 UNLIMITED MODE: Fetching ALL events for contract: {} from block {} to {}", contract_address, from_block, to_block);
-        
-        let mut all_events = Vec::new();
-        let mut continuation_token: Option<String> = None;
-        let mut page_count = 0;
-        let max_pages = 100; // Safety limit
+    // Admits a request to a provider. A half-open circuit takes one probe at a time, so
+    // while one is in flight every other request skips the provider.
+    fn admit(&self, index: usize) -> Option<Probe<'_>> {
+        let provider = &self.providers[index];
+        if provider.health.lock().unwrap().circuit != CircuitState::HalfOpen {
+            return Some(Probe(None));
+        }
+        provider.probing
+            .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+            .ok()
+            .map(|_| Probe(Some(&provider.probing)))
+    }
 
-        loop {
-            page_count += 1;
-            println!("📄 Fetching page {}...", page_count);
-
-            let mut params = json!({
-                "filter": {
-                    "from_block": {"block_number": from_block},
-                    "to_block": {"block_number": to_block},
-                    "address": contract_address,
-                    "chunk_size": 1000
-                }
-            });
+    fn record_success(&self, index: usize, latency: Duration) {
+        let provider = &self.providers[index];
+        let mut health = provider.health.lock().unwrap();
+        let latency_ms = latency.as_secs_f64() * 1000.0;
 
-            // Add continuation token if we have one
-            if let Some(token) = &continuation_token {
-                params["filter"]["continuation_token"] = json!(token);
-            }
+        health.total_requests += 1;
+        health.latency_ms = HEALTH_EWMA_ALPHA * latency_ms + (1.0 - HEALTH_EWMA_ALPHA) * health.latency_ms;
+        health.error_rate *= 1.0 - HEALTH_EWMA_ALPHA;
+        health.consecutive_failures = 0;
 
-            let result = self.rpc_call("starknet_getEvents", params).await?;
-            
-            let events = result.get("events")
-                .and_then(|v| v.as_array())
-                .ok_or(AppError::BadRequest("Invalid events response".to_string()))?;
-
-            println!("   ✅ Page {}: {} events (Total: {})", page_count, events.len(), all_events.len() + events.len());
-
-            // Check for continuation token
-            continuation_token = result.get("continuation_token")
-                .and_then(|v| v.as_str())
-                .map(String::from);
-
-            // Process events from this page
-            for event in events {
-                let block_number = event.get("block_number")
-                    .and_then(|v| v.as_u64())
-                    .unwrap_or(0);
-                
-                let tx_hash = event.get("transaction_hash")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("")
-                    .to_string();
+        if health.circuit != CircuitState::Closed {
+            log::info!("Circuit closed for {}", provider.config.url);
+            health.circuit = CircuitState::Closed;
+            health.opened_at = None;
+        }
+    }
 
-                let keys: Vec<String> = event.get("keys")
-                    .and_then(|v| v.as_array())
-                    .map(|arr| arr.iter().filter_map(|k| k.as_str().map(String::from)).collect())
-                    .unwrap_or_default();
+    fn record_failure(&self, index: usize, latency: Duration) {
+        let provider = &self.providers[index];
+        let mut health = provider.health.lock().unwrap();
+        let latency_ms = latency.as_secs_f64() * 1000.0;
 
-                let data: Vec<String> = event.get("data")
-                    .and_then(|v| v.as_array())
-                    .map(|arr| arr.iter().filter_map(|d| d.as_str().map(String::from)).collect())
-                    .unwrap_or_default();
+        health.total_requests += 1;
+        health.total_failures += 1;
+        health.latency_ms = HEALTH_EWMA_ALPHA * latency_ms + (1.0 - HEALTH_EWMA_ALPHA) * health.latency_ms;
+        health.error_rate = HEALTH_EWMA_ALPHA + (1.0 - HEALTH_EWMA_ALPHA) * health.error_rate;
+        health.consecutive_failures += 1;
 
-                // Decode event
-                let (event_name, decoded_data) = self.decode_event(&keys, &data);
+        let should_open = health.circuit == CircuitState::HalfOpen
+            || health.consecutive_failures >= self.failure_threshold;
 
-                // We'll estimate timestamp later in batch
-                all_events.push((block_number, tx_hash, keys, data, event_name, decoded_data));
-            }
+        if should_open && health.circuit != CircuitState::Open {
+            log::warn!(
+                "Circuit opened for {} after {} consecutive failures",
+                provider.config.url,
+                health.consecutive_failures
+            );
+            health.circuit = CircuitState::Open;
+            health.opened_at = Some(Instant::now());
+        } else if should_open {
+            health.opened_at = Some(Instant::now());
+        }
+    }
 
-            // Check if we should continue
-            if continuation_token.is_none() {
-                println!("   ✅ No more pages. Pagination complete!");
-                break;
-            }
+    // Counts one single-call attempt against a provider
+    fn observe(&self, index: usize, method: &str, outcome: &Result<Value, AttemptError>, elapsed: Duration) {
+        let label = &self.providers[index].label;
+        let outcome = match outcome {
+            Ok(_) => "success",
+            Err(AttemptError::Rpc(_)) => "rpc_error",
+            Err(AttemptError::Provider(_)) => "provider_error",
+            Err(AttemptError::BatchRejected(_)) => "batch_rejected",
+        };
+        metrics().observe_rpc(label, method, outcome);
+        metrics().observe_rpc_latency(label, elapsed);
+    }
 
-            if page_count >= max_pages {
-                println!("   ⚠️ Reached maximum page limit ({}). Stopping pagination.", max_pages);
-                break;
-            }
+    // Sends one JSON-RPC payload (a request or a batch array) to a provider, or answers it
+    // from the cassette. Recording keeps whatever the provider answered, errors included.
+    async fn exchange<T: Serialize + ?Sized>(&self, index: usize, body: &T) -> Result<Recorded, AttemptError> {
+        let Some(cassette) = &self.cassette else {
+            return self.post(index, body).await;
+        };
 
-            println!("   🔄 More events available, fetching next page...");
+        let request = serde_json::to_value(body)
+            .map_err(|e| AttemptError::Provider(format!("Unserializable request: {}", e)))?;
+        if cassette.replaying() {
+            return cassette.replay(&request)
+                .await
+                .ok_or_else(|| AttemptError::Provider("No recorded response".to_string()));
         }
 
-        println!("🎉 COMPLETE! Fetched {} total events across {} pages", all_events.len(), page_count);
+        let response = self.post(index, body).await?;
+        cassette.record(&request, &response).await;
+        Ok(response)
+    }
 
-        // Now fetch timestamps for interpolation
-        let latest_block = self.get_block_with_txs(to_block).await?;
-        let from_block_data = self.get_block_with_txs(from_block).await?;
-        
-        let latest_timestamp = latest_block.timestamp;
-        let from_timestamp = from_block_data.timestamp;
-        let total_diff = to_block.saturating_sub(from_block);
-        let time_diff = latest_timestamp.saturating_sub(from_timestamp);
+    async fn post<T: Serialize + ?Sized>(&self, index: usize, body: &T) -> Result<Recorded, AttemptError> {
+        let provider = &self.providers[index];
 
-        // Convert to EventData with timestamps
-        let decoded_events: Vec<EventData> = all_events.into_iter().map(|(block_number, tx_hash, keys, data, event_name, decoded_data)| {
-            // Estimate timestamp
-            let block_diff = to_block.saturating_sub(block_number);
-            let estimated_timestamp = if total_diff > 0 {
-                latest_timestamp.saturating_sub((block_diff * time_diff) / total_diff)
-            } else {
-                latest_timestamp
-            };
+        let mut builder = self.client
+            .post(provider.endpoint())
+            .timeout(Duration::from_secs(provider.config.timeout_secs))
+            .json(body);
 
-            EventData {
-                block_number,
-                transaction_hash: tx_hash,
-                keys,
-                data,
-                event_name,
-                decoded_data,
-                timestamp: chrono::DateTime::from_timestamp(estimated_timestamp as i64, 0)
-                    .map(|dt| dt.to_rfc3339())
-                    .unwrap_or_default(),
-                timestamp_raw: estimated_timestamp,
+        if let Some(key) = &provider.config.api_key {
+            if !provider.config.url.contains("{api_key}") {
+                builder = builder.header("x-api-key", key);
             }
-        }).collect();
+        }
 
-        Ok(decoded_events)
+        let response = builder.send()
+            .await
+            .map_err(|e| AttemptError::Provider(e.to_string()))?;
+        let status = response.status().as_u16();
+        let body = response.text()
+            .await
+            .map_err(|e| AttemptError::Provider(e.to_string()))?;
+        Ok(Recorded { status, body })
     }
 
-    fn decode_event(&self, keys: &[String], data: &[String]) -> (String, Value) {
-        if keys.is_empty() {
-            return ("Unknown Event".to_string(), json!({}));
+    async fn call_provider(&self, index: usize, request: &RpcRequest) -> Result<Value, AttemptError> {
+        let response = self.exchange(index, request).await?;
+        let status = http_status(&response);
+
+        if !status.is_success() {
+            return Err(AttemptError::Provider(format!("HTTP {}", status)));
         }
 
-        let key = &keys[0];
+        let rpc_response = serde_json::from_str::<RpcResponse>(&response.body)
+            .map_err(|e| AttemptError::Provider(format!("Invalid RPC response: {}", e)))?;
 
-        // Transfer event
-        if key == "0x99cd8bde557814842a3121e8ddfd433a539b8c9f14bf31ebf108d12e6196e9" {
-            if data.len() >= 3 {
-                return ("Transfer".to_string(), json!({
-                    "from": data[0],
-                    "to": data[1],
-                    "amount": u128::from_str_radix(data[2].trim_start_matches("0x"), 16)
-                        .map(|v| v.to_string())
-                        .unwrap_or_else(|_| data[2].clone())
-                }));
-            }
+        if let Some(error) = rpc_response.error {
+            return Err(if (self.is_fault)(&error) {
+                AttemptError::Provider(format!("RPC error {}: {}", error.code, error.message))
+            } else {
+                AttemptError::Rpc(error)
+            });
         }
 
-        // Approval event
-        if key == "0x1dcde06aabdbca2f80aa51392b345d7549d7757aa855f7e37f5d335ac8243b1" {
-            if data.len() >= 3 {
-                return ("Approval".to_string(), json!({
-                    "owner": data[0],
-                    "spender": data[1],
-                    "amount": u128::from_str_radix(data[2].trim_start_matches("0x"), 16)
-                        .map(|v| v.to_string())
-                        .unwrap_or_else(|_| data[2].clone())
-                }));
-            }
+        rpc_response.result
+            .ok_or_else(|| AttemptError::Provider("RPC response without result".to_string()))
+    }
+
+    async fn call_provider_batch(&self, index: usize, requests: &[RpcRequest]) -> Result<Vec<RpcResponse>, AttemptError> {
+        let response = self.exchange(index, requests).await?;
+        let status = http_status(&response);
+
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+            return Err(AttemptError::Provider(format!("HTTP {}", status)));
+        }
+        if !status.is_success() {
+            return Err(AttemptError::BatchRejected(format!("HTTP {}", status)));
         }
 
-        ("Unknown Event".to_string(), json!({}))
+        let body = serde_json::from_str::<Value>(&response.body)
+            .map_err(|e| AttemptError::Provider(format!("Invalid RPC response: {}", e)))?;
+
+        match body {
+            Value::Array(items) => items
+                .into_iter()
+                .map(|item| serde_json::from_value::<RpcResponse>(item)
+                    .map_err(|e| AttemptError::Provider(format!("Invalid RPC response: {}", e))))
+                .collect(),
+            // A single error object instead of an array means the batch itself was refused
+            other => Err(AttemptError::BatchRejected(
+                other.pointer("/error/message")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("non-array response")
+                    .to_string()
+            )),
+        }
     }
 
-    pub async fn analyze_contract(
-        &self,
-        contract_address: &str,
-        from_timestamp: Option<u64>,
-        to_timestamp: Option<u64>,
-    ) -> Result<ContractAnalysis, AppError> {
-        let current_block = self.get_block_number().await?;
-        
-        let from_block = if let Some(ts) = from_timestamp {
-            self.find_block_by_timestamp(ts).await?
-        } else {
-            current_block.saturating_sub(1000)
+    /// One JSON-RPC call, answered from the response cache when the result is immutable
+    pub async fn rpc_call(&self, method: &str, params: Value) -> Result<Value, AppError> {
+        let cached = self.response_cache.as_ref()
+            .and_then(|cache| Some((cache, cache.key(method, &params)?)));
+        if let Some((cache, key)) = &cached {
+            if let Some(hit) = cache.get(key).await {
+                return Ok(hit);
+            }
+        }
+
+        let Some((cache, key)) = &cached else {
+            return self.send_call(method, params).await;
         };
+        let result = self.send_call(method, params.clone()).await?;
+        cache.store(key, method, &params, &result);
+        Ok(result)
+    }
 
-        let to_block = if let Some(ts) = to_timestamp {
-            self.find_block_by_timestamp(ts).await?
-        } else {
-            current_block
+    // One call with provider failover, bypassing the response cache
+    async fn send_call(&self, method: &str, params: Value) -> Result<Value, AppError> {
+        let request = RpcRequest {
+            jsonrpc: "2.0".to_string(),
+            method: method.to_string(),
+            params,
+            id: 1,
         };
 
-        // UNLIMITED MODE: No artificial limits - search the entire range requested
-        let search_blocks = to_block - from_block + 1;
-        let mut contract_transactions = Vec::new();
-        
-        println!("🔍 Analyzing {} blocks for contract transactions", search_blocks);
+        let mut last_error = None;
 
-        for i in 0..search_blocks {
-            let block_num = to_block - i;
-            if block_num < from_block {
-                break;
-            }
+        for (attempt, index) in self.ranked_providers().into_iter().enumerate() {
+            let Some(_probe) = self.admit(index) else {
+                continue;
+            };
+            let url = &self.providers[index].config.url;
+            log::info!("RPC call attempt {} to {}: {}", attempt + 1, url, method);
 
-            if let Ok(block) = self.get_block_with_txs(block_num).await {
-                for tx in block.transactions {
-                    let sender = tx.get("sender_address")
-                        .and_then(|v| v.as_str())
-                        .unwrap_or("");
-                    
-                    let contract = tx.get("contract_address")
-                        .and_then(|v| v.as_str())
-                        .unwrap_or("");
-
-                    if sender == contract_address || contract == contract_address {
-                        let tx_hash = tx.get("transaction_hash")
-                            .and_then(|v| v.as_str())
-                            .unwrap_or("")
-                            .to_string();
-                        
-                        let max_fee = tx.get("max_fee")
-                            .and_then(|v| v.as_str())
-                            .unwrap_or("0x0")
-                            .to_string();
-
-                        let tx_type = tx.get("type")
-                            .and_then(|v| v.as_str())
-                            .unwrap_or("INVOKE")
-                            .to_string();
-
-                        contract_transactions.push(TransactionInfo {
-                            block_number: block_num,
-                            transaction_hash: tx_hash,
-                            sender_address: sender.to_string(),
-                            contract_address: contract_address.to_string(),
-                            max_fee,
-                            tx_type,
-                            timestamp: block.timestamp,
-                        });
-                    }
+            let started = Instant::now();
+            let outcome = self.call_provider(index, &request).await;
+            self.observe(index, method, &outcome, started.elapsed());
+            match outcome {
+                Ok(result) => {
+                    self.record_success(index, started.elapsed());
+                    log::info!("RPC call successful on attempt {}", attempt + 1);
+                    return Ok(result);
+                }
+                Err(AttemptError::Rpc(error)) => {
+                    self.record_success(index, started.elapsed());
+                    log::warn!("RPC error: {} - {}", error.code, error.message);
+                    return Err(AppError::Rpc(error.code, error.message));
+                }
+                Err(AttemptError::Provider(message)) | Err(AttemptError::BatchRejected(message)) => {
+                    self.record_failure(index, started.elapsed());
+                    log::warn!("RPC request to {} failed: {}", url, message);
+                    last_error = Some(message);
                 }
             }
         }
 
-        if contract_transactions.is_empty() {
-            return Ok(ContractAnalysis {
-                contract_address: contract_address.to_string(),
-                status: "No Recent Activity".to_string(),
-                transaction_count: 0,
-                avg_fee: "0".to_string(),
-                total_fees: "0".to_string(),
-                unique_senders: 0,
-                blocks_analyzed: search_blocks as usize,
-                current_block,
-                from_block,
-                to_block,
-                transactions: vec![],
-            });
-        }
-
-        let total_fees: u128 = contract_transactions.iter()
-            .filter_map(|tx| u128::from_str_radix(tx.max_fee.trim_start_matches("0x"), 16).ok())
-            .sum();
-
-        let avg_fee = total_fees / contract_transactions.len() as u128;
-        let unique_senders = contract_transactions.iter()
-            .map(|tx| tx.sender_address.clone())
-            .collect::<std::collections::HashSet<_>>()
-            .len();
+        Err(AppError::BadRequest(
+            last_error.unwrap_or_else(|| "All RPC endpoints failed".to_string())
+        ))
+    }
+
+    /// Sends many calls as JSON-RPC batch arrays of at most `batch_size` requests and
+    /// returns one result per call, in input order. Responses are matched by id; calls a
+    /// provider dropped from its reply or failed on its side, and every call when no
+    /// provider accepts batches, go out individually with failover.
+    pub async fn rpc_batch(&self, calls: &[(&str, Value)]) -> Vec<Result<Value, AppError>> {
+        let Some(cache) = &self.response_cache else {
+            return self.send_batch(calls).await;
+        };
+
+        // Only the calls the cache cannot answer go out
+        let keys: Vec<Option<String>> = calls.iter().map(|(method, params)| cache.key(method, params)).collect();
+        let mut results: Vec<Option<Result<Value, AppError>>> = Vec::with_capacity(calls.len());
+        for key in &keys {
+            results.push(match key {
+                Some(key) => cache.get(key).await.map(Ok),
+                None => None,
+            });
+        }
+
+        let missing: Vec<usize> = (0..calls.len()).filter(|&i| results[i].is_none()).collect();
+        let pending: Vec<(&str, Value)> = missing.iter().map(|&i| calls[i].clone()).collect();
+        for (i, result) in missing.into_iter().zip(self.send_batch(&pending).await) {
+            if let (Ok(value), Some(key)) = (&result, &keys[i]) {
+                let (method, params) = &calls[i];
+                cache.store(key, method, params, value);
+            }
+            results[i] = Some(result);
+        }
+
+        results.into_iter().flatten().collect()
+    }
+
+    async fn send_batch(&self, calls: &[(&str, Value)]) -> Vec<Result<Value, AppError>> {
+        let mut results = Vec::with_capacity(calls.len());
+        for chunk in calls.chunks(self.batch_size) {
+            results.extend(self.rpc_batch_chunk(chunk).await);
+        }
+        results
+    }
+
+    async fn rpc_batch_chunk(&self, calls: &[(&str, Value)]) -> Vec<Result<Value, AppError>> {
+        if calls.len() > 1 {
+            let requests: Vec<RpcRequest> = calls.iter()
+                .enumerate()
+                .map(|(id, (method, params))| RpcRequest {
+                    jsonrpc: "2.0".to_string(),
+                    method: method.to_string(),
+                    params: params.clone(),
+                    id: id as u64,
+                })
+                .collect();
+
+            for index in self.ranked_providers() {
+                let provider = &self.providers[index];
+                if provider.rejects_batches(self.cooldown) {
+                    continue;
+                }
+                let Some(_probe) = self.admit(index) else {
+                    continue;
+                };
+
+                log::info!("RPC batch of {} calls to {}", calls.len(), provider.config.url);
+
+                let started = Instant::now();
+                let outcome = self.call_provider_batch(index, &requests).await;
+                metrics().observe_rpc_latency(&provider.label, started.elapsed());
+                match outcome {
+                    Ok(responses) => {
+                        self.record_success(index, started.elapsed());
+                        for response in &responses {
+                            let method = response.id.and_then(|id| calls.get(id as usize)).map_or("unknown", |(method, _)| *method);
+                            let outcome = if response.error.is_some() { "rpc_error" } else { "success" };
+                            metrics().observe_rpc(&provider.label, method, outcome);
+                        }
+                        return self.collect_batch(calls, responses).await;
+                    }
+                    Err(AttemptError::BatchRejected(reason)) => {
+                        for (method, _) in calls {
+                            metrics().observe_rpc(&provider.label, method, "batch_rejected");
+                        }
+                        log::warn!("{} rejected batch request ({}), using single calls", provider.config.url, reason);
+                        *provider.batch_rejected_at.lock().unwrap() = Some(Instant::now());
+                    }
+                    Err(AttemptError::Provider(message)) | Err(AttemptError::Rpc(RpcError { message, .. })) => {
+                        for (method, _) in calls {
+                            metrics().observe_rpc(&provider.label, method, "provider_error");
+                        }
+                        self.record_failure(index, started.elapsed());
+                        log::warn!("RPC batch to {} failed: {}", provider.config.url, message);
+                    }
+                }
+            }
+        }
+
+        // Futures are built up front: a borrowing closure inside the stream would make
+        // this future fail the `Send` bound needed by spawned tasks
+        let singles: Vec<_> = calls.iter()
+            .map(|(method, params)| self.send_call(method, params.clone()))
+            .collect();
+
+        stream::iter(singles)
+            .buffered(self.scan_workers)
+            .collect()
+            .await
+    }
+
+    // Results of a batch by call, in input order. Calls the provider dropped or failed
+    // on its side (errors `is_fault` blames on the provider, such as rate limits) are
+    // retried one by one with failover.
+    async fn collect_batch(&self, calls: &[(&str, Value)], responses: Vec<RpcResponse>) -> Vec<Result<Value, AppError>> {
+        let mut slots: Vec<Option<Result<Value, AppError>>> = calls.iter().map(|_| None).collect();
+
+        for response in responses {
+            let Some(slot) = response.id.and_then(|id| slots.get_mut(id as usize)) else {
+                continue;
+            };
+            *slot = match (response.result, response.error) {
+                (Some(result), _) => Some(Ok(result)),
+                (None, Some(error)) if !(self.is_fault)(&error) => Some(Err(AppError::Rpc(error.code, error.message))),
+                _ => None,
+            };
+        }
+
+        let mut results = Vec::with_capacity(calls.len());
+        for (slot, (method, params)) in slots.into_iter().zip(calls) {
+            results.push(match slot {
+                Some(result) => result,
+                None => self.send_call(method, params.clone()).await,
+            });
+        }
+        results
+    }
 
-        Ok(ContractAnalysis {
+    pub async fn get_block_number(&self) -> Result<u64, AppError> {
+        let result = self.rpc_call("starknet_blockNumber", json!([])).await?;
+        
+        if let Some(num_str) = result.as_str() {
+            let num = u64::from_str_radix(num_str.trim_start_matches("0x"), 16)
+                .map_err(|_| AppError::BadRequest("Invalid block number".to_string()))?;
+            return Ok(num);
+        }
+        
+        if let Some(num) = result.as_u64() {
+            return Ok(num);
+        }
+
+        Err(AppError::BadRequest("Invalid block number format".to_string()))
+    }
+
+    /// Fetches a block with its transactions and their receipts. The pending block carries
+    /// no number and takes the one of `block`.
+    pub async fn get_block_with_receipts(&self, block: BlockId) -> Result<BlockInfo, AppError> {
+        let result = self.rpc_call("starknet_getBlockWithReceipts", json!([block.to_param()])).await?;
+
+        let mut info = Self::parse_block(&result, block.number());
+        if let BlockId::Pending(n) = block {
+            info.block_number = n;
+            info.status = BlockStatus::Pending;
+        }
+        Ok(info)
+    }
+
+    /// Fetches several blocks with transactions and receipts using batched calls
+    pub async fn get_blocks_with_receipts(&self, block_numbers: &[u64]) -> Vec<(u64, Result<BlockInfo, AppError>)> {
+        let calls: Vec<(&str, Value)> = block_numbers.iter()
+            .map(|n| ("starknet_getBlockWithReceipts", json!([{"block_number": n}])))
+            .collect();
+
+        self.rpc_batch(&calls)
+            .await
+            .into_iter()
+            .zip(block_numbers)
+            .map(|(result, &n)| (n, result.map(|block| Self::parse_block(&block, n))))
+            .collect()
+    }
+
+    /// Timestamps of the given blocks, served from the header cache and otherwise fetched as
+    /// batched header-only calls. Blocks that could not be fetched are absent from the map.
+    pub async fn get_block_timestamps(&self, block_numbers: &[u64]) -> HashMap<u64, u64> {
+        let mut timestamps = HashMap::with_capacity(block_numbers.len());
+        let mut missing = Vec::new();
+        {
+            let mut cache = self.header_cache.lock().unwrap();
+            for &n in block_numbers {
+                match cache.get(&n) {
+                    Some(&ts) => {
+                        timestamps.insert(n, ts);
+                    }
+                    None => missing.push(n),
+                }
+            }
+        }
+
+        if !missing.is_empty() {
+            timestamps.extend(
+                self.fetch_headers(&missing)
+                    .await
+                    .into_iter()
+                    .map(|(n, (ts, _))| (n, ts))
+            );
+        }
+
+        timestamps
+    }
+
+    // Header-only fetch of timestamps and statuses. Timestamps go into the header cache;
+    // statuses are never cached since they change, but L1-accepted ones raise the L1 head.
+    async fn fetch_headers(&self, block_numbers: &[u64]) -> HashMap<u64, (u64, BlockStatus)> {
+        let calls: Vec<(&str, Value)> = block_numbers.iter()
+            .map(|n| ("starknet_getBlockWithTxHashes", json!([{"block_number": n}])))
+            .collect();
+
+        let results = self.rpc_batch(&calls).await;
+        let mut headers = HashMap::with_capacity(block_numbers.len());
+
+        for (result, &n) in results.into_iter().zip(block_numbers) {
+            match result {
+                Ok(block) => {
+                    let Some(ts) = block.get("timestamp").and_then(|v| v.as_u64()) else {
+                        continue;
+                    };
+                    let status = BlockStatus::from_value(&block).unwrap_or(BlockStatus::AcceptedOnL2);
+                    headers.insert(n, (ts, status));
+                }
+                Err(e) => log::warn!("Failed to fetch header for block {}: {}", n, e),
+            }
+        }
+
+        let mut cache = self.header_cache.lock().unwrap();
+        for (&n, &(ts, status)) in &headers {
+            cache.put(n, ts);
+            if status == BlockStatus::AcceptedOnL1 {
+                self.raise_l1_head(n);
+            }
+        }
+
+        headers
+    }
+
+    fn raise_l1_head(&self, block_number: u64) {
+        let mut head = self.l1_head.lock().unwrap();
+        head.block = head.block.max(block_number);
+    }
+
+    /// Highest block whose state update is accepted on L1. Searches upwards from the last
+    /// known L1 block, probing `L1_SEARCH_FANOUT` headers per batched round; the result is
+    /// reused for `L1_HEAD_TTL`.
+    pub async fn l1_accepted_head(&self) -> Result<u64, AppError> {
+        let known = {
+            let head = self.l1_head.lock().unwrap();
+            if head.checked_at.is_some_and(|at| at.elapsed() < L1_HEAD_TTL) {
+                return Ok(head.block);
+            }
+            head.block
+        };
+
+        let latest = self.get_block_number().await?;
+        // `low` is accepted on L1, `high` is the first block known not to be (exclusive bound)
+        let mut low = known.min(latest);
+        let mut high = latest + 1;
+
+        while high - low > 1 {
+            let step = ((high - low) / (L1_SEARCH_FANOUT + 1)).max(1);
+            let probes: Vec<u64> = (1..=L1_SEARCH_FANOUT)
+                .map(|i| low + step * i)
+                .take_while(|&probe| probe < high)
+                .collect();
+            let headers = self.fetch_headers(&probes).await;
+
+            for &probe in &probes {
+                let (_, status) = headers.get(&probe).copied().ok_or_else(|| {
+                    AppError::BadRequest(format!("Failed to fetch block {} during L1 head search", probe))
+                })?;
+                if status != BlockStatus::AcceptedOnL1 {
+                    high = probe;
+                    break;
+                }
+                low = probe;
+            }
+        }
+
+        let block = {
+            let mut head = self.l1_head.lock().unwrap();
+            head.block = head.block.max(low);
+            head.checked_at = Some(Instant::now());
+            head.block
+        };
+        self.note_finalized_head(block);
+        Ok(block)
+    }
+
+    /// Last block a request with the given finality may read
+    pub async fn head_block(&self, finality: Finality) -> Result<BlockId, AppError> {
+        match finality {
+            Finality::Pending => Ok(BlockId::Pending(self.get_block_number().await? + 1)),
+            Finality::Latest => Ok(BlockId::Number(self.get_block_number().await?)),
+            Finality::L1Accepted => Ok(BlockId::Number(self.l1_accepted_head().await?)),
+        }
+    }
+
+    /// Finality status of a block given the L1 head; without one, accepted blocks are only
+    /// ever reported as accepted on L2
+    pub fn block_status(l1_head: Option<u64>, block_number: u64, pending: bool) -> BlockStatus {
+        match l1_head {
+            _ if pending => BlockStatus::Pending,
+            Some(head) if block_number <= head => BlockStatus::AcceptedOnL1,
+            _ => BlockStatus::AcceptedOnL2,
+        }
+    }
+
+    /// L1 head for labelling results; a failed search only downgrades labels to L2
+    pub async fn try_l1_accepted_head(&self) -> Option<u64> {
+        match self.l1_accepted_head().await {
+            Ok(head) => Some(head),
+            Err(e) => {
+                log::warn!("Could not determine the L1-accepted head: {}", e);
+                None
+            }
+        }
+    }
+
+    fn parse_block(result: &Value, block_number: u64) -> BlockInfo {
+        let block_num = result.get("block_number")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(block_number);
+
+        let timestamp = result.get("timestamp")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0);
+
+        let status = BlockStatus::from_value(result).unwrap_or(BlockStatus::AcceptedOnL2);
+
+        // Entries are `{transaction, receipt}` pairs; the hash lives on the receipt
+        let mut transactions = Vec::new();
+        let mut receipts = HashMap::new();
+        for entry in result.get("transactions").and_then(|v| v.as_array()).into_iter().flatten() {
+            let Some(receipt) = entry.get("receipt") else {
+                transactions.push(entry.clone());
+                continue;
+            };
+
+            let mut tx = entry.get("transaction").cloned().unwrap_or_else(|| json!({}));
+            if let (Some(fields), Some(hash)) = (tx.as_object_mut(), receipt.get("transaction_hash")) {
+                fields.entry("transaction_hash").or_insert_with(|| hash.clone());
+            }
+            match TransactionReceipt::from_value(receipt) {
+                Some(parsed) => {
+                    receipts.insert(normalize_hex(&parsed.transaction_hash), parsed);
+                }
+                None => log::warn!("Unparseable receipt in block {}", block_num),
+            }
+            transactions.push(tx);
+        }
+
+        BlockInfo {
+            block_number: block_num,
+            timestamp,
+            status,
+            transactions,
+            receipts,
+        }
+    }
+
+    // Block fetch with exponential backoff, on top of the per-call provider failover
+    async fn get_block_with_retry(&self, block: BlockId) -> Result<BlockInfo, AppError> {
+        let mut attempt = 1;
+        loop {
+            match self.get_block_with_receipts(block).await {
+                Ok(block) => return Ok(block),
+                Err(e) if attempt >= self.block_retries => return Err(e),
+                Err(e) => {
+                    log::warn!("Block {} fetch attempt {} failed: {}", block.number(), attempt, e);
+                    tokio::time::sleep(Duration::from_millis(250 * 2u64.pow(attempt - 1))).await;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    /// One `starknet_getEvents` page for a contract, of the events matching a non-empty
+    /// `keys` pattern. Events carry no `event_index` yet; callers number them with an
+    /// `EventSequencer` as pages arrive in order.
+    pub async fn get_events_page(
+        &self,
+        contract_address: &str,
+        from_block: u64,
+        to_block: BlockId,
+        keys: &[Vec<String>],
+        continuation_token: Option<&str>,
+    ) -> Result<EventPage, AppError> {
+        let mut params = json!({
+            "filter": {
+                "from_block": {"block_number": from_block},
+                "to_block": to_block.to_param(),
+                "address": contract_address,
+                "chunk_size": EVENTS_CHUNK_SIZE
+            }
+        });
+
+        if !keys.is_empty() {
+            params["filter"]["keys"] = json!(keys);
+        }
+
+        // Add continuation token if we have one
+        if let Some(token) = continuation_token {
+            params["filter"]["continuation_token"] = json!(token);
+        }
+
+        let result = self.rpc_call("starknet_getEvents", params).await?;
+
+        let events = result.get("events")
+            .and_then(|v| v.as_array())
+            .ok_or(AppError::BadRequest("Invalid events response".to_string()))?
+            .iter()
+            .map(|event| {
+                // Pending events carry no block number
+                let block_number = event.get("block_number").and_then(|v| v.as_u64());
+                RawEvent {
+                    contract_address: event.get("from_address")
+                        .and_then(|v| v.as_str())
+                        .map(normalize_hex)
+                        .unwrap_or_else(|| normalize_hex(contract_address)),
+                    block_number: block_number.unwrap_or(to_block.number()),
+                    pending: block_number.is_none(),
+                    transaction_hash: event.get("transaction_hash")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("")
+                        .to_string(),
+                    event_index: 0,
+                    transaction_index: event.get("transaction_index").and_then(|v| v.as_u64()),
+                    receipt_index: event.get("event_index").and_then(|v| v.as_u64()),
+                    keys: event.get("keys")
+                        .and_then(|v| v.as_array())
+                        .map(|arr| arr.iter().filter_map(|k| k.as_str().map(String::from)).collect())
+                        .unwrap_or_default(),
+                    data: event.get("data")
+                        .and_then(|v| v.as_array())
+                        .map(|arr| arr.iter().filter_map(|d| d.as_str().map(String::from)).collect())
+                        .unwrap_or_default(),
+                }
+            })
+            .collect();
+
+        let continuation_token = result.get("continuation_token")
+            .and_then(|v| v.as_str())
+            .map(String::from);
+
+        Ok(EventPage { events, continuation_token })
+    }
+
+    /// Pages through the events of a contract in a block range, yielding each page decoded
+    /// and timestamped as soon as it arrives. A non-empty `keys` pattern (see `EventFilter`)
+    /// is applied by the node, so `event_index` then counts the matching events only. With
+    /// `max_pages` set, pagination stops early and the last page is marked incomplete. The
+    /// stream ends after the first error.
+    pub fn stream_events(
+        &self,
+        contract_address: &str,
+        from_block: u64,
+        to_block: BlockId,
+        keys: &[Vec<String>],
+        max_pages: Option<usize>,
+    ) -> impl Stream<Item = Result<DecodedEventPage, AppError>> + Send + 'static {
+        let rpc = self.clone();
+        let cursor = EventCursor {
             contract_address: contract_address.to_string(),
-            status: "Active".to_string(),
-            transaction_count: contract_transactions.len(),
-            avg_fee: format!("{:.6}", avg_fee as f64 / 1e18),
-            total_fees: format!("{:.4}", total_fees as f64 / 1e18),
-            unique_senders,
-            blocks_analyzed: search_blocks as usize,
+            keys: keys.to_vec(),
+            continuation_token: None,
+            pages: 0,
+            sequencer: EventSequencer::default(),
+            abi: None,
+        };
+
+        stream::unfold(Some(cursor), move |cursor| {
+            let rpc = rpc.clone();
+            async move {
+                let mut cursor = cursor?;
+                if cursor.pages == 0 {
+                    cursor.abi = rpc.try_contract_abi(&cursor.contract_address).await;
+                }
+
+                let page = match rpc.get_events_page(
+                    &cursor.contract_address,
+                    from_block,
+                    to_block,
+                    &cursor.keys,
+                    cursor.continuation_token.as_deref(),
+                ).await {
+                    Ok(page) => page,
+                    Err(e) => return Some((Err(e), None)),
+                };
+                cursor.pages += 1;
+
+                let mut raw_events = page.events;
+                for event in &mut raw_events {
+                    event.event_index = cursor.sequencer.next(event.block_number);
+                }
+                log::info!(
+                    "Events page {} for {}: {} events",
+                    cursor.pages, cursor.contract_address, raw_events.len()
+                );
+
+                let events = match rpc.finalize_events(cursor.abi.as_deref(), raw_events).await {
+                    Ok(events) => events,
+                    Err(e) => return Some((Err(e), None)),
+                };
+
+                let complete = match page.continuation_token {
+                    None => Some(true),
+                    Some(_) if max_pages.is_some_and(|max| cursor.pages >= max) => {
+                        log::warn!("Reached maximum page limit ({}) for {}", cursor.pages, cursor.contract_address);
+                        Some(false)
+                    }
+                    Some(token) => {
+                        cursor.continuation_token = Some(token);
+                        None
+                    }
+                };
+
+                let decoded = DecodedEventPage { page: cursor.pages, events, complete };
+                Some((Ok(decoded), complete.is_none().then_some(cursor)))
+            }
+        })
+    }
+
+    /// Collects `stream_events` into memory
+    pub async fn fetch_events(
+        &self,
+        contract_address: &str,
+        from_block: u64,
+        to_block: BlockId,
+        keys: &[Vec<String>],
+        max_pages: Option<usize>,
+    ) -> Result<EventFetch, AppError> {
+        let mut pages = std::pin::pin!(self.stream_events(contract_address, from_block, to_block, keys, max_pages));
+        let mut events = Vec::new();
+        let mut complete = true;
+
+        while let Some(page) = pages.next().await {
+            let page = page?;
+            events.extend(page.events);
+            if let Some(page_complete) = page.complete {
+                complete = page_complete;
+            }
+        }
+
+        log::info!("Fetched {} events for {} (complete: {})", events.len(), contract_address, complete);
+        Ok(EventFetch { events, complete })
+    }
+
+    /// Decodes raw events and attaches block timestamps and finality statuses
+    pub async fn finalize_events(&self, abi: Option<&ContractAbi>, raw_events: Vec<RawEvent>) -> Result<Vec<EventData>, AppError> {
+        let l1_head = if raw_events.is_empty() {
+            None
+        } else {
+            self.try_l1_accepted_head().await
+        };
+
+        let all_events: Vec<_> = raw_events.into_iter()
+            .map(|event| {
+                let (event_name, decoded_data) = self.decode_event(abi, &event.keys, &event.data);
+                (event, event_name, decoded_data)
+            })
+            .collect();
+
+        // Real timestamps for every distinct block in the result set (the pending block has no header yet)
+        let mut blocks: Vec<u64> = all_events.iter()
+            .filter(|(event, _, _)| !event.pending)
+            .map(|(event, _, _)| event.block_number)
+            .collect();
+        blocks.sort_unstable();
+        blocks.dedup();
+        let timestamps = self.get_block_timestamps(&blocks).await;
+
+        if timestamps.is_empty() && !blocks.is_empty() {
+            return Err(AppError::BadRequest("Failed to fetch block timestamps".to_string()));
+        }
+
+        let mut known: Vec<(u64, u64)> = timestamps.iter().map(|(&block, &ts)| (block, ts)).collect();
+        known.sort_unstable();
+
+        // Convert to EventData with timestamps
+        let decoded_events: Vec<EventData> = all_events.into_iter().map(|(event, event_name, decoded_data)| {
+            let (timestamp, timestamp_estimated) = match timestamps.get(&event.block_number) {
+                Some(&ts) if !event.pending => (ts, false),
+                _ => (Self::interpolate_timestamp(&known, event.block_number), true),
+            };
+            let finality_status = Self::block_status(l1_head, event.block_number, event.pending);
+
+            EventData {
+                contract_address: event.contract_address,
+                block_number: event.block_number,
+                transaction_hash: event.transaction_hash,
+                event_index: event.event_index,
+                transaction_index: event.transaction_index,
+                receipt_index: event.receipt_index,
+                keys: event.keys,
+                data: event.data,
+                event_name,
+                decoded_data,
+                timestamp: chrono::DateTime::from_timestamp(timestamp as i64, 0)
+                    .map(|dt| dt.to_rfc3339())
+                    .unwrap_or_default(),
+                timestamp_raw: timestamp,
+                timestamp_estimated,
+                finality_status,
+            }
+        }).collect();
+
+        let estimated = decoded_events.iter().filter(|e| e.timestamp_estimated).count();
+        if estimated > 0 {
+            log::warn!("{} of {} event timestamps are estimated", estimated, decoded_events.len());
+        }
+
+        Ok(decoded_events)
+    }
+
+    /// Fills in `transaction_index` and `receipt_index` where events of different contracts
+    /// share a block or a transaction, which is all that merging them in chain order needs.
+    /// Only those blocks and receipts are fetched; positions the node reported are kept.
+    pub async fn locate_events(&self, events: &mut [EventData]) -> Result<(), AppError> {
+        let mut block_contracts: HashMap<(u64, bool), HashSet<&str>> = HashMap::new();
+        let mut transaction_contracts: HashMap<&str, HashSet<&str>> = HashMap::new();
+        for event in events.iter() {
+            let pending = event.finality_status == BlockStatus::Pending;
+            if event.transaction_index.is_none() {
+                block_contracts.entry((event.block_number, pending)).or_default().insert(&event.contract_address);
+            }
+            if event.receipt_index.is_none() {
+                transaction_contracts.entry(&event.transaction_hash).or_default().insert(&event.contract_address);
+            }
+        }
+        let blocks: Vec<(u64, bool)> = block_contracts.into_iter()
+            .filter(|(_, contracts)| contracts.len() > 1)
+            .map(|(block, _)| block)
+            .collect();
+        let transactions: Vec<String> = transaction_contracts.into_iter()
+            .filter(|(_, contracts)| contracts.len() > 1)
+            .map(|(hash, _)| hash.to_string())
+            .collect();
+
+        let calls: Vec<(&str, Value)> = blocks.iter()
+            .map(|&(n, pending)| {
+                let block = if pending { BlockId::Pending(n) } else { BlockId::Number(n) };
+                ("starknet_getBlockWithTxHashes", json!([block.to_param()]))
+            })
+            .collect();
+        let mut positions = HashMap::new();
+        for result in self.rpc_batch(&calls).await {
+            let block = result?;
+            let hashes = block.get("transactions").and_then(|v| v.as_array()).cloned().unwrap_or_default();
+            for (position, hash) in hashes.iter().enumerate() {
+                if let Some(hash) = hash.as_str() {
+                    positions.insert(normalize_hex(hash), position as u64);
+                }
+            }
+        }
+        for event in events.iter_mut().filter(|event| event.transaction_index.is_none()) {
+            event.transaction_index = positions.get(&normalize_hex(&event.transaction_hash)).copied();
+        }
+
+        let calls: Vec<(&str, Value)> = transactions.iter()
+            .map(|hash| ("starknet_getTransactionReceipt", json!([hash])))
+            .collect();
+        for (hash, result) in transactions.iter().zip(self.rpc_batch(&calls).await) {
+            let receipt = result?;
+            let emitted = receipt.get("events").and_then(|v| v.as_array()).cloned().unwrap_or_default();
+            for (position, emitted) in emitted.iter().enumerate() {
+                let felts = |field: &str| -> Vec<String> {
+                    emitted.get(field)
+                        .and_then(|v| v.as_array())
+                        .map(|arr| arr.iter().filter_map(|v| v.as_str().map(normalize_hex)).collect())
+                        .unwrap_or_default()
+                };
+                let from = emitted.get("from_address").and_then(|v| v.as_str()).map(normalize_hex);
+                let (keys, data) = (felts("keys"), felts("data"));
+                // Receipt events are matched to ours by emitter and content, first unplaced match wins
+                let event = events.iter_mut().find(|event| {
+                    event.receipt_index.is_none()
+                        && event.transaction_hash == *hash
+                        && from.as_deref() == Some(normalize_hex(&event.contract_address).as_str())
+                        && event.keys.iter().map(|k| normalize_hex(k)).eq(keys.iter().cloned())
+                        && event.data.iter().map(|d| normalize_hex(d)).eq(data.iter().cloned())
+                });
+                if let Some(event) = event {
+                    event.receipt_index = Some(position as u64);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Linear interpolation between the nearest blocks with known timestamps (sorted by block)
+    pub fn interpolate_timestamp(known: &[(u64, u64)], block_number: u64) -> u64 {
+        let position = known.partition_point(|&(block, _)| block < block_number);
+        match (position.checked_sub(1).map(|i| known[i]), known.get(position)) {
+            (Some((low_block, low_ts)), Some(&(high_block, high_ts))) => {
+                let span = high_block - low_block;
+                low_ts + (block_number - low_block) * high_ts.saturating_sub(low_ts) / span.max(1)
+            }
+            (Some((_, ts)), None) | (None, Some(&(_, ts))) => ts,
+            (None, None) => 0,
+        }
+    }
+
+    /// ABI of the class currently deployed at `contract_address`. Parsed ABIs are cached per
+    /// class hash, so contracts sharing a class (e.g. token proxies) share one decoder; the
+    /// class of an address is looked up again after `CLASS_HASH_TTL` to pick up upgrades.
+    pub async fn get_contract_abi(&self, contract_address: &str) -> Result<Arc<ContractAbi>, AppError> {
+        let contract = normalize_hex(contract_address);
+
+        let cached_class = self.class_hashes.lock().unwrap()
+            .get(&contract)
+            .filter(|(_, fetched_at)| fetched_at.elapsed() < CLASS_HASH_TTL)
+            .map(|(hash, _)| hash.clone());
+        let class_hash = match cached_class {
+            Some(hash) => hash,
+            None => {
+                let hash = self.rpc_call("starknet_getClassHashAt", json!(["latest", contract])).await?
+                    .as_str()
+                    .map(normalize_hex)
+                    .ok_or_else(|| AppError::BadRequest("Invalid class hash response".to_string()))?;
+                self.class_hashes.lock().unwrap().insert(contract.clone(), (hash.clone(), Instant::now()));
+                hash
+            }
+        };
+
+        self.get_class_abi(&class_hash).await
+    }
+
+    /// ABI of a declared class. Fetched by hash rather than address: a class never changes,
+    /// so both the response and the parsed ABI are cached.
+    pub async fn get_class_abi(&self, class_hash: &str) -> Result<Arc<ContractAbi>, AppError> {
+        let class_hash = normalize_hex(class_hash);
+        if let Some(abi) = self.abi_cache.lock().unwrap().get(&class_hash) {
+            return Ok(abi.clone());
+        }
+
+        let class = self.rpc_call("starknet_getClass", json!(["latest", class_hash])).await?;
+        let abi = Arc::new(ContractAbi::from_class(&class).map_err(AppError::BadRequest)?);
+        log::info!("Loaded ABI for class {} with {} events", class_hash, abi.event_count());
+
+        self.abi_cache.lock().unwrap().insert(class_hash, abi.clone());
+        Ok(abi)
+    }
+
+    /// Calls a contract function at `block` without sending a transaction. Arguments are
+    /// encoded and results decoded with the ABI of the class currently at the address.
+    pub async fn call_contract(
+        &self,
+        contract_address: &str,
+        function: &str,
+        args: &Value,
+        block: BlockId,
+    ) -> Result<ContractCallResult, AppError> {
+        let abi = self.get_contract_abi(contract_address).await?;
+        let entry = abi.function(function)
+            .ok_or_else(|| AppError::NotFound(format!("Function '{}' not in the contract ABI", function)))?;
+        let calldata = abi.encode_calldata(entry, args).map_err(AppError::BadRequest)?;
+
+        let result = self.rpc_call("starknet_call", json!({
+            "request": {
+                "contract_address": normalize_hex(contract_address),
+                "entry_point_selector": selector(function),
+                "calldata": calldata
+            },
+            "block_id": block.to_param()
+        })).await?;
+
+        let result: Vec<String> = result.as_array()
+            .ok_or_else(|| AppError::BadRequest("Invalid call response".to_string()))?
+            .iter()
+            .filter_map(|felt| felt.as_str().map(String::from))
+            .collect();
+        let decoded = abi.decode_outputs(entry, &result).map_err(|e| {
+            AppError::BadRequest(format!("Result does not match the ABI outputs of '{}': {}", function, e))
+        })?;
+
+        Ok(ContractCallResult {
+            contract_address: contract_address.to_string(),
+            function: function.to_string(),
+            state_mutability: entry.state_mutability.clone(),
+            block_number: match block {
+                BlockId::Number(n) => Some(n),
+                BlockId::Pending(_) => None,
+            },
+            calldata,
+            result,
+            decoded,
+        })
+    }
+
+    /// Name, symbol and decimals of a token, read with raw calls so proxies and contracts
+    /// whose ABI lacks the getters resolve too
+    pub async fn token_metadata(&self, contract_address: &str) -> Result<TokenMetadata, AppError> {
+        let address = normalize_hex(contract_address);
+        let calls: Vec<(&str, Value)> = ["name", "symbol", "decimals"].iter()
+            .map(|getter| ("starknet_call", json!({
+                "request": {
+                    "contract_address": address,
+                    "entry_point_selector": selector(getter),
+                    "calldata": []
+                },
+                "block_id": "latest"
+            })))
+            .collect();
+
+        let mut felts: Vec<Option<Vec<String>>> = Vec::with_capacity(calls.len());
+        for result in self.rpc_batch(&calls).await {
+            felts.push(match result {
+                Ok(value) => serde_json::from_value(value).ok(),
+                // The getter failed or does not exist: the token does not expose it
+                Err(AppError::Rpc(..)) => None,
+                // No answer at all; the metadata is unknown rather than partial
+                Err(e) => return Err(e),
+            });
+        }
+        let text = |i: usize| felts[i].as_deref().and_then(decode_text);
+
+        Ok(TokenMetadata {
+            chain: STARKNET.to_string(),
+            name: text(0),
+            symbol: text(1),
+            decimals: felts[2].as_ref()
+                .and_then(|result| result.first())
+                .and_then(|felt| parse_felt(felt))
+                .and_then(|decimals| decimals.to_u8()),
+            address,
+        })
+    }
+
+    /// Reads raw storage of a contract variable at `block`. `keys` are the serialized mapping
+    /// keys (one felt each, two for a u256), chained through nested maps. With `value_type` the
+    /// slot count follows from the type and the value is decoded with the contract ABI.
+    pub async fn read_storage(
+        &self,
+        contract_address: &str,
+        variable: &str,
+        keys: &[BigUint],
+        value_type: Option<&str>,
+        slots: Option<usize>,
+        block: BlockId,
+    ) -> Result<StorageRead, AppError> {
+        let address = storage_address(variable, keys);
+
+        let abi = match value_type {
+            Some(_) => self.try_contract_abi(contract_address).await,
+            None => None,
+        };
+        let abi = abi.unwrap_or_default();
+        let slots = match (slots, value_type) {
+            (Some(slots), _) => slots,
+            (None, Some(ty)) => abi.felt_size(ty).ok_or_else(|| AppError::BadRequest(format!(
+                "{} has no fixed storage size or is not defined in the contract ABI, pass slots explicitly", ty
+            )))?,
+            (None, None) => 1,
+        };
+        if slots == 0 || slots > MAX_STORAGE_SLOTS {
+            return Err(AppError::BadRequest(format!("slots must be between 1 and {}", MAX_STORAGE_SLOTS)));
+        }
+
+        let contract = normalize_hex(contract_address);
+        let calls: Vec<(&str, Value)> = (0..slots)
+            .map(|offset| ("starknet_getStorageAt", json!([
+                contract,
+                felt_to_hex(&(&address + offset)),
+                block.to_param()
+            ])))
+            .collect();
+
+        let values = self.rpc_batch(&calls)
+            .await
+            .into_iter()
+            .map(|result| result?
+                .as_str()
+                .map(String::from)
+                .ok_or_else(|| AppError::BadRequest("Invalid storage response".to_string())))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let decoded = match value_type {
+            Some(ty) => Some(abi.decode_value(ty, &values).map_err(|e| {
+                AppError::BadRequest(format!("Storage does not decode as {}: {}", ty, e))
+            })?),
+            None => None,
+        };
+
+        Ok(StorageRead {
+            contract_address: contract_address.to_string(),
+            variable: variable.to_string(),
+            block_number: match block {
+                BlockId::Number(n) => Some(n),
+                BlockId::Pending(_) => None,
+            },
+            address: felt_to_hex(&address),
+            values,
+            decoded,
+        })
+    }
+
+    /// Everything known about one transaction: body, receipt and, when the provider supports
+    /// `starknet_traceTransaction`, the call trees. Calldata, results and events are decoded
+    /// with the ABI of each contract involved.
+    pub async fn inspect_transaction(&self, transaction_hash: &str) -> Result<TransactionDetails, AppError> {
+        let hash = normalize_hex(transaction_hash);
+        let mut results = self.rpc_batch(&[
+            ("starknet_getTransactionByHash", json!([hash])),
+            ("starknet_getTransactionReceipt", json!([hash])),
+            ("starknet_traceTransaction", json!([hash])),
+        ]).await.into_iter();
+
+        let (Some(tx), Some(receipt), Some(trace)) = (results.next(), results.next(), results.next()) else {
+            return Err(AppError::BadRequest("Incomplete batch response".to_string()));
+        };
+        let tx = tx?;
+        let receipt = receipt?;
+        let (trace, trace_error) = match trace {
+            Ok(trace) => (Some(trace), None),
+            Err(e) => {
+                log::warn!("No trace for {}: {}", hash, e);
+                (None, Some(e.to_string()))
+            }
+        };
+
+        let mut contracts = emitting_contracts(&receipt);
+        if let Some(trace) = &trace {
+            contracts.extend(invoked_contracts(trace));
+        }
+        contracts.sort_unstable();
+        contracts.dedup();
+        let classes = trace.as_ref().map(invoked_classes).unwrap_or_default();
+
+        let (lookups, class_lookups) = futures::future::join(
+            futures::future::join_all(contracts.iter().map(|address| self.try_contract_abi(address))),
+            futures::future::join_all(classes.iter().map(|hash| self.try_class_abi(hash))),
+        ).await;
+        let abis: HashMap<String, Arc<ContractAbi>> = contracts.into_iter()
+            .zip(lookups)
+            .filter_map(|(address, abi)| abi.map(|abi| (address, abi)))
+            .collect();
+        let class_abis: HashMap<String, Arc<ContractAbi>> = classes.into_iter()
+            .zip(class_lookups)
+            .filter_map(|(hash, abi)| abi.map(|abi| (hash, abi)))
+            .collect();
+
+        let text = |value: &Value, field: &str| value.get(field).and_then(|v| v.as_str()).map(String::from);
+
+        Ok(TransactionDetails {
+            transaction_hash: hash,
+            tx_type: text(&tx, "type"),
+            version: text(&tx, "version"),
+            sender_address: text(&tx, "sender_address"),
+            block_number: receipt.get("block_number").and_then(|v| v.as_u64()),
+            finality_status: text(&receipt, "finality_status"),
+            execution_status: text(&receipt, "execution_status"),
+            revert_reason: text(&receipt, "revert_reason"),
+            fee: FeeBreakdown::new(&tx, &receipt),
+            events: receipt_events(&receipt, &abis, self),
+            messages_to_l1: receipt.get("messages_sent")
+                .and_then(|v| v.as_array())
+                .cloned()
+                .unwrap_or_default(),
+            trace: trace.map(|trace| parse_trace(&trace, &abis, &class_abis, self)),
+            trace_error,
+        })
+    }
+
+    /// Class ABI lookup that never fails the caller, like `try_contract_abi`
+    pub async fn try_class_abi(&self, class_hash: &str) -> Option<Arc<ContractAbi>> {
+        match self.get_class_abi(class_hash).await {
+            Ok(abi) => Some(abi),
+            Err(e) => {
+                log::warn!("No ABI for class {}: {}", class_hash, e);
+                None
+            }
+        }
+    }
+
+    /// ABI lookup that never fails the caller: events fall back to the built-in decoders
+    pub async fn try_contract_abi(&self, contract_address: &str) -> Option<Arc<ContractAbi>> {
+        match self.get_contract_abi(contract_address).await {
+            Ok(abi) => Some(abi),
+            Err(e) => {
+                log::warn!("No ABI for {}, using built-in event decoders: {}", contract_address, e);
+                None
+            }
+        }
+    }
+
+    /// Decodes an event with the contract ABI when available, otherwise with the built-in
+    /// Transfer/Approval decoders
+    pub fn decode_event(&self, abi: Option<&ContractAbi>, keys: &[String], data: &[String]) -> (String, Value) {
+        if let Some(decoded) = abi.and_then(|abi| abi.decode_event(keys, data)) {
+            return decoded;
+        }
+
+        if keys.is_empty() {
+            return ("Unknown Event".to_string(), json!({}));
+        }
+
+        let key = normalize_hex(&keys[0]);
+        let event = match key.as_str() {
+            "0x99cd8bde557814842a3121e8ddfd433a539b8c9f14bf31ebf108d12e6196e9" => Some(("Transfer", "from", "to")),
+            "0x1dcde06aabdbca2f80aa51392b345d7549d7757aa855f7e37f5d335ac8243b1" => Some(("Approval", "owner", "spender")),
+            _ => None,
+        };
+
+        if let Some((name, first, second)) = event {
+            if let Some(token_event) = TokenEvent::parse(keys, data) {
+                let mut fields = serde_json::Map::new();
+                fields.insert(first.to_string(), json!(token_event.first));
+                fields.insert(second.to_string(), json!(token_event.second));
+                fields.insert(token_event.value_field.to_string(), json!(token_event.value));
+                return (name.to_string(), Value::Object(fields));
+            }
+        }
+
+        ("Unknown Event".to_string(), json!({}))
+    }
+
+    /// Transactions sent by or to a contract over a block range, with their receipts
+    pub async fn scan_contract(
+        &self,
+        contract_address: &str,
+        from_block: Option<u64>,
+        to_block: Option<u64>,
+        finality: Finality,
+    ) -> Result<ContractScan, AppError> {
+        let current_block = self.get_block_number().await?;
+        // Pending analysis also reads the pending block, numbered one past the latest block
+        let head = match finality {
+            Finality::L1Accepted => self.l1_accepted_head().await?,
+            Finality::Latest => current_block,
+            Finality::Pending => current_block + 1,
+        };
+        let from_block = from_block.unwrap_or_else(|| head.saturating_sub(1000));
+        let to_block = to_block.unwrap_or(head).min(head);
+
+        if from_block > to_block {
+            return Err(AppError::BadRequest(format!(
+                "Invalid block range: {} is after {}", from_block, to_block
+            )));
+        }
+
+        let search_blocks = to_block - from_block + 1;
+        let mut contract_transactions = Vec::new();
+        let mut contract_receipts: Vec<TransactionReceipt> = Vec::new();
+        let mut failed_blocks = Vec::new();
+        let mut blocks_analyzed = 0usize;
+
+        log::info!(
+            "Analyzing {} blocks of {} with {} workers",
+            search_blocks, contract_address, self.scan_workers
+        );
+
+        // Each worker fetches one batch of blocks; blocks missing from a batch are retried alone
+        // Chunks are owned so the scan future stays `Send` behind the `ChainClient` trait
+        let pending_block = (to_block > current_block).then_some(to_block);
+        let block_numbers: Vec<u64> = (from_block..=to_block.min(current_block)).rev().collect();
+        let chunks: Vec<Vec<u64>> = block_numbers.chunks(self.batch_size).map(<[u64]>::to_vec).collect();
+        let pending = stream::iter(pending_block)
+            .then(|n| async move { (n, self.get_block_with_retry(BlockId::Pending(n)).await) });
+        let mut blocks = std::pin::pin!(pending.chain(
+            stream::iter(chunks)
+                .map(|chunk| async move {
+                    let mut fetched = Vec::with_capacity(chunk.len());
+                    for (block_num, result) in self.get_blocks_with_receipts(&chunk).await {
+                        let result = match result {
+                            Ok(block) => Ok(block),
+                            Err(_) => self.get_block_with_retry(BlockId::Number(block_num)).await,
+                        };
+                        fetched.push((block_num, result));
+                    }
+                    fetched
+                })
+                .buffer_unordered(self.scan_workers)
+                .flat_map(stream::iter),
+        ));
+
+        while let Some((block_num, result)) = blocks.next().await {
+            let block = match result {
+                Ok(block) => block,
+                Err(e) => {
+                    log::warn!("Giving up on block {}: {}", block_num, e);
+                    failed_blocks.push(block_num);
+                    continue;
+                }
+            };
+            blocks_analyzed += 1;
+
+            for tx in block.transactions {
+                let sender = tx.get("sender_address")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("");
+
+                let contract = tx.get("contract_address")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("");
+
+                if sender == contract_address || contract == contract_address {
+                    let tx_hash = tx.get("transaction_hash")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("")
+                        .to_string();
+
+                    let (max_fee, fee_unit) = fee_cap(&tx);
+                    let receipt = block.receipts.get(&normalize_hex(&tx_hash));
+                    contract_receipts.extend(receipt.cloned());
+
+                    let tx_type = tx.get("type")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("INVOKE")
+                        .to_string();
+
+                    contract_transactions.push(TransactionInfo {
+                        block_number: block_num,
+                        transaction_hash: tx_hash,
+                        sender_address: sender.to_string(),
+                        contract_address: contract_address.to_string(),
+                        max_fee: format!("{:#x}", max_fee),
+                        actual_fee: receipt.map(|r| format!("{:#x}", r.actual_fee)),
+                        fee_unit: receipt.map_or(fee_unit, |r| r.fee_unit),
+                        execution_status: receipt.map(|r| r.execution_status),
+                        revert_reason: receipt.and_then(|r| r.revert_reason.clone()),
+                        tx_type,
+                        timestamp: block.timestamp,
+                        finality_status: block.status,
+                    });
+                }
+            }
+        }
+
+        Ok(ContractScan {
+            contract_address: contract_address.to_string(),
+            transactions: contract_transactions,
+            receipts: contract_receipts,
+            blocks_analyzed,
+            failed_blocks,
             current_block,
+            finality,
             from_block,
             to_block,
-            transactions: contract_transactions.into_iter().take(10).collect(),
+            native_symbol: STARKNET_NATIVE_SYMBOL.to_string(),
         })
     }
 }
 
 impl Default for RpcService {
     fn default() -> Self {
-        Self::new()
+        Self::new(RpcProviderConfig::defaults(), 3, Duration::from_secs(30))
+    }
+}
+
+#[cfg(test)]
+impl RpcService {
+    /// A service answering only from the recordings under `tests/fixtures/cassettes/<namespace>`
+    pub fn replayed(namespace: &str) -> Self {
+        let provider = RpcProviderConfig {
+            url: "http://replay.invalid".to_string(),
+            weight: 1,
+            timeout_secs: 10,
+            api_key: None,
+        };
+        let dir = std::path::Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/cassettes")).join(namespace);
+        let cassette = Cassette::new(crate::services::cassette::CassetteMode::Replay, dir);
+        Self::new(vec![provider], 3, Duration::from_secs(30)).with_cassette(cassette)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn felts(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_cairo1_transfer_with_u256_amount() {
+        let keys = felts(&["0x99cd", "0xa", "0xb"]);
+        let data = felts(&["0x0", "0x1"]);
+        let event = TokenEvent::parse(&keys, &data).expect("parses");
+        assert_eq!((event.first, event.second, event.value_field), ("0xa", "0xb", "amount"));
+        assert_eq!(event.value, (BigUint::from(1u8) << 128u32).to_string());
+    }
+
+    #[test]
+    fn parses_erc721_transfer_with_indexed_token_id() {
+        let keys = felts(&["0x99cd", "0xa", "0xb", "0x2a", "0x0"]);
+        let event = TokenEvent::parse(&keys, &[]).expect("parses");
+        assert_eq!(event.value_field, "token_id");
+        assert_eq!(event.value, "42");
+    }
+
+    #[test]
+    fn parses_cairo0_transfer_from_data() {
+        let keys = felts(&["0x99cd"]);
+        let data = felts(&["0xa", "0xb", "0x64", "0x0"]);
+        let event = TokenEvent::parse(&keys, &data).expect("parses");
+        assert_eq!((event.first, event.second, event.value.as_str()), ("0xa", "0xb", "100"));
+
+        let data = felts(&["0xa", "0xb", "0x64"]);
+        let single = TokenEvent::parse(&keys, &data).expect("parses");
+        assert_eq!(single.value, "100");
+    }
+
+    #[test]
+    fn rejects_events_of_other_shapes() {
+        assert!(TokenEvent::parse(&felts(&["0x99cd"]), &felts(&["0xa"])).is_none());
+        assert!(TokenEvent::parse(&felts(&["0x99cd", "0xa", "0xb"]), &felts(&["0x1", "0x2", "0x3"])).is_none());
+        assert!(TokenEvent::parse(&felts(&["0x99cd", "0xa", "0xb"]), &felts(&["not a felt"])).is_none());
+    }
+
+    // Blocks 100-102 of a recorded chain: a reverted WEI-paid invoke, a FRI-paid account
+    // deployment next to an unrelated transaction, and a pre-0.6 receipt with a bare fee
+    #[tokio::test]
+    async fn analyzes_replayed_blocks() {
+        let rpc = RpcService::replayed("starknet");
+        let analysis = rpc.scan_contract("0x4a1b2c3d", Some(100), None, Finality::Latest)
+            .await
+            .expect("replays")
+            .into_analysis();
+
+        assert_eq!((analysis.from_block, analysis.to_block, analysis.current_block), (100, 102, 102));
+        assert_eq!(analysis.blocks_analyzed, 3);
+        assert!(analysis.failed_blocks.is_empty());
+        assert_eq!(analysis.transaction_count, 3);
+        assert_eq!((analysis.successful_txs, analysis.reverted_txs), (2, 1));
+        assert_eq!(analysis.unique_senders, 2);
+
+        // WEI totals leave out the FRI-paid deployment
+        assert_eq!(analysis.total_fees, "0.04");
+        assert_eq!(analysis.avg_fee, "0.02");
+        let units: Vec<(FeeUnit, usize, &str)> = analysis.fees.iter()
+            .map(|fee| (fee.unit, fee.transaction_count, fee.total.as_str()))
+            .collect();
+        assert_eq!(units, vec![(FeeUnit::Wei, 2, "0.04"), (FeeUnit::Fri, 1, "0.0001")]);
+
+        let newest = &analysis.transactions[0];
+        assert_eq!((newest.block_number, newest.transaction_hash.as_str()), (102, "0x102a"));
+        let reverted = analysis.transactions.iter().find(|tx| tx.block_number == 100).expect("block 100");
+        assert_eq!(reverted.revert_reason.as_deref(), Some("Out of gas"));
+    }
+
+    // The recorded chain's pending block holds one more invoke by the contract
+    #[tokio::test]
+    async fn analyzes_the_pending_block() {
+        let rpc = RpcService::replayed("starknet");
+        let scan = rpc.scan_contract("0x4a1b2c3d", Some(102), None, Finality::Pending).await.expect("replays");
+
+        assert_eq!((scan.from_block, scan.to_block, scan.current_block), (102, 103, 102));
+        assert_eq!(scan.blocks_analyzed, 2);
+        let found: Vec<(u64, &str, BlockStatus)> = scan.transactions.iter()
+            .map(|tx| (tx.block_number, tx.transaction_hash.as_str(), tx.finality_status))
+            .collect();
+        assert!(found.contains(&(103, "0x103a", BlockStatus::Pending)), "{:?}", found);
+        assert!(found.contains(&(102, "0x102a", BlockStatus::AcceptedOnL2)), "{:?}", found);
+
+        // Latest stops short of the pending block
+        let latest = rpc.scan_contract("0x4a1b2c3d", Some(102), None, Finality::Latest).await.expect("replays");
+        assert_eq!((latest.to_block, latest.transactions.len()), (102, 1));
+    }
+
+    // Contract 0xa emits Transfer(0x1 -> 0xb) and an Approval in block 10's first
+    // transaction, Transfer(0x2 -> 0xc) in its second and Transfer(0x3 -> 0xb) in block 11.
+    // The recordings only answer a getEvents call that carries the key pattern.
+    #[tokio::test]
+    async fn sends_key_filters_to_the_node() {
+        let keys = vec![vec![selector("Transfer")], vec![], vec!["0xb".to_string()]];
+        let fetch = RpcService::replayed("starknet").fetch_events("0xa", 10, BlockId::Number(11), &keys, None)
+            .await
+            .expect("replays");
+
+        assert!(fetch.complete);
+        let found: Vec<(u64, &str, u64, Option<u64>)> = fetch.events.iter()
+            .map(|event| (event.block_number, event.transaction_hash.as_str(), event.event_index, event.receipt_index))
+            .collect();
+        assert_eq!(found, vec![(10, "0x100", 0, Some(0)), (11, "0x300", 0, Some(0))]);
+        assert!(fetch.events.iter().all(|event| event.keys[2] == "0xb"));
     }
 }
@@ -0,0 +1,405 @@
+use lru::LruCache;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::db::DbPool;
+use crate::services::chain::STARKNET;
+
+// Methods whose result cannot change once the block, transaction or class they name is fixed
+const CACHEABLE_METHODS: [&str; 17] = [
+    "starknet_getBlockWithReceipts",
+    "starknet_getBlockWithTxHashes",
+    "starknet_getBlockWithTxs",
+    "starknet_getStateUpdate",
+    "starknet_getTransactionByHash",
+    "starknet_getTransactionReceipt",
+    "starknet_traceTransaction",
+    "starknet_getEvents",
+    "starknet_call",
+    "starknet_getStorageAt",
+    "starknet_getClass",
+    "eth_getBlockByNumber",
+    "eth_getBlockReceipts",
+    "eth_getLogs",
+    "eth_call",
+    "eth_getTransactionByHash",
+    "eth_getTransactionReceipt",
+];
+
+// Block tags that resolve to a different block over time
+const MOVING_TAGS: [&str; 4] = ["latest", "pending", "finalized", "safe"];
+
+// Entries kept in SQLite unless configured otherwise
+const DEFAULT_SPILL_LIMIT: usize = 100_000;
+// Spilled entries between two prunings of the SQLite table
+const SPILL_PRUNE_INTERVAL: u64 = 500;
+
+#[derive(Debug, Serialize)]
+pub struct RpcCacheStats {
+    pub capacity: usize,
+    pub entries: usize,
+    /// Whether entries evicted from memory are kept in SQLite
+    pub spill: bool,
+    pub memory_hits: u64,
+    pub spill_hits: u64,
+    pub misses: u64,
+    /// Calls that were never cacheable (moving block tags, mutable methods)
+    pub bypassed: u64,
+    pub stored: u64,
+    /// Hits over cacheable lookups
+    pub hit_rate: f64,
+}
+
+struct CacheState {
+    memory: Mutex<LruCache<String, Value>>,
+    capacity: usize,
+    spill: Option<DbPool>,
+    spilled: AtomicU64,
+    memory_hits: AtomicU64,
+    spill_hits: AtomicU64,
+    misses: AtomicU64,
+    bypassed: AtomicU64,
+    stored: AtomicU64,
+}
+
+/// Cache of immutable JSON-RPC results, keyed by method and canonical params. Only calls
+/// naming an explicit block number, hash or class are cached, and only once their result
+/// is final: Starknet data must be accepted on L1, and anything read at or up to a block
+/// number must be at or below the chain's finalized head.
+///
+/// Entries live in an in-memory LRU; with a pool, entries it evicts spill to SQLite and
+/// are read back from there on a memory miss. The SQLite table keeps the newest
+/// `spill_limit` entries. Clones share storage and statistics.
+#[derive(Clone)]
+pub struct RpcCache {
+    namespace: String,
+    /// Finalized head of the namespace's chain as last reported by its client; 0 until known
+    finalized: Arc<AtomicU64>,
+    spill_limit: usize,
+    state: Arc<CacheState>,
+}
+
+impl RpcCache {
+    pub fn new(capacity: usize, spill: Option<DbPool>) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            namespace: STARKNET.to_string(),
+            finalized: Arc::new(AtomicU64::new(0)),
+            spill_limit: DEFAULT_SPILL_LIMIT,
+            state: Arc::new(CacheState {
+                memory: Mutex::new(LruCache::new(NonZeroUsize::new(capacity).expect("non-zero capacity"))),
+                capacity,
+                spill,
+                spilled: AtomicU64::new(0),
+                memory_hits: AtomicU64::new(0),
+                spill_hits: AtomicU64::new(0),
+                misses: AtomicU64::new(0),
+                bypassed: AtomicU64::new(0),
+                stored: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Most entries kept in the SQLite spill table; older ones are pruned
+    pub fn with_spill_limit(mut self, entries: usize) -> Self {
+        self.spill_limit = entries.max(1);
+        self
+    }
+
+    /// The same storage for another chain; keys are prefixed so chains never collide
+    pub fn scoped(&self, namespace: &str) -> Self {
+        Self {
+            namespace: namespace.to_string(),
+            finalized: Arc::new(AtomicU64::new(0)),
+            spill_limit: self.spill_limit,
+            state: self.state.clone(),
+        }
+    }
+
+    /// Records the chain's finalized head (L1-accepted on Starknet). Results read at or up
+    /// to later blocks are not stored.
+    pub fn set_finalized_head(&self, block: u64) {
+        self.finalized.fetch_max(block, Ordering::Relaxed);
+    }
+
+    /// Cache key of a call, or `None` when its result may still change
+    pub fn key(&self, method: &str, params: &Value) -> Option<String> {
+        let cacheable = CACHEABLE_METHODS.contains(&method)
+            // A class is identified by its hash whatever block it is read at
+            && (method == "starknet_getClass" || !names_moving_block(params));
+        if !cacheable {
+            self.state.bypassed.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+        Some(format!("{}:{}:{}", self.namespace, method, canonical(params)))
+    }
+
+    pub async fn get(&self, key: &str) -> Option<Value> {
+        if let Some(value) = self.state.memory.lock().unwrap().get(key) {
+            self.state.memory_hits.fetch_add(1, Ordering::Relaxed);
+            return Some(value.clone());
+        }
+
+        if let Some(pool) = &self.state.spill {
+            let stored: Option<(String,)> = sqlx::query_as("SELECT response FROM rpc_cache WHERE cache_key = ?")
+                .bind(key)
+                .fetch_optional(pool)
+                .await
+                .unwrap_or_else(|e| {
+                    log::warn!("RPC cache spill lookup failed: {}", e);
+                    None
+                });
+            if let Some(value) = stored.and_then(|(response,)| serde_json::from_str::<Value>(&response).ok()) {
+                self.state.spill_hits.fetch_add(1, Ordering::Relaxed);
+                self.insert(key.to_string(), value.clone());
+                return Some(value);
+            }
+        }
+
+        self.state.misses.fetch_add(1, Ordering::Relaxed);
+        None
+    }
+
+    /// Keeps the result of a call if it is final; results of not-yet-final data are dropped
+    pub fn store(&self, key: &str, method: &str, params: &Value, result: &Value) {
+        if self.is_final(method, params, result) {
+            self.state.stored.fetch_add(1, Ordering::Relaxed);
+            self.insert(key.to_string(), result.clone());
+        }
+    }
+
+    pub fn stats(&self) -> RpcCacheStats {
+        let state = &self.state;
+        let memory_hits = state.memory_hits.load(Ordering::Relaxed);
+        let spill_hits = state.spill_hits.load(Ordering::Relaxed);
+        let misses = state.misses.load(Ordering::Relaxed);
+        let lookups = memory_hits + spill_hits + misses;
+
+        RpcCacheStats {
+            capacity: state.capacity,
+            entries: state.memory.lock().unwrap().len(),
+            spill: state.spill.is_some(),
+            memory_hits,
+            spill_hits,
+            misses,
+            bypassed: state.bypassed.load(Ordering::Relaxed),
+            stored: state.stored.load(Ordering::Relaxed),
+            hit_rate: if lookups == 0 { 0.0 } else { (memory_hits + spill_hits) as f64 / lookups as f64 },
+        }
+    }
+
+    // Whether a result can no longer change. Starknet blocks, receipts and transaction
+    // statuses carry a status that must have reached L1; pending EVM transactions are not
+    // final. Anything else read at or up to a block number (events, logs, calls, EVM
+    // blocks and receipts) must be at or below the finalized head.
+    fn is_final(&self, method: &str, params: &Value, result: &Value) -> bool {
+        if result.is_null() {
+            return false;
+        }
+        let status = result.get("finality_status")
+            .or_else(|| result.get("status"))
+            .and_then(|v| v.as_str())
+            .filter(|status| status.starts_with("ACCEPTED_ON") || *status == "PENDING");
+        if let Some(status) = status {
+            return status == "ACCEPTED_ON_L1";
+        }
+        if matches!(result.get("blockNumber"), Some(Value::Null)) {
+            return false;
+        }
+
+        let finalized = self.finalized.load(Ordering::Relaxed);
+        match named_block(params, method.starts_with("eth_")).max(result_block(result)) {
+            Some(block) => finalized > 0 && block <= finalized,
+            None => true,
+        }
+    }
+
+    // Memory insert; the entry it evicts, if any, is written to SQLite in the background
+    fn insert(&self, key: String, value: Value) {
+        let evicted = self.state.memory.lock().unwrap().push(key.clone(), value);
+        let (Some(pool), Some((evicted_key, evicted_value))) = (self.state.spill.clone(), evicted) else {
+            return;
+        };
+        if evicted_key == key {
+            return;
+        }
+
+        let prune = self.state.spilled.fetch_add(1, Ordering::Relaxed).is_multiple_of(SPILL_PRUNE_INTERVAL);
+        let limit = self.spill_limit;
+        tokio::spawn(async move {
+            let method = evicted_key.split(':').nth(1).unwrap_or_default().to_string();
+            let result = sqlx::query("INSERT OR REPLACE INTO rpc_cache (cache_key, method, response) VALUES (?, ?, ?)")
+                .bind(&evicted_key)
+                .bind(method)
+                .bind(evicted_value.to_string())
+                .execute(&pool)
+                .await;
+            if let Err(e) = result {
+                log::warn!("Failed to spill RPC cache entry: {}", e);
+            }
+            if !prune {
+                return;
+            }
+
+            // Everything past the newest `limit` entries
+            let pruned = sqlx::query(
+                "DELETE FROM rpc_cache WHERE rowid IN
+                 (SELECT rowid FROM rpc_cache ORDER BY created_at DESC, rowid DESC LIMIT -1 OFFSET ?)"
+            )
+            .bind(limit as i64)
+            .execute(&pool)
+            .await;
+            match pruned {
+                Ok(done) if done.rows_affected() > 0 => log::info!("Pruned {} spilled RPC cache entries", done.rows_affected()),
+                Ok(_) => {}
+                Err(e) => log::warn!("Failed to prune the RPC cache spill table: {}", e),
+            }
+        });
+    }
+}
+
+// True when any param refers to a block by a tag rather than a number or hash
+fn names_moving_block(params: &Value) -> bool {
+    match params {
+        Value::String(tag) => MOVING_TAGS.contains(&tag.as_str()),
+        Value::Array(items) => items.iter().any(names_moving_block),
+        Value::Object(fields) => fields.values().any(names_moving_block),
+        _ => false,
+    }
+}
+
+// Highest block number a call names: Starknet `block_number` fields, EVM `fromBlock` and
+// `toBlock`, and on EVM chains the block quantities passed as positional params
+fn named_block(params: &Value, evm: bool) -> Option<u64> {
+    match params {
+        Value::Array(items) => items.iter()
+            .map(|item| match item {
+                Value::String(text) if evm => block_quantity(text),
+                other => named_block(other, evm),
+            })
+            .max()
+            .flatten(),
+        Value::Object(fields) => fields.iter()
+            .map(|(name, value)| match (name.as_str(), value) {
+                ("block_number", value) => value.as_u64(),
+                ("fromBlock" | "toBlock", Value::String(text)) => block_quantity(text),
+                (_, value) => named_block(value, evm),
+            })
+            .max()
+            .flatten(),
+        _ => None,
+    }
+}
+
+// Block a result belongs to, for results fetched by hash
+fn result_block(result: &Value) -> Option<u64> {
+    result.get("block_number")
+        .and_then(|v| v.as_u64())
+        .or_else(|| ["blockNumber", "number"].iter().find_map(|field| result.get(field)?.as_str().and_then(block_quantity)))
+}
+
+// A hex quantity short enough to be a block number; hashes, addresses and calldata are not
+fn block_quantity(text: &str) -> Option<u64> {
+    text.strip_prefix("0x")
+        .filter(|hex| !hex.is_empty() && hex.len() <= 16)
+        .and_then(|hex| u64::from_str_radix(hex, 16).ok())
+}
+
+/// Params with object keys sorted and hex strings lowercased, so equivalent calls share a key
+pub fn canonical(params: &Value) -> Value {
+    match params {
+        Value::String(text) if text.starts_with("0x") || text.starts_with("0X") => Value::String(text.to_lowercase()),
+        Value::Array(items) => Value::Array(items.iter().map(canonical).collect()),
+        Value::Object(fields) => {
+            let mut keys: Vec<&String> = fields.keys().collect();
+            keys.sort();
+            let mut sorted = Map::new();
+            for key in keys {
+                sorted.insert(key.clone(), canonical(&fields[key]));
+            }
+            Value::Object(sorted)
+        }
+        other => other.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn cached(cache: &RpcCache, method: &str, params: Value, result: Value) -> Option<String> {
+        let key = cache.key(method, &params)?;
+        cache.store(&key, method, &params, &result);
+        Some(key)
+    }
+
+    #[tokio::test]
+    async fn bypasses_moving_blocks_and_mutable_methods() {
+        let cache = RpcCache::new(10, None);
+        assert!(cache.key("eth_getBlockByNumber", &json!(["latest", false])).is_none());
+        assert!(cache.key("starknet_call", &json!({"block_id": "pending"})).is_none());
+        assert!(cache.key("eth_blockNumber", &json!([])).is_none());
+        assert!(cache.key("starknet_getClass", &json!({"block_id": "latest", "class_hash": "0x1"})).is_some());
+        assert_eq!(cache.stats().bypassed, 3);
+    }
+
+    #[tokio::test]
+    async fn keys_equivalent_calls_alike_per_chain() {
+        let cache = RpcCache::new(10, None);
+        let key = cache.key("eth_getLogs", &json!([{"toBlock": "0xA", "fromBlock": "0x1"}]));
+        assert_eq!(key, cache.key("eth_getLogs", &json!([{"fromBlock": "0x1", "toBlock": "0xa"}])));
+        assert_ne!(key, cache.scoped("ethereum").key("eth_getLogs", &json!([{"fromBlock": "0x1", "toBlock": "0xa"}])));
+    }
+
+    #[tokio::test]
+    async fn stores_only_final_results() {
+        let cache = RpcCache::new(10, None).scoped("ethereum");
+        let block = |n: &str| json!({"number": n, "transactions": []});
+
+        // Nothing is final before the finalized head is known
+        let early = cached(&cache, "eth_getBlockByNumber", json!(["0x5", false]), block("0x5")).unwrap();
+        assert!(cache.get(&early).await.is_none());
+
+        cache.set_finalized_head(10);
+        let done = cached(&cache, "eth_getBlockByNumber", json!(["0xa", false]), block("0xa")).unwrap();
+        let recent = cached(&cache, "eth_getBlockByNumber", json!(["0xb", false]), block("0xb")).unwrap();
+        let pending = cached(&cache, "eth_getTransactionByHash", json!([format!("0x{}", "ab".repeat(32))]), json!({"blockNumber": null})).unwrap();
+        assert_eq!(cache.get(&done).await, Some(block("0xa")));
+        assert!(cache.get(&recent).await.is_none());
+        assert!(cache.get(&pending).await.is_none());
+
+        let starknet = RpcCache::new(10, None);
+        let l2 = cached(&starknet, "starknet_getTransactionReceipt", json!({"transaction_hash": "0x1"}), json!({"finality_status": "ACCEPTED_ON_L2"})).unwrap();
+        let l1 = cached(&starknet, "starknet_getTransactionReceipt", json!({"transaction_hash": "0x2"}), json!({"finality_status": "ACCEPTED_ON_L1"})).unwrap();
+        assert!(starknet.get(&l2).await.is_none());
+        assert!(starknet.get(&l1).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn reads_evicted_entries_back_from_sqlite() {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new().max_connections(1).connect("sqlite::memory:").await.unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        let cache = RpcCache::new(1, Some(pool.clone()));
+        cache.set_finalized_head(100);
+
+        let first = cached(&cache, "starknet_getEvents", json!({"filter": {"to_block": {"block_number": 1}}}), json!({"events": [1]})).unwrap();
+        cached(&cache, "starknet_getEvents", json!({"filter": {"to_block": {"block_number": 2}}}), json!({"events": [2]})).unwrap();
+
+        // The eviction is written in the background
+        for _ in 0..50 {
+            let (spilled,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM rpc_cache").fetch_one(&pool).await.unwrap();
+            if spilled > 0 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+
+        assert_eq!(cache.get(&first).await, Some(json!({"events": [1]})));
+        let stats = cache.stats();
+        assert_eq!((stats.memory_hits, stats.spill_hits, stats.entries), (0, 1, 1));
+    }
+}
@@ -0,0 +1,884 @@
+use num_bigint::{BigInt, BigUint};
+use num_traits::{Num, One, ToPrimitive, Zero};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+use crate::utils::felt::{
+    felt_to_hex, felt_to_short_string, felt_to_signed, normalize_hex, parse_felt, selector, short_string_to_felt,
+    signed_to_felt, u256_from_parts, FIELD_PRIME,
+};
+
+// Deepest selector path tried when matching keys (component events nested two levels down)
+const MAX_EVENT_PATH: usize = 3;
+
+#[derive(Debug, Clone)]
+pub struct AbiMember {
+    pub name: String,
+    pub ty: String,
+    /// Event member placement: "key", "data", "nested" or "flat" (None outside events)
+    pub kind: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AbiFunction {
+    pub name: String,
+    pub inputs: Vec<AbiMember>,
+    /// Sierra outputs are unnamed; Cairo 0 outputs carry names
+    pub outputs: Vec<AbiMember>,
+    pub state_mutability: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct EventLayout {
+    pub name: String,
+    pub members: Vec<AbiMember>,
+}
+
+/// Parsed contract ABI with the selector-to-event map used to decode emitted events and the
+/// functions used to encode calls. Handles both Sierra (Cairo 1) and legacy Cairo 0 ABIs.
+#[derive(Debug, Default)]
+pub struct ContractAbi {
+    legacy: bool,
+    structs: HashMap<String, Vec<AbiMember>>,
+    enums: HashMap<String, Vec<AbiMember>>,
+    events: HashMap<Vec<String>, EventLayout>,
+    functions: HashMap<String, AbiFunction>,
+}
+
+// Sequential reader over a list of hex felts
+struct FeltReader<'a> {
+    felts: &'a [String],
+    position: usize,
+}
+
+impl<'a> FeltReader<'a> {
+    fn new(felts: &'a [String]) -> Self {
+        Self { felts, position: 0 }
+    }
+
+    fn next(&mut self) -> Result<BigUint, String> {
+        let raw = self.felts
+            .get(self.position)
+            .ok_or_else(|| "not enough felts".to_string())?;
+        self.position += 1;
+        parse_felt(raw).ok_or_else(|| format!("invalid felt {}", raw))
+    }
+
+    fn next_usize(&mut self) -> Result<usize, String> {
+        self.next()?
+            .to_usize()
+            .ok_or_else(|| "length does not fit in usize".to_string())
+    }
+}
+
+impl ContractAbi {
+    /// Builds the ABI from a `starknet_getClassAt` / `starknet_getClass` result. Sierra classes
+    /// carry the ABI as a JSON string, Cairo 0 classes as an array.
+    pub fn from_class(class: &Value) -> Result<Self, String> {
+        let entries = match class.get("abi") {
+            Some(Value::String(raw)) => serde_json::from_str::<Value>(raw)
+                .map_err(|e| format!("Invalid ABI JSON: {}", e))?,
+            Some(abi @ Value::Array(_)) => abi.clone(),
+            _ => return Err("Class has no ABI".to_string()),
+        };
+
+        let entries = entries.as_array().ok_or("ABI is not an array")?;
+        Ok(Self::from_entries(entries, class.get("sierra_program").is_none()))
+    }
+
+    fn from_entries(entries: &[Value], legacy: bool) -> Self {
+        let mut abi = ContractAbi {
+            legacy,
+            ..Default::default()
+        };
+        let mut event_structs: HashMap<String, Vec<AbiMember>> = HashMap::new();
+        let mut event_enums: HashMap<String, Vec<AbiMember>> = HashMap::new();
+
+        for entry in entries {
+            let name = entry.get("name").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+            match entry.get("type").and_then(|v| v.as_str()) {
+                Some("struct") => {
+                    abi.structs.insert(name, parse_members(entry.get("members")));
+                }
+                Some("function") => abi.add_function(entry),
+                Some("interface") => {
+                    // Sierra: functions grouped under the interfaces the contract implements
+                    for item in entry.get("items").and_then(|v| v.as_array()).into_iter().flatten() {
+                        if item.get("type").and_then(|v| v.as_str()) == Some("function") {
+                            abi.add_function(item);
+                        }
+                    }
+                }
+                Some("enum") => {
+                    abi.enums.insert(name, parse_members(entry.get("variants")));
+                }
+                Some("event") if legacy => {
+                    // Cairo 0: selector of the event name, explicit key and data lists
+                    let mut members: Vec<AbiMember> = parse_members(entry.get("keys"))
+                        .into_iter()
+                        .map(|m| AbiMember { kind: Some("key".to_string()), ..m })
+                        .collect();
+                    members.extend(parse_members(entry.get("data")).into_iter()
+                        .map(|m| AbiMember { kind: Some("data".to_string()), ..m }));
+                    abi.events.insert(vec![selector(&name)], EventLayout { name, members });
+                }
+                Some("event") => match entry.get("kind").and_then(|v| v.as_str()) {
+                    Some("struct") => {
+                        event_structs.insert(name, parse_members(entry.get("members")));
+                    }
+                    Some("enum") => {
+                        event_enums.insert(name, parse_members(entry.get("variants")));
+                    }
+                    _ => {
+                        // Pre-2.0 compilers: every input is data, selected by the short name
+                        let members = parse_members(entry.get("inputs"))
+                            .into_iter()
+                            .map(|m| AbiMember { kind: Some("data".to_string()), ..m })
+                            .collect();
+                        let short = short_name(&name).to_string();
+                        abi.events.insert(vec![selector(&short)], EventLayout { name: short, members });
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        // The contract's root event enum is the one no other event enum refers to
+        let referenced: std::collections::HashSet<&String> = event_enums.values()
+            .flatten()
+            .map(|variant| &variant.ty)
+            .collect();
+        let roots: Vec<&String> = event_enums.keys().filter(|name| !referenced.contains(name)).collect();
+
+        for root in roots {
+            abi.collect_events(root, &[], &event_structs, &event_enums, 0);
+        }
+
+        abi
+    }
+
+    // Walks an event enum: `nested` variants append their selector to the key path,
+    // `flat` variants defer to the selectors of the inner enum.
+    fn collect_events(
+        &mut self,
+        enum_name: &str,
+        path: &[String],
+        event_structs: &HashMap<String, Vec<AbiMember>>,
+        event_enums: &HashMap<String, Vec<AbiMember>>,
+        depth: usize,
+    ) {
+        if depth > MAX_EVENT_PATH {
+            return;
+        }
+        let Some(variants) = event_enums.get(enum_name) else {
+            return;
+        };
+
+        for variant in variants {
+            let mut variant_path = path.to_vec();
+            if variant.kind.as_deref() != Some("flat") {
+                variant_path.push(selector(&variant.name));
+            }
+
+            if event_enums.contains_key(&variant.ty) {
+                self.collect_events(&variant.ty, &variant_path, event_structs, event_enums, depth + 1);
+            } else if let Some(members) = event_structs.get(&variant.ty) {
+                self.events.insert(variant_path, EventLayout {
+                    name: variant.name.clone(),
+                    members: members.clone(),
+                });
+            }
+        }
+    }
+
+    // The first definition wins when interfaces share a function name
+    fn add_function(&mut self, entry: &Value) {
+        let name = entry.get("name").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+        self.functions.entry(name.clone()).or_insert_with(|| AbiFunction {
+            name,
+            inputs: parse_members(entry.get("inputs")),
+            outputs: parse_members(entry.get("outputs")),
+            state_mutability: entry.get("state_mutability")
+                .or_else(|| entry.get("stateMutability"))
+                .and_then(|v| v.as_str())
+                .map(String::from),
+        });
+    }
+
+    pub fn event_count(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn function(&self, name: &str) -> Option<&AbiFunction> {
+        self.functions.get(name)
+    }
+
+    /// Function whose entry point selector is `selector` (any hex form)
+    pub fn function_by_selector(&self, selector_hex: &str) -> Option<&AbiFunction> {
+        let wanted = normalize_hex(selector_hex);
+        self.functions.values().find(|function| selector(&function.name) == wanted)
+    }
+
+    /// Serializes call arguments to calldata. `args` is either an array in input order or an
+    /// object keyed by input name. Cairo 0 `<name>_len` inputs are filled in from their array.
+    pub fn encode_calldata(&self, function: &AbiFunction, args: &Value) -> Result<Vec<String>, String> {
+        let inputs: Vec<&AbiMember> = function.inputs.iter()
+            .enumerate()
+            .filter(|(i, input)| !self.is_array_length(&function.inputs, *i, input))
+            .map(|(_, input)| input)
+            .collect();
+
+        let values: Vec<&Value> = match args {
+            Value::Array(items) => {
+                if items.len() != inputs.len() {
+                    return Err(format!(
+                        "{} expects {} arguments, got {}", function.name, inputs.len(), items.len()
+                    ));
+                }
+                items.iter().collect()
+            }
+            Value::Object(named) => inputs.iter()
+                .map(|input| named.get(&input.name).ok_or_else(|| format!("missing argument '{}'", input.name)))
+                .collect::<Result<_, _>>()?,
+            Value::Null if inputs.is_empty() => Vec::new(),
+            _ => return Err("arguments must be an array or an object".to_string()),
+        };
+
+        let mut calldata = Vec::new();
+        for (input, value) in inputs.iter().zip(values) {
+            self.encode_member(input, value, &mut calldata)
+                .map_err(|e| format!("argument '{}': {}", input.name, e))?;
+        }
+        Ok(calldata.iter().map(felt_to_hex).collect())
+    }
+
+    /// Decodes a call result into a single value (one Sierra output), an array (several) or,
+    /// for Cairo 0, an object keyed by output name
+    pub fn decode_outputs(&self, function: &AbiFunction, result: &[String]) -> Result<Value, String> {
+        let mut reader = FeltReader::new(result);
+        let mut fields = Map::new();
+        let mut values = Vec::new();
+
+        for output in &function.outputs {
+            let value = self.decode_member(output, &mut reader, &fields)?;
+            fields.insert(output.name.clone(), value.clone());
+            values.push(value);
+        }
+
+        Ok(if self.legacy {
+            Value::Object(fields)
+        } else if values.len() == 1 {
+            values.remove(0)
+        } else {
+            Value::Array(values)
+        })
+    }
+
+    /// Decodes calldata into an object keyed by input name
+    pub fn decode_inputs(&self, function: &AbiFunction, calldata: &[String]) -> Result<Value, String> {
+        let mut reader = FeltReader::new(calldata);
+        let mut fields = Map::new();
+
+        for input in &function.inputs {
+            let value = self.decode_member(input, &mut reader, &fields)?;
+            fields.insert(input.name.clone(), value);
+        }
+        Ok(Value::Object(fields))
+    }
+
+    /// Decodes consecutive felts (e.g. storage slots) as a value of type `ty`
+    pub fn decode_value(&self, ty: &str, felts: &[String]) -> Result<Value, String> {
+        self.decode_type(ty, &mut FeltReader::new(felts))
+    }
+
+    /// Felts occupied by a fixed-size type; None for arrays, enums and byte arrays whose
+    /// size depends on the value, and for types the ABI does not define
+    pub fn felt_size(&self, ty: &str) -> Option<usize> {
+        let ty = ty.trim();
+        if ty == "()" {
+            return Some(0);
+        }
+        if let Some(inner) = ty.strip_prefix('(').and_then(|t| t.strip_suffix(')')) {
+            return split_top_level(inner)
+                .iter()
+                .map(|element| self.felt_size(element.split_once(':').map_or(element.as_str(), |(_, t)| t)))
+                .sum();
+        }
+        if let Some(inner) = generic_argument(ty, "core::zeroable::NonZero") {
+            return self.felt_size(inner);
+        }
+        if generic_argument(ty, "core::array::Array").is_some()
+            || generic_argument(ty, "core::array::Span").is_some()
+            || ty == "core::byte_array::ByteArray"
+            || self.enums.contains_key(ty)
+        {
+            return None;
+        }
+        if ty == "core::integer::u256" || ty == "Uint256" {
+            return Some(2);
+        }
+        if let Some(members) = self.structs.get(ty) {
+            return members.iter().map(|member| self.felt_size(&member.ty)).sum();
+        }
+        // felt252, integers, bool, ContractAddress, ClassHash, EthAddress, bytes31 and Cairo 0
+        // felts; a struct or generic the ABI leaves out could span any number of felts
+        ((ty.starts_with("core::") && !ty.contains('<')) || ty == "felt").then_some(1)
+    }
+
+    // A Cairo 0 `x_len` input directly followed by its `x: T*` array
+    fn is_array_length(&self, inputs: &[AbiMember], index: usize, input: &AbiMember) -> bool {
+        self.legacy
+            && inputs.get(index + 1).is_some_and(|next| {
+                next.ty.ends_with('*') && input.name == format!("{}_len", next.name)
+            })
+    }
+
+    fn encode_member(&self, member: &AbiMember, value: &Value, out: &mut Vec<BigUint>) -> Result<(), String> {
+        if self.legacy {
+            if let Some(inner) = member.ty.strip_suffix('*') {
+                return self.encode_array(inner, value, out);
+            }
+        }
+        self.encode_type(&member.ty, value, out)
+    }
+
+    fn encode_array(&self, inner: &str, value: &Value, out: &mut Vec<BigUint>) -> Result<(), String> {
+        let items = value.as_array().ok_or("expected an array")?;
+        out.push(BigUint::from(items.len()));
+        for item in items {
+            self.encode_type(inner, item, out)?;
+        }
+        Ok(())
+    }
+
+    fn encode_type(&self, ty: &str, value: &Value, out: &mut Vec<BigUint>) -> Result<(), String> {
+        let ty = ty.trim();
+
+        if ty == "()" {
+            return Ok(());
+        }
+        if let Some(inner) = ty.strip_prefix('(').and_then(|t| t.strip_suffix(')')) {
+            let elements = split_top_level(inner);
+            let items = value.as_array()
+                .filter(|items| items.len() == elements.len())
+                .ok_or_else(|| format!("expected a tuple of {} elements", elements.len()))?;
+            for (element, item) in elements.iter().zip(items) {
+                self.encode_type(element.split_once(':').map_or(element.as_str(), |(_, t)| t), item, out)?;
+            }
+            return Ok(());
+        }
+        if let Some(inner) = generic_argument(ty, "core::array::Array")
+            .or_else(|| generic_argument(ty, "core::array::Span"))
+        {
+            return self.encode_array(inner, value, out);
+        }
+        if let Some(inner) = generic_argument(ty, "core::zeroable::NonZero") {
+            return self.encode_type(inner, value, out);
+        }
+
+        match ty {
+            "core::bool" => {
+                let flag = match value {
+                    Value::Bool(flag) => *flag,
+                    other => !parse_integer(other)?.is_zero(),
+                };
+                out.push(BigUint::from(flag as u8));
+                return Ok(());
+            }
+            "core::integer::u8" => return push_unsigned(value, 8, out),
+            "core::integer::u16" => return push_unsigned(value, 16, out),
+            "core::integer::u32" | "core::integer::usize" => return push_unsigned(value, 32, out),
+            "core::integer::u64" => return push_unsigned(value, 64, out),
+            "core::integer::u128" => return push_unsigned(value, 128, out),
+            "core::integer::i8" => return push_signed(value, 8, out),
+            "core::integer::i16" => return push_signed(value, 16, out),
+            "core::integer::i32" => return push_signed(value, 32, out),
+            "core::integer::i64" => return push_signed(value, 64, out),
+            "core::integer::i128" => return push_signed(value, 128, out),
+            "core::integer::u256" | "Uint256" if !value.is_object() => {
+                let amount = to_unsigned(value, 256)?;
+                let mask = (BigUint::one() << 128u32) - BigUint::one();
+                out.push(&amount & &mask);
+                out.push(amount >> 128u32);
+                return Ok(());
+            }
+            "core::byte_array::ByteArray" => {
+                let text = value.as_str().ok_or("expected a string")?;
+                encode_byte_array(text.as_bytes(), out);
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        // `{low, high}` objects for u256 are encoded as the struct they are
+        if let Some(members) = self.structs.get(ty).or_else(|| (ty == "core::integer::u256").then_some(&*U256_MEMBERS)) {
+            let fields = value.as_object().ok_or_else(|| format!("expected an object for {}", ty))?;
+            for member in members {
+                let field = fields.get(&member.name).ok_or_else(|| format!("missing field '{}'", member.name))?;
+                self.encode_member(member, field, out)?;
+            }
+            return Ok(());
+        }
+
+        if let Some(variants) = self.enums.get(ty) {
+            // `"Variant"` for unit variants, `{"variant": "Variant", "value": ...}` otherwise
+            let (name, inner) = match value {
+                Value::String(name) => (name.as_str(), &Value::Null),
+                other => (
+                    other.get("variant").and_then(|v| v.as_str()).ok_or("expected {variant, value}")?,
+                    other.get("value").unwrap_or(&Value::Null),
+                ),
+            };
+            let index = variants.iter()
+                .position(|variant| variant.name == name)
+                .ok_or_else(|| format!("unknown variant {} for {}", name, ty))?;
+            out.push(BigUint::from(index));
+            return self.encode_type(&variants[index].ty, inner, out);
+        }
+
+        // felt252, ContractAddress, ClassHash, EthAddress, bytes31 and anything unknown:
+        // a number, or a short string when the text is not numeric
+        let felt = match value {
+            Value::String(text) => parse_felt(text)
+                .or_else(|| short_string_to_felt(text))
+                .ok_or_else(|| format!("invalid felt {}", text))?,
+            other => to_unsigned(other, 252)?,
+        };
+        if felt >= *FIELD_PRIME {
+            return Err("felt exceeds the field prime".to_string());
+        }
+        out.push(felt);
+        Ok(())
+    }
+
+    /// Decodes an emitted event into its name and named, typed fields.
+    /// Returns None when no ABI event matches the keys or the payload does not fit the layout.
+    pub fn decode_event(&self, keys: &[String], data: &[String]) -> Option<(String, Value)> {
+        let normalized: Vec<String> = keys.iter().map(|k| normalize_hex(k)).collect();
+
+        for path_len in 1..=MAX_EVENT_PATH.min(normalized.len()) {
+            let Some(layout) = self.events.get(&normalized[..path_len]) else {
+                continue;
+            };
+
+            let mut key_reader = FeltReader::new(&keys[path_len..]);
+            let mut data_reader = FeltReader::new(data);
+            let mut fields = Map::new();
+
+            for member in &layout.members {
+                let reader = if member.kind.as_deref() == Some("key") {
+                    &mut key_reader
+                } else {
+                    &mut data_reader
+                };
+                match self.decode_member(member, reader, &fields) {
+                    Ok(value) => {
+                        fields.insert(member.name.clone(), value);
+                    }
+                    Err(e) => {
+                        log::debug!("Event {} does not match ABI layout: {}", layout.name, e);
+                        return None;
+                    }
+                }
+            }
+
+            return Some((layout.name.clone(), Value::Object(fields)));
+        }
+
+        None
+    }
+
+    fn decode_member(&self, member: &AbiMember, reader: &mut FeltReader, previous: &Map<String, Value>) -> Result<Value, String> {
+        if self.legacy {
+            // Cairo 0 arrays are `T*` preceded by an `<name>_len` member
+            if let Some(inner) = member.ty.strip_suffix('*') {
+                let len = previous.get(&format!("{}_len", member.name))
+                    .and_then(|v| v.as_str())
+                    .and_then(parse_felt)
+                    .and_then(|v| v.to_usize())
+                    .ok_or("missing array length")?;
+                return (0..len).map(|_| self.decode_type(inner, reader)).collect::<Result<Vec<_>, _>>().map(Value::Array);
+            }
+        }
+        self.decode_type(&member.ty, reader)
+    }
+
+    fn decode_type(&self, ty: &str, reader: &mut FeltReader) -> Result<Value, String> {
+        let ty = ty.trim();
+
+        if ty == "()" {
+            return Ok(Value::Null);
+        }
+        if let Some(inner) = ty.strip_prefix('(').and_then(|t| t.strip_suffix(')')) {
+            return split_top_level(inner)
+                .iter()
+                .map(|element| self.decode_type(element.split_once(':').map_or(element.as_str(), |(_, t)| t), reader))
+                .collect::<Result<Vec<_>, _>>()
+                .map(Value::Array);
+        }
+        if let Some(inner) = generic_argument(ty, "core::array::Array")
+            .or_else(|| generic_argument(ty, "core::array::Span"))
+        {
+            let len = reader.next_usize()?;
+            return (0..len).map(|_| self.decode_type(inner, reader)).collect::<Result<Vec<_>, _>>().map(Value::Array);
+        }
+        if let Some(inner) = generic_argument(ty, "core::zeroable::NonZero") {
+            return self.decode_type(inner, reader);
+        }
+
+        match ty {
+            "core::bool" => return Ok(Value::Bool(!reader.next()?.is_zero())),
+            "core::integer::u8" | "core::integer::u16" | "core::integer::u32" | "core::integer::usize" => {
+                return reader.next()?.to_u64().map(|v| json!(v)).ok_or_else(|| "integer overflow".to_string());
+            }
+            "core::integer::u64" | "core::integer::u128" => return Ok(json!(reader.next()?.to_string())),
+            "core::integer::i8" | "core::integer::i16" | "core::integer::i32" => {
+                return felt_to_signed(&reader.next()?).to_i64().map(|v| json!(v)).ok_or_else(|| "integer overflow".to_string());
+            }
+            "core::integer::i64" | "core::integer::i128" => return Ok(json!(felt_to_signed(&reader.next()?).to_string())),
+            "core::integer::u256" | "Uint256" => {
+                let low = reader.next()?;
+                let high = reader.next()?;
+                return Ok(json!(u256_from_parts(&low, &high).to_string()));
+            }
+            "core::byte_array::ByteArray" => return decode_byte_array(reader).map(Value::String),
+            _ => {}
+        }
+
+        if let Some(members) = self.structs.get(ty) {
+            let mut fields = Map::new();
+            for member in members {
+                let value = self.decode_member(member, reader, &fields)?;
+                fields.insert(member.name.clone(), value);
+            }
+            return Ok(Value::Object(fields));
+        }
+
+        if let Some(variants) = self.enums.get(ty) {
+            let index = reader.next_usize()?;
+            let variant = variants.get(index).ok_or_else(|| format!("invalid variant {} for {}", index, ty))?;
+            let value = self.decode_type(&variant.ty, reader)?;
+            return Ok(json!({ "variant": variant.name, "value": value }));
+        }
+
+        // felt252, ContractAddress, ClassHash, EthAddress, bytes31 and anything unknown
+        Ok(Value::String(felt_to_hex(&reader.next()?)))
+    }
+}
+
+static U256_MEMBERS: std::sync::LazyLock<Vec<AbiMember>> = std::sync::LazyLock::new(|| {
+    ["low", "high"].iter()
+        .map(|name| AbiMember {
+            name: name.to_string(),
+            ty: "core::integer::u128".to_string(),
+            kind: None,
+        })
+        .collect()
+});
+
+// Integer argument given as a JSON number or a decimal / `0x` hex string, possibly negative
+fn parse_integer(value: &Value) -> Result<BigInt, String> {
+    match value {
+        Value::Number(n) => n.as_i64().map(BigInt::from)
+            .or_else(|| n.as_u64().map(BigInt::from))
+            .ok_or_else(|| format!("{} is not an integer", n)),
+        Value::String(text) => {
+            let text = text.trim();
+            let (negative, digits) = match text.strip_prefix('-') {
+                Some(rest) => (true, rest),
+                None => (false, text),
+            };
+            let magnitude = match digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+                Some(hex) => BigInt::from_str_radix(hex, 16),
+                None => BigInt::from_str_radix(digits, 10),
+            }
+            .map_err(|_| format!("invalid integer {}", text))?;
+            Ok(if negative { -magnitude } else { magnitude })
+        }
+        other => Err(format!("expected an integer, got {}", other)),
+    }
+}
+
+fn to_unsigned(value: &Value, bits: u32) -> Result<BigUint, String> {
+    parse_integer(value)?
+        .to_biguint()
+        .filter(|v| v.bits() <= bits as u64)
+        .ok_or_else(|| format!("value out of range for u{}", bits))
+}
+
+fn push_unsigned(value: &Value, bits: u32, out: &mut Vec<BigUint>) -> Result<(), String> {
+    out.push(to_unsigned(value, bits)?);
+    Ok(())
+}
+
+fn push_signed(value: &Value, bits: u32, out: &mut Vec<BigUint>) -> Result<(), String> {
+    let number = parse_integer(value)?;
+    let limit = BigInt::one() << (bits - 1);
+    if number < -&limit || number >= limit {
+        return Err(format!("value out of range for i{}", bits));
+    }
+    out.push(signed_to_felt(&number));
+    Ok(())
+}
+
+// Inverse of `decode_byte_array`
+fn encode_byte_array(bytes: &[u8], out: &mut Vec<BigUint>) {
+    let full_words = bytes.len() / 31;
+    out.push(BigUint::from(full_words));
+    for word in bytes[..full_words * 31].chunks(31) {
+        out.push(BigUint::from_bytes_be(word));
+    }
+    let pending = &bytes[full_words * 31..];
+    out.push(BigUint::from_bytes_be(pending));
+    out.push(BigUint::from(pending.len()));
+}
+
+fn parse_members(value: Option<&Value>) -> Vec<AbiMember> {
+    value
+        .and_then(|v| v.as_array())
+        .map(|members| {
+            members.iter()
+                .map(|m| AbiMember {
+                    name: m.get("name").and_then(|v| v.as_str()).unwrap_or_default().to_string(),
+                    ty: m.get("type").and_then(|v| v.as_str()).unwrap_or_default().to_string(),
+                    kind: m.get("kind").and_then(|v| v.as_str()).map(String::from),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn short_name(path: &str) -> &str {
+    path.rsplit("::").next().unwrap_or(path)
+}
+
+// `core::array::Array::<T>` -> `T`
+fn generic_argument<'a>(ty: &'a str, base: &str) -> Option<&'a str> {
+    ty.strip_prefix(base)?
+        .strip_prefix("::<")?
+        .strip_suffix('>')
+}
+
+// Splits tuple members on commas that are not inside nested brackets
+fn split_top_level(inner: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut depth = 0i32;
+    let mut current = String::new();
+
+    for c in inner.chars() {
+        match c {
+            '(' | '<' => depth += 1,
+            ')' | '>' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    if !current.trim().is_empty() {
+        parts.push(current.trim().to_string());
+    }
+    parts
+}
+
+/// Text returned by a getter such as `name` or `symbol`: a `ByteArray`, or a short string
+/// in a single felt as older contracts return it
+pub fn decode_text(felts: &[String]) -> Option<String> {
+    match felts {
+        [felt] => felt_to_short_string(&parse_felt(felt)?),
+        _ => decode_byte_array(&mut FeltReader::new(felts)).ok(),
+    }
+}
+
+// ByteArray: full 31-byte words, then a pending word and its byte length
+fn decode_byte_array(reader: &mut FeltReader) -> Result<String, String> {
+    let words = reader.next_usize()?;
+    let mut bytes = Vec::new();
+
+    for _ in 0..words {
+        let word = reader.next()?.to_bytes_be();
+        if word.len() > 31 {
+            return Err("ByteArray word longer than 31 bytes".to_string());
+        }
+        bytes.extend(std::iter::repeat_n(0u8, 31 - word.len()));
+        bytes.extend(word);
+    }
+
+    let pending = reader.next()?;
+    let pending = if pending.is_zero() { vec![] } else { pending.to_bytes_be() };
+    let pending_len = reader.next_usize()?;
+    if pending_len > 31 || pending_len < pending.len() {
+        return Err(format!("invalid ByteArray pending length {}", pending_len));
+    }
+    bytes.extend(std::iter::repeat_n(0u8, pending_len - pending.len()));
+    bytes.extend(pending);
+
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn felts(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    fn sierra(abi: Value) -> ContractAbi {
+        ContractAbi::from_class(&json!({"sierra_program": [], "abi": abi.to_string()})).expect("valid ABI")
+    }
+
+    #[test]
+    fn decodes_sierra_events_through_the_event_enum() {
+        let abi = sierra(json!([
+            {"type": "struct", "name": "core::integer::u256", "members": [
+                {"name": "low", "type": "core::integer::u128"},
+                {"name": "high", "type": "core::integer::u128"}]},
+            {"type": "event", "name": "token::Transfer", "kind": "struct", "members": [
+                {"name": "from", "type": "core::starknet::contract_address::ContractAddress", "kind": "key"},
+                {"name": "to", "type": "core::starknet::contract_address::ContractAddress", "kind": "key"},
+                {"name": "value", "type": "core::integer::u256", "kind": "data"}]},
+            {"type": "event", "name": "token::Event", "kind": "enum", "variants": [
+                {"name": "Transfer", "type": "token::Transfer", "kind": "nested"}]}
+        ]));
+
+        let keys = vec![selector("Transfer"), "0xa".to_string(), "0xb".to_string()];
+        let (name, fields) = abi.decode_event(&keys, &felts(&["0x5", "0x1"])).expect("decodes");
+        assert_eq!(name, "Transfer");
+        assert_eq!(fields["from"], "0xa");
+        assert_eq!(fields["to"], "0xb");
+        assert_eq!(fields["value"], ((BigUint::one() << 128u32) + 5u32).to_string());
+
+        // Missing data does not fit the layout
+        assert!(abi.decode_event(&keys, &felts(&["0x5"])).is_none());
+        assert!(abi.decode_event(&[selector("Approval")], &[]).is_none());
+    }
+
+    #[test]
+    fn decodes_cairo0_events_from_keys_and_data_lists() {
+        let abi = ContractAbi::from_class(&json!({"abi": [
+            {"type": "event", "name": "Minted", "keys": [], "data": [
+                {"name": "owner", "type": "felt"},
+                {"name": "ids_len", "type": "felt"},
+                {"name": "ids", "type": "felt*"}]}
+        ]})).expect("valid ABI");
+
+        let (name, fields) = abi.decode_event(&[selector("Minted")], &felts(&["0xa", "0x2", "0x7", "0x8"])).expect("decodes");
+        assert_eq!(name, "Minted");
+        assert_eq!(fields["owner"], "0xa");
+        assert_eq!(fields["ids"], json!(["0x7", "0x8"]));
+    }
+
+    #[test]
+    fn decodes_byte_arrays_and_short_strings() {
+        // "hello": no full words, a 5-byte pending word
+        assert_eq!(decode_text(&felts(&["0x0", "0x68656c6c6f", "0x5"])).as_deref(), Some("hello"));
+        // 31 bytes fill one word exactly; the empty pending word adds nothing
+        let word = format!("0x{}", "61".repeat(31));
+        assert_eq!(decode_text(&felts(&["0x1", &word, "0x0", "0x0"])), Some("a".repeat(31)));
+        // Leading zero bytes of the pending word are kept
+        assert_eq!(decode_text(&felts(&["0x0", "0x41", "0x2"])).as_deref(), Some("\0A"));
+        assert_eq!(decode_text(&felts(&["0x535452"])).as_deref(), Some("STR"));
+    }
+
+    #[test]
+    fn rejects_malformed_byte_arrays() {
+        // A pending length no pending word can have must not allocate it
+        assert!(decode_text(&felts(&["0x0", "0x41", "0xffffffffffffffff"])).is_none());
+        assert!(decode_text(&felts(&["0x0", "0x41", "0x20"])).is_none());
+        // Shorter than the pending word itself
+        assert!(decode_text(&felts(&["0x0", "0x68656c6c6f", "0x2"])).is_none());
+        // Truncated
+        assert!(decode_text(&felts(&["0x2", "0x41"])).is_none());
+
+        let abi = ContractAbi::default();
+        assert!(abi.decode_value("core::byte_array::ByteArray", &felts(&["0x0", "0x0", "0x7fffffff"])).is_err());
+    }
+
+    fn token() -> ContractAbi {
+        sierra(json!([
+            {"type": "struct", "name": "token::Order", "members": [
+                {"name": "id", "type": "core::integer::u64"},
+                {"name": "side", "type": "token::Side"}]},
+            {"type": "enum", "name": "token::Side", "variants": [
+                {"name": "Buy", "type": "()"},
+                {"name": "Sell", "type": "core::integer::i8"}]},
+            {"type": "interface", "name": "token::IToken", "items": [
+                {"type": "function", "name": "transfer", "state_mutability": "external", "inputs": [
+                    {"name": "recipient", "type": "core::starknet::contract_address::ContractAddress"},
+                    {"name": "amount", "type": "core::integer::u256"},
+                    {"name": "memo", "type": "core::byte_array::ByteArray"}], "outputs": []},
+                {"type": "function", "name": "place", "state_mutability": "external", "inputs": [
+                    {"name": "orders", "type": "core::array::Array::<token::Order>"}], "outputs": []},
+                {"type": "function", "name": "balance_of", "state_mutability": "view", "inputs": [
+                    {"name": "account", "type": "core::starknet::contract_address::ContractAddress"}], "outputs": [
+                    {"type": "core::integer::u256"}]},
+                {"type": "function", "name": "info", "state_mutability": "view", "inputs": [], "outputs": [
+                    {"type": "core::byte_array::ByteArray"}, {"type": "core::bool"}]}]}
+        ]))
+    }
+
+    #[test]
+    fn encodes_call_arguments_in_order_or_by_name() {
+        let abi = token();
+        let transfer = abi.function("transfer").expect("in the interface");
+        let expected = felts(&["0xabc", "0x1", "0x1", "0x0", "0x6869", "0x2"]);
+
+        let positional = json!(["0xabc", "340282366920938463463374607431768211457", "hi"]);
+        assert_eq!(abi.encode_calldata(transfer, &positional).unwrap(), expected);
+        let named = json!({"memo": "hi", "amount": {"low": 1, "high": 1}, "recipient": "0xabc"});
+        assert_eq!(abi.encode_calldata(transfer, &named).unwrap(), expected);
+
+        assert!(abi.encode_calldata(transfer, &json!(["0xabc", 1])).unwrap_err().contains("expects 3 arguments"));
+        assert!(abi.encode_calldata(transfer, &json!({"recipient": "0xabc", "amount": 1})).unwrap_err().contains("missing argument 'memo'"));
+        assert!(abi.encode_calldata(transfer, &json!(["0xabc", "-1", "hi"])).unwrap_err().contains("argument 'amount'"));
+    }
+
+    #[test]
+    fn encodes_arrays_of_structs_and_enums() {
+        let abi = token();
+        let place = abi.function("place").unwrap();
+        let orders = json!([[{"id": 7, "side": "Buy"}, {"id": "0x8", "side": {"variant": "Sell", "value": -1}}]]);
+        let minus_one = felt_to_hex(&(&*FIELD_PRIME - BigUint::one()));
+
+        assert_eq!(
+            abi.encode_calldata(place, &orders).unwrap(),
+            vec!["0x2", "0x7", "0x0", "0x8", "0x1", minus_one.as_str()]
+        );
+        assert!(abi.encode_calldata(place, &json!([[{"id": 1, "side": "Hold"}]])).unwrap_err().contains("unknown variant"));
+        assert!(abi.encode_calldata(place, &json!([[{"id": -1, "side": "Buy"}]])).is_err());
+    }
+
+    #[test]
+    fn decodes_call_results() {
+        let abi = token();
+        let balance = abi.function("balance_of").unwrap();
+        assert_eq!(abi.decode_outputs(balance, &felts(&["0x5", "0x0"])).unwrap(), json!("5"));
+        assert!(abi.decode_outputs(balance, &felts(&["0x5"])).is_err());
+
+        let info = abi.function("info").unwrap();
+        assert_eq!(abi.decode_outputs(info, &felts(&["0x0", "0x6869", "0x2", "0x1"])).unwrap(), json!(["hi", true]));
+    }
+
+    #[test]
+    fn fills_in_cairo0_array_lengths() {
+        let abi = ContractAbi::from_class(&json!({"abi": [
+            {"type": "function", "name": "set_ids", "inputs": [
+                {"name": "owner", "type": "felt"},
+                {"name": "ids_len", "type": "felt"},
+                {"name": "ids", "type": "felt*"}], "outputs": [
+                {"name": "count", "type": "felt"},
+                {"name": "total", "type": "Uint256"}]}
+        ]})).expect("valid ABI");
+        let set_ids = abi.function("set_ids").unwrap();
+
+        assert_eq!(abi.encode_calldata(set_ids, &json!({"owner": "0xa", "ids": [7, 8]})).unwrap(), felts(&["0xa", "0x2", "0x7", "0x8"]));
+        assert_eq!(
+            abi.decode_outputs(set_ids, &felts(&["0x2", "0xf", "0x0"])).unwrap(),
+            json!({"count": "0x2", "total": "15"})
+        );
+    }
+}
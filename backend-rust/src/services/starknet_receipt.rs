@@ -0,0 +1,118 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use crate::services::token::TokenAmount;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum FeeUnit {
+    /// ETH, paid by V0-V2 transactions
+    Wei,
+    /// STRK, paid by V3 transactions
+    Fri,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ExecutionStatus {
+    Succeeded,
+    Reverted,
+}
+
+/// The parts of a transaction receipt the analytics rely on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionReceipt {
+    pub transaction_hash: String,
+    pub actual_fee: u128,
+    pub fee_unit: FeeUnit,
+    pub execution_status: ExecutionStatus,
+    pub revert_reason: Option<String>,
+}
+
+impl TransactionReceipt {
+    /// Parses a receipt. `actual_fee` is a `{amount, unit}` object since RPC 0.6 and a
+    /// plain WEI amount before that.
+    pub fn from_value(value: &Value) -> Option<Self> {
+        let (actual_fee, fee_unit) = match value.get("actual_fee")? {
+            Value::String(amount) => (parse_amount(amount)?, FeeUnit::Wei),
+            fee => (
+                parse_amount(fee.get("amount")?.as_str()?)?,
+                serde_json::from_value(fee.get("unit")?.clone()).ok()?,
+            ),
+        };
+
+        Some(Self {
+            transaction_hash: value.get("transaction_hash")?.as_str()?.to_string(),
+            actual_fee,
+            fee_unit,
+            execution_status: serde_json::from_value(value.get("execution_status")?.clone()).ok()?,
+            revert_reason: value.get("revert_reason")
+                .and_then(|v| v.as_str())
+                .map(String::from),
+        })
+    }
+}
+
+/// Most a transaction may pay: `max_fee` up to V2, and for V3 the sum over its resource
+/// bounds of max amount times max price per unit
+pub fn fee_cap(tx: &Value) -> (u128, FeeUnit) {
+    if let Some(bounds) = tx.get("resource_bounds").and_then(|v| v.as_object()) {
+        let cap = bounds.values()
+            .filter_map(|bound| {
+                let amount = parse_amount(bound.get("max_amount")?.as_str()?)?;
+                let price = parse_amount(bound.get("max_price_per_unit")?.as_str()?)?;
+                amount.checked_mul(price)
+            })
+            .fold(0u128, u128::saturating_add);
+        return (cap, FeeUnit::Fri);
+    }
+
+    let max_fee = tx.get("max_fee")
+        .and_then(|v| v.as_str())
+        .and_then(parse_amount)
+        .unwrap_or(0);
+    (max_fee, FeeUnit::Wei)
+}
+
+/// Fees paid in one unit
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FeeSummary {
+    pub unit: FeeUnit,
+    pub transaction_count: usize,
+    /// In the fee currency: ETH or STRK, or the native coin of an EVM chain
+    pub total: String,
+    pub average: String,
+    pub total_amount: TokenAmount,
+    pub average_amount: TokenAmount,
+}
+
+/// Actual fees per unit, since WEI and FRI amounts cannot be added together.
+/// `native_symbol` names the coin WEI amounts are in.
+pub fn summarize_fees<'a>(receipts: impl IntoIterator<Item = &'a TransactionReceipt>, native_symbol: &str) -> Vec<FeeSummary> {
+    let mut totals: BTreeMap<FeeUnit, (usize, u128)> = BTreeMap::new();
+    for receipt in receipts {
+        let entry = totals.entry(receipt.fee_unit).or_default();
+        entry.0 += 1;
+        entry.1 = entry.1.saturating_add(receipt.actual_fee);
+    }
+
+    totals.into_iter()
+        .map(|(unit, (count, total))| {
+            let total_amount = TokenAmount::fee(total, unit, native_symbol);
+            let average_amount = TokenAmount::fee(total / count as u128, unit, native_symbol);
+            FeeSummary {
+                unit,
+                transaction_count: count,
+                total: total_amount.formatted.clone(),
+                average: average_amount.formatted.clone(),
+                total_amount,
+                average_amount,
+            }
+        })
+        .collect()
+}
+
+fn parse_amount(value: &str) -> Option<u128> {
+    u128::from_str_radix(value.trim_start_matches("0x"), 16).ok()
+}
@@ -0,0 +1,438 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+use crate::services::{
+    chain::STARKNET_NATIVE_SYMBOL,
+    rpc::RpcService,
+    starknet_abi::ContractAbi,
+    starknet_receipt::{fee_cap, FeeUnit, TransactionReceipt},
+    token::TokenAmount,
+};
+use crate::utils::felt::normalize_hex;
+
+// Invocation fields of each transaction trace type, with the phase they are reported under
+const TRACE_PHASES: [(&str, &str); 5] = [
+    ("validate_invocation", "validate"),
+    ("execute_invocation", "execute"),
+    ("constructor_invocation", "execute"),
+    ("function_invocation", "execute"),
+    ("fee_transfer_invocation", "fee_transfer"),
+];
+
+#[derive(Debug, Serialize)]
+pub struct TraceEvent {
+    /// Position among all events of the transaction
+    pub order: Option<u64>,
+    pub from_address: String,
+    pub keys: Vec<String>,
+    pub data: Vec<String>,
+    pub event_name: String,
+    pub decoded_data: Value,
+}
+
+/// One function invocation and everything it called
+#[derive(Debug, Serialize)]
+pub struct CallNode {
+    pub contract_address: String,
+    pub caller_address: String,
+    pub class_hash: Option<String>,
+    pub entry_point_selector: String,
+    /// Function name from the called contract's ABI
+    pub entry_point: Option<String>,
+    pub entry_point_type: Option<String>,
+    pub call_type: Option<String>,
+    pub calldata: Vec<String>,
+    pub decoded_calldata: Option<Value>,
+    pub result: Vec<String>,
+    pub decoded_result: Option<Value>,
+    pub events: Vec<TraceEvent>,
+    /// L2 to L1 messages sent by this invocation
+    pub messages: Vec<Value>,
+    pub calls: Vec<CallNode>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct TransactionTrace {
+    pub validate: Option<CallNode>,
+    /// `__execute__`, the constructor of a deploy, or the L1 handler
+    pub execute: Option<CallNode>,
+    pub fee_transfer: Option<CallNode>,
+    /// Set when execution reverted; the trace then has no execute call tree
+    pub revert_reason: Option<String>,
+}
+
+// Every invocation anywhere in a trace
+fn invocations(trace: &Value) -> Vec<&Value> {
+    fn walk<'a>(invocation: &'a Value, found: &mut Vec<&'a Value>) {
+        found.push(invocation);
+        for call in invocation.get("calls").and_then(|v| v.as_array()).into_iter().flatten() {
+            walk(call, found);
+        }
+    }
+
+    let mut found = Vec::new();
+    for (field, _) in TRACE_PHASES {
+        if let Some(invocation) = trace.get(field) {
+            walk(invocation, &mut found);
+        }
+    }
+    found
+}
+
+/// Every class executed anywhere in a trace, so their ABIs can be loaded up front
+pub fn invoked_classes(trace: &Value) -> Vec<String> {
+    let found: BTreeSet<String> = invocations(trace).into_iter()
+        .filter_map(|invocation| text(invocation, "class_hash"))
+        .map(|hash| normalize_hex(&hash))
+        .collect();
+    found.into_iter().collect()
+}
+
+/// Contracts invoked in a trace without a reported class hash, whose ABIs can only be
+/// looked up by address
+pub fn invoked_contracts(trace: &Value) -> Vec<String> {
+    let found: BTreeSet<String> = invocations(trace).into_iter()
+        .filter(|invocation| invocation.get("class_hash").is_none())
+        .filter_map(|invocation| text(invocation, "contract_address"))
+        .map(|address| normalize_hex(&address))
+        .collect();
+    found.into_iter().collect()
+}
+
+/// Builds the call trees of a `starknet_traceTransaction` result, naming entry points and
+/// decoding calldata, results and events. Each invocation is decoded with the ABI of the
+/// class it executed (`class_abis`, by normalized class hash), which for library calls and
+/// upgraded contracts differs from the class now at its address; invocations without a
+/// class hash fall back to `abis`, keyed by normalized address.
+pub fn parse_trace(
+    trace: &Value,
+    abis: &HashMap<String, Arc<ContractAbi>>,
+    class_abis: &HashMap<String, Arc<ContractAbi>>,
+    rpc: &RpcService,
+) -> TransactionTrace {
+    let mut parsed = TransactionTrace::default();
+
+    for (field, phase) in TRACE_PHASES {
+        let Some(invocation) = trace.get(field) else {
+            continue;
+        };
+        if let Some(reason) = invocation.get("revert_reason").and_then(|v| v.as_str()) {
+            parsed.revert_reason = Some(reason.to_string());
+            continue;
+        }
+
+        let node = Some(parse_invocation(invocation, abis, class_abis, rpc));
+        match phase {
+            "validate" => parsed.validate = node,
+            "execute" => parsed.execute = node,
+            _ => parsed.fee_transfer = node,
+        }
+    }
+
+    parsed
+}
+
+fn parse_invocation(
+    invocation: &Value,
+    abis: &HashMap<String, Arc<ContractAbi>>,
+    class_abis: &HashMap<String, Arc<ContractAbi>>,
+    rpc: &RpcService,
+) -> CallNode {
+    let contract_address = text(invocation, "contract_address").unwrap_or_default();
+    let class_hash = text(invocation, "class_hash");
+    let entry_point_selector = text(invocation, "entry_point_selector").unwrap_or_default();
+    let calldata = felts(invocation, "calldata");
+    let result = felts(invocation, "result");
+
+    let abi = match &class_hash {
+        Some(hash) => class_abis.get(&normalize_hex(hash)),
+        None => abis.get(&normalize_hex(&contract_address)),
+    };
+    let function = abi.and_then(|abi| abi.function_by_selector(&entry_point_selector));
+    let (decoded_calldata, decoded_result) = match (abi, function) {
+        (Some(abi), Some(function)) => (
+            abi.decode_inputs(function, &calldata).ok(),
+            abi.decode_outputs(function, &result).ok(),
+        ),
+        _ => (None, None),
+    };
+
+    let events = invocation.get("events")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .map(|event| {
+            let keys = felts(event, "keys");
+            let data = felts(event, "data");
+            let (event_name, decoded_data) = rpc.decode_event(abi.map(|abi| abi.as_ref()), &keys, &data);
+            TraceEvent {
+                order: event.get("order").and_then(|v| v.as_u64()),
+                from_address: contract_address.clone(),
+                keys,
+                data,
+                event_name,
+                decoded_data,
+            }
+        })
+        .collect();
+
+    CallNode {
+        caller_address: text(invocation, "caller_address").unwrap_or_default(),
+        class_hash,
+        entry_point: function.map(|f| f.name.clone()),
+        entry_point_type: text(invocation, "entry_point_type"),
+        call_type: text(invocation, "call_type"),
+        decoded_calldata,
+        decoded_result,
+        events,
+        messages: invocation.get("messages")
+            .and_then(|v| v.as_array())
+            .cloned()
+            .unwrap_or_default(),
+        calls: invocation.get("calls")
+            .and_then(|v| v.as_array())
+            .into_iter()
+            .flatten()
+            .map(|call| parse_invocation(call, abis, class_abis, rpc))
+            .collect(),
+        contract_address,
+        entry_point_selector,
+        calldata,
+        result,
+    }
+}
+
+fn text(value: &Value, field: &str) -> Option<String> {
+    value.get(field).and_then(|v| v.as_str()).map(String::from)
+}
+
+fn felts(value: &Value, field: &str) -> Vec<String> {
+    value.get(field)
+        .and_then(|v| v.as_array())
+        .map(|arr| arr.iter().filter_map(|felt| felt.as_str().map(String::from)).collect())
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Serialize)]
+pub struct FeeBreakdown {
+    /// Charged fee from the receipt
+    pub actual_fee: Option<String>,
+    /// `actual_fee` in ETH or STRK
+    pub actual_fee_amount: Option<TokenAmount>,
+    pub unit: FeeUnit,
+    /// `max_fee`, or the resource bound total of a V3 transaction
+    pub fee_cap: String,
+    pub tip: Option<String>,
+    pub resource_bounds: Option<Value>,
+    /// Gas and steps consumed, as reported by the receipt
+    pub execution_resources: Option<Value>,
+}
+
+impl FeeBreakdown {
+    pub fn new(tx: &Value, receipt: &Value) -> Self {
+        let (cap, cap_unit) = fee_cap(tx);
+        let parsed = TransactionReceipt::from_value(receipt);
+        Self {
+            actual_fee: parsed.as_ref().map(|r| format!("{:#x}", r.actual_fee)),
+            actual_fee_amount: parsed.as_ref().map(|r| TokenAmount::fee(r.actual_fee, r.fee_unit, STARKNET_NATIVE_SYMBOL)),
+            unit: parsed.map_or(cap_unit, |r| r.fee_unit),
+            fee_cap: format!("{:#x}", cap),
+            tip: text(tx, "tip"),
+            resource_bounds: tx.get("resource_bounds").cloned(),
+            execution_resources: receipt.get("execution_resources").cloned(),
+        }
+    }
+}
+
+/// A single transaction: receipt outcome, fees, decoded events, L1 messages and call trees
+#[derive(Debug, Serialize)]
+pub struct TransactionDetails {
+    pub transaction_hash: String,
+    pub tx_type: Option<String>,
+    pub version: Option<String>,
+    pub sender_address: Option<String>,
+    pub block_number: Option<u64>,
+    pub finality_status: Option<String>,
+    pub execution_status: Option<String>,
+    pub revert_reason: Option<String>,
+    pub fee: FeeBreakdown,
+    /// Every event of the transaction in emission order
+    pub events: Vec<TraceEvent>,
+    pub messages_to_l1: Vec<Value>,
+    /// Absent when the provider cannot trace the transaction; `trace_error` says why
+    pub trace: Option<TransactionTrace>,
+    pub trace_error: Option<String>,
+}
+
+/// Receipt events, decoded with the ABI of each emitting contract
+pub fn receipt_events(receipt: &Value, abis: &HashMap<String, Arc<ContractAbi>>, rpc: &RpcService) -> Vec<TraceEvent> {
+    receipt.get("events")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .enumerate()
+        .map(|(order, event)| {
+            let from_address = text(event, "from_address").unwrap_or_default();
+            let keys = felts(event, "keys");
+            let data = felts(event, "data");
+            let abi = abis.get(&normalize_hex(&from_address)).map(|abi| abi.as_ref());
+            let (event_name, decoded_data) = rpc.decode_event(abi, &keys, &data);
+            TraceEvent {
+                order: Some(order as u64),
+                from_address,
+                keys,
+                data,
+                event_name,
+                decoded_data,
+            }
+        })
+        .collect()
+}
+
+/// Contracts that emitted events in a receipt
+pub fn emitting_contracts(receipt: &Value) -> Vec<String> {
+    receipt.get("events")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|event| text(event, "from_address"))
+        .map(|address| normalize_hex(&address))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::utils::felt::selector;
+
+    const ACCOUNT: &str = "0xacc";
+    const TOKEN: &str = "0x70c";
+    // Class the token proxy delegates to, not the class at its address
+    const TOKEN_IMPL: &str = "0x1a55";
+    const FEE_TOKEN: &str = "0xfee";
+
+    fn token_abi() -> Arc<ContractAbi> {
+        let abi = json!([
+            {"type": "function", "name": "transfer", "state_mutability": "external", "inputs": [
+                {"name": "recipient", "type": "core::starknet::contract_address::ContractAddress"},
+                {"name": "amount", "type": "core::integer::u256"}], "outputs": [
+                {"type": "core::bool"}]},
+            {"type": "event", "name": "token::Moved", "kind": "struct", "members": [
+                {"name": "to", "type": "core::starknet::contract_address::ContractAddress", "kind": "key"},
+                {"name": "amount", "type": "core::integer::u256", "kind": "data"}]},
+            {"type": "event", "name": "token::Event", "kind": "enum", "variants": [
+                {"name": "Moved", "type": "token::Moved", "kind": "nested"}]}
+        ]);
+        Arc::new(ContractAbi::from_class(&json!({"sierra_program": [], "abi": abi.to_string()})).expect("valid ABI"))
+    }
+
+    // An account's __execute__ library-calling the token implementation, and a fee
+    // transfer reported without class hashes
+    fn trace() -> Value {
+        json!({
+            "type": "INVOKE",
+            "validate_invocation": {
+                "contract_address": ACCOUNT, "caller_address": "0x0", "class_hash": "0xacc1",
+                "entry_point_selector": selector("__validate__"), "calldata": [], "result": [],
+                "calls": [], "events": [], "messages": []
+            },
+            "execute_invocation": {
+                "contract_address": ACCOUNT, "caller_address": "0x0", "class_hash": "0xacc1",
+                "entry_point_selector": selector("__execute__"), "calldata": ["0x1"], "result": [],
+                "events": [], "messages": [],
+                "calls": [{
+                    "contract_address": TOKEN, "caller_address": ACCOUNT, "class_hash": TOKEN_IMPL,
+                    "entry_point_selector": selector("transfer"), "call_type": "LIBRARY_CALL",
+                    "entry_point_type": "EXTERNAL", "calldata": ["0xb0b", "0x5", "0x0"], "result": ["0x1"],
+                    "events": [{"order": 0, "keys": [selector("Moved"), "0xb0b"], "data": ["0x5", "0x0"]}],
+                    "messages": [{"order": 1, "to_address": "0x1111", "payload": ["0x2"]}],
+                    "calls": []
+                }]
+            },
+            "fee_transfer_invocation": {
+                "contract_address": FEE_TOKEN, "caller_address": ACCOUNT,
+                "entry_point_selector": selector("transfer"), "calldata": ["0x5e9", "0x64", "0x0"], "result": ["0x1"],
+                "calls": [], "events": [], "messages": []
+            }
+        })
+    }
+
+    #[test]
+    fn lists_invoked_classes_and_unhashed_contracts() {
+        assert_eq!(invoked_classes(&trace()), vec!["0x1a55", "0xacc1"]);
+        assert_eq!(invoked_contracts(&trace()), vec![FEE_TOKEN]);
+    }
+
+    #[test]
+    fn decodes_calls_with_the_abi_of_the_executed_class() {
+        let class_abis = HashMap::from([(TOKEN_IMPL.to_string(), token_abi())]);
+        let proxy = json!([{"type": "function", "name": "upgrade", "inputs": [{"name": "class_hash", "type": "core::felt252"}], "outputs": []}]);
+        let proxy = ContractAbi::from_class(&json!({"sierra_program": [], "abi": proxy.to_string()})).expect("valid ABI");
+        let abis = HashMap::from([(TOKEN.to_string(), Arc::new(proxy)), (FEE_TOKEN.to_string(), token_abi())]);
+        let parsed = parse_trace(&trace(), &abis, &class_abis, &RpcService::replayed("starknet"));
+
+        assert!(parsed.revert_reason.is_none());
+        assert_eq!(parsed.validate.as_ref().and_then(|call| call.entry_point.as_deref()), None);
+
+        let execute = parsed.execute.expect("execute tree");
+        let transfer = &execute.calls[0];
+        assert_eq!(transfer.entry_point.as_deref(), Some("transfer"));
+        assert_eq!(transfer.call_type.as_deref(), Some("LIBRARY_CALL"));
+        assert_eq!(transfer.decoded_calldata, Some(json!({"recipient": "0xb0b", "amount": "5"})));
+        assert_eq!(transfer.decoded_result, Some(json!(true)));
+        assert_eq!(transfer.messages.len(), 1);
+
+        let event = &transfer.events[0];
+        assert_eq!((event.order, event.from_address.as_str(), event.event_name.as_str()), (Some(0), TOKEN, "Moved"));
+        assert_eq!(event.decoded_data["amount"], "5");
+
+        // No class hash: decoded with the ABI at the contract's address
+        let fee = parsed.fee_transfer.expect("fee transfer");
+        assert_eq!(fee.decoded_calldata, Some(json!({"recipient": "0x5e9", "amount": "100"})));
+    }
+
+    #[test]
+    fn reports_reverted_executions_without_a_call_tree() {
+        let mut trace = trace();
+        trace["execute_invocation"] = json!({"revert_reason": "Insufficient balance"});
+        let parsed = parse_trace(&trace, &HashMap::new(), &HashMap::new(), &RpcService::replayed("starknet"));
+
+        assert_eq!(parsed.revert_reason.as_deref(), Some("Insufficient balance"));
+        assert!(parsed.execute.is_none());
+        assert!(parsed.validate.is_some() && parsed.fee_transfer.is_some());
+    }
+
+    #[test]
+    fn breaks_down_v3_fees() {
+        let tx = json!({"version": "0x3", "tip": "0x0", "resource_bounds": {
+            "l1_gas": {"max_amount": "0x10", "max_price_per_unit": "0x100"},
+            "l2_gas": {"max_amount": "0x0", "max_price_per_unit": "0x0"}}});
+        let receipt = json!({
+            "transaction_hash": "0x1", "actual_fee": {"amount": "0xde0b6b3a7640000", "unit": "FRI"},
+            "execution_status": "SUCCEEDED", "execution_resources": {"steps": 100}
+        });
+        let fee = FeeBreakdown::new(&tx, &receipt);
+
+        assert_eq!((fee.unit, fee.fee_cap.as_str(), fee.tip.as_deref()), (FeeUnit::Fri, "0x1000", Some("0x0")));
+        let amount = fee.actual_fee_amount.expect("parsed receipt");
+        assert_eq!((amount.formatted.as_str(), amount.symbol.as_deref()), ("1", Some("STRK")));
+        assert_eq!(fee.execution_resources, Some(json!({"steps": 100})));
+    }
+
+    #[test]
+    fn numbers_receipt_events_in_emission_order() {
+        let receipt = json!({"events": [
+            {"from_address": "0x0A", "keys": [selector("Moved"), "0xb0b"], "data": ["0x1", "0x0"]},
+            {"from_address": "0xb", "keys": [], "data": []}
+        ]});
+        let abis = HashMap::from([("0xa".to_string(), token_abi())]);
+        let events = receipt_events(&receipt, &abis, &RpcService::replayed("starknet"));
+
+        let found: Vec<(Option<u64>, &str)> = events.iter().map(|event| (event.order, event.event_name.as_str())).collect();
+        assert_eq!(found, vec![(Some(0), "Moved"), (Some(1), "Unknown Event")]);
+        assert_eq!(emitting_contracts(&receipt), vec!["0xa", "0xb"]);
+    }
+}
@@ -0,0 +1,153 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde_json::Value;
+
+use crate::errors::AppError;
+use crate::services::block_index::BlockIndex;
+
+/// A request's window in time as unix seconds, either end open. Every endpoint that
+/// takes dates parses them into one, and it resolves to blocks on any chain.
+///
+/// An end is an ISO 8601 date or date-time (`2024-05-01`, `2024-05-01T12:00:00Z`; UTC
+/// when no offset is given), `now`, or a span back from now such as `7d`, `last 7d` or
+/// `36 hours ago`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TimeRange {
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+}
+
+impl TimeRange {
+    pub fn parse(from: Option<&str>, to: Option<&str>) -> Result<Self, AppError> {
+        let now = Utc::now().timestamp().max(0) as u64;
+        let range = Self {
+            from: from.map(|value| parse_time(value, now)).transpose()?,
+            to: to.map(|value| parse_time(value, now)).transpose()?,
+        };
+
+        if let (Some(from), Some(to)) = (range.from, range.to) {
+            if from > to {
+                return Err(AppError::BadRequest(format!(
+                    "Invalid time range: {} is after {}", format_time(from), format_time(to)
+                )));
+            }
+        }
+        Ok(range)
+    }
+
+    /// `fromDate`/`toDate` of a request
+    pub fn from_payload(payload: &Value) -> Result<Self, AppError> {
+        let field = |name: &str| payload.get(name).and_then(|v| v.as_str());
+        Self::parse(field("fromDate"), field("toDate"))
+    }
+
+    /// Blocks of the window on the chain of `blocks`: the first one at or after `from` and
+    /// the last one before `to`, so back-to-back windows never share a block
+    pub async fn resolve(&self, blocks: &BlockIndex) -> Result<(Option<u64>, Option<u64>), AppError> {
+        let from = match self.from {
+            Some(ts) => Some(blocks.find_block_by_timestamp(ts).await?),
+            None => None,
+        };
+        let to = match self.to {
+            Some(ts) => Some(blocks.find_last_block_before(ts).await?.ok_or_else(|| AppError::BadRequest(format!(
+                "No blocks before {}", format_time(ts)
+            )))?),
+            None => None,
+        };
+
+        if let (Some(from_block), Some(to_block)) = (from, to) {
+            if from_block > to_block {
+                return Err(AppError::BadRequest(format!(
+                    "No blocks between {} and {}",
+                    self.from.map(format_time).unwrap_or_default(),
+                    self.to.map(format_time).unwrap_or_default()
+                )));
+            }
+        }
+        Ok((from, to))
+    }
+}
+
+fn parse_time(value: &str, now: u64) -> Result<u64, AppError> {
+    let value = value.trim();
+    if value.eq_ignore_ascii_case("now") {
+        return Ok(now);
+    }
+    if let Some(span) = parse_span(value) {
+        return Ok(now.saturating_sub(span));
+    }
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Ok(dt.timestamp().max(0) as u64);
+    }
+
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M"))
+        .ok()
+        .or_else(|| NaiveDate::parse_from_str(value, "%Y-%m-%d").ok().and_then(|date| date.and_hms_opt(0, 0, 0)))
+        .map(|dt| dt.and_utc().timestamp().max(0) as u64)
+        .ok_or_else(|| AppError::BadRequest(format!(
+            "Invalid date '{}': expected an ISO 8601 date or a span such as 'last 7d'", value
+        )))
+}
+
+// Seconds in `7d`, `last 7d`, `7 days ago` and the like
+fn parse_span(value: &str) -> Option<u64> {
+    let value = value.to_ascii_lowercase();
+    let span = value.strip_prefix("last").unwrap_or(&value);
+    let span = span.strip_suffix("ago").unwrap_or(span).trim();
+
+    let unit_start = span.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = span.split_at(unit_start);
+    let seconds = match unit.trim() {
+        "s" | "sec" | "secs" | "second" | "seconds" => 1,
+        "m" | "min" | "mins" | "minute" | "minutes" => 60,
+        "h" | "hr" | "hrs" | "hour" | "hours" => 3_600,
+        "d" | "day" | "days" => 86_400,
+        "w" | "week" | "weeks" => 604_800,
+        _ => return None,
+    };
+    amount.parse::<u64>().ok()?.checked_mul(seconds)
+}
+
+fn format_time(ts: u64) -> String {
+    DateTime::from_timestamp(ts as i64, 0)
+        .map(|dt| dt.to_rfc3339())
+        .unwrap_or_else(|| ts.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_dates_and_date_times_as_utc() {
+        let range = TimeRange::parse(Some("2024-05-01"), Some("2024-05-01T12:30:00Z")).unwrap();
+        assert_eq!(range.from, Some(1_714_521_600));
+        assert_eq!(range.to, Some(1_714_566_600));
+
+        let offset = TimeRange::parse(Some("2024-05-01T02:00:00+02:00"), Some("2024-05-01T00:00")).unwrap();
+        assert_eq!(offset.from, Some(1_714_521_600));
+        assert_eq!(offset.to, Some(1_714_521_600));
+    }
+
+    #[test]
+    fn parses_spans_back_from_now() {
+        for span in ["7d", "last 7d", "7 days ago", "1 week ago", "168 hours ago"] {
+            let range = TimeRange::parse(Some(span), Some("now")).unwrap();
+            assert_eq!(range.to.unwrap() - range.from.unwrap(), 7 * 86_400, "{}", span);
+        }
+    }
+
+    #[test]
+    fn leaves_missing_ends_open() {
+        let range = TimeRange::parse(Some("2024-05-01"), None).unwrap();
+        assert_eq!(range.to, None);
+        assert!(TimeRange::parse(None, None).unwrap().from.is_none());
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        assert!(TimeRange::parse(Some("yesterday-ish"), None).is_err());
+        assert!(TimeRange::parse(Some("7 fortnights"), None).is_err());
+        assert!(TimeRange::parse(Some("2024-05-02"), Some("2024-05-01")).is_err());
+    }
+}
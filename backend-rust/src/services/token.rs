@@ -0,0 +1,247 @@
+use num_bigint::BigUint;
+use num_traits::Num;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::db::DbPool;
+use crate::services::chain::{ChainRegistry, STARKNET};
+use crate::services::rpc::EventData;
+use crate::services::starknet_receipt::FeeUnit;
+use crate::utils::felt::normalize_hex;
+
+/// Decimals of every fee currency: ETH and STRK on Starknet, the native coin of EVM chains
+pub const FEE_DECIMALS: u8 = 18;
+
+// How long a contract that resolved to nothing is left alone before being asked again
+const UNRESOLVED_TTL: Duration = Duration::from_secs(600);
+
+// Events whose amount is a quantity of the emitting token
+const AMOUNT_EVENTS: [&str; 2] = ["Transfer", "Approval"];
+const AMOUNT_FIELDS: [&str; 2] = ["amount", "value"];
+
+/// An integer amount of a token, exact and scaled by its decimals
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenAmount {
+    /// In the token's smallest unit
+    pub raw: String,
+    /// `raw` divided by 10^decimals, without rounding
+    pub formatted: String,
+    pub decimals: u8,
+    pub symbol: Option<String>,
+}
+
+impl TokenAmount {
+    pub fn new(raw: &BigUint, decimals: u8, symbol: Option<&str>) -> Self {
+        Self {
+            raw: raw.to_string(),
+            formatted: format_units(raw, decimals),
+            decimals,
+            symbol: symbol.map(String::from),
+        }
+    }
+
+    /// A fee in `unit`, with `native_symbol` naming the chain's WEI-denominated coin
+    pub fn fee(raw: u128, unit: FeeUnit, native_symbol: &str) -> Self {
+        let symbol = match unit {
+            FeeUnit::Wei => native_symbol,
+            FeeUnit::Fri => "STRK",
+        };
+        Self::new(&BigUint::from(raw), FEE_DECIMALS, Some(symbol))
+    }
+}
+
+/// `raw / 10^decimals` as a decimal string, trailing zeros trimmed
+pub fn format_units(raw: &BigUint, decimals: u8) -> String {
+    let digits = raw.to_string();
+    let decimals = decimals as usize;
+    if decimals == 0 {
+        return digits;
+    }
+
+    let padded = format!("{:0>width$}", digits, width = decimals + 1);
+    let (whole, fraction) = padded.split_at(padded.len() - decimals);
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() {
+        whole.to_string()
+    } else {
+        format!("{}.{}", whole, fraction)
+    }
+}
+
+/// What a token contract reports about itself. Fields it does not expose are `None`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenMetadata {
+    pub chain: String,
+    pub address: String,
+    pub name: Option<String>,
+    pub symbol: Option<String>,
+    pub decimals: Option<u8>,
+}
+
+impl TokenMetadata {
+    fn resolved(&self) -> bool {
+        self.symbol.is_some() || self.decimals.is_some()
+    }
+}
+
+// Lookups by `chain:address`, with when each was made
+type KnownTokens = HashMap<String, (Option<TokenMetadata>, Instant)>;
+
+/// Token metadata of every chain, resolved once through contract calls and kept in
+/// SQLite. Contracts that answer none of the calls are retried after a while rather
+/// than stored, since the failure may have been the RPC's.
+#[derive(Clone)]
+pub struct TokenService {
+    pool: DbPool,
+    chains: ChainRegistry,
+    known: Arc<Mutex<KnownTokens>>,
+}
+
+impl TokenService {
+    pub fn new(pool: DbPool, chains: ChainRegistry) -> Self {
+        Self {
+            pool,
+            chains,
+            known: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub async fn metadata(&self, chain: &str, address: &str) -> Option<TokenMetadata> {
+        let chain = chain.to_lowercase();
+        let address = if chain == STARKNET { normalize_hex(address) } else { address.to_lowercase() };
+        let key = format!("{}:{}", chain, address);
+
+        if let Some((metadata, checked_at)) = self.known.lock().unwrap().get(&key) {
+            if metadata.is_some() || checked_at.elapsed() < UNRESOLVED_TTL {
+                return metadata.clone();
+            }
+        }
+
+        let stored: Option<(Option<String>, Option<String>, Option<i64>)> = sqlx::query_as(
+            "SELECT name, symbol, decimals FROM token_metadata WHERE chain = ? AND address = ?"
+        )
+            .bind(&chain)
+            .bind(&address)
+            .fetch_optional(&self.pool)
+            .await
+            .unwrap_or_else(|e| {
+                log::warn!("Token metadata lookup failed: {}", e);
+                None
+            });
+
+        let metadata = match stored {
+            Some((name, symbol, decimals)) => Some(TokenMetadata {
+                chain: chain.clone(),
+                address: address.clone(),
+                name,
+                symbol,
+                decimals: decimals.and_then(|d| u8::try_from(d).ok()),
+            }),
+            None => self.resolve(&chain, &address).await,
+        };
+
+        self.known.lock().unwrap().insert(key, (metadata.clone(), Instant::now()));
+        metadata
+    }
+
+    /// `raw` units of a token, scaled when its decimals are known
+    pub async fn amount(&self, chain: &str, address: &str, raw: &BigUint) -> Option<TokenAmount> {
+        let metadata = self.metadata(chain, address).await?;
+        Some(TokenAmount::new(raw, metadata.decimals?, metadata.symbol.as_deref()))
+    }
+
+    /// Adds a `token_amount` to a decoded Transfer or Approval emitted by `token`
+    pub async fn annotate(&self, chain: &str, token: &str, event_name: &str, decoded: &mut Value) {
+        if !AMOUNT_EVENTS.contains(&event_name) {
+            return;
+        }
+        let Some(fields) = decoded.as_object_mut() else {
+            return;
+        };
+        let Some(raw) = AMOUNT_FIELDS.iter().find_map(|name| fields.get(*name).and_then(parse_amount)) else {
+            return;
+        };
+
+        if let Some(amount) = self.amount(chain, token, &raw).await {
+            fields.insert("token_amount".to_string(), serde_json::to_value(amount).unwrap_or_default());
+        }
+    }
+
+    /// `annotate` over events all emitted by `token`
+    pub async fn annotate_events(&self, chain: &str, token: &str, events: &mut [EventData]) {
+        for event in events {
+            self.annotate(chain, token, &event.event_name, &mut event.decoded_data).await;
+        }
+    }
+
+    async fn resolve(&self, chain: &str, address: &str) -> Option<TokenMetadata> {
+        let client = self.chains.get(Some(chain)).ok()?;
+        let metadata = match client.token_metadata(address).await {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                log::debug!("Token metadata of {} on {} unavailable: {}", address, chain, e);
+                return None;
+            }
+        };
+        if !metadata.resolved() {
+            return None;
+        }
+
+        let stored = sqlx::query(
+            "INSERT OR REPLACE INTO token_metadata (chain, address, name, symbol, decimals) VALUES (?, ?, ?, ?, ?)"
+        )
+            .bind(chain)
+            .bind(address)
+            .bind(&metadata.name)
+            .bind(&metadata.symbol)
+            .bind(metadata.decimals.map(i64::from))
+            .execute(&self.pool)
+            .await;
+        if let Err(e) = stored {
+            log::warn!("Failed to store token metadata of {}: {}", address, e);
+        }
+
+        Some(TokenMetadata {
+            chain: chain.to_string(),
+            address: address.to_string(),
+            ..metadata
+        })
+    }
+}
+
+/// An integer as decoders render it: a decimal string, or a number when small; hex is accepted too
+pub fn parse_amount(value: &Value) -> Option<BigUint> {
+    match value {
+        Value::Number(n) => n.as_u64().map(BigUint::from),
+        Value::String(text) => match text.strip_prefix("0x") {
+            Some(hex) => BigUint::from_str_radix(hex, 16).ok(),
+            None => BigUint::from_str_radix(text, 10).ok(),
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_units_without_rounding() {
+        let units = |raw: u128, decimals| format_units(&BigUint::from(raw), decimals);
+        assert_eq!(units(1_500_000_000_000_000_000, 18), "1.5");
+        assert_eq!(units(1, 18), "0.000000000000000001");
+        assert_eq!(units(0, 18), "0");
+        assert_eq!(units(123_456_789, 6), "123.456789");
+        assert_eq!(units(2_000_000, 6), "2");
+        assert_eq!(units(42, 0), "42");
+    }
+
+    #[test]
+    fn formats_values_beyond_u128() {
+        let raw = BigUint::from(u128::MAX) * BigUint::from(1_000u32);
+        assert_eq!(format_units(&raw, 3), u128::MAX.to_string());
+    }
+}
@@ -0,0 +1,145 @@
+use num_bigint::{BigInt, BigUint};
+use num_traits::{Num, Zero};
+use sha3::{Digest, Keccak256};
+use starknet_crypto::{pedersen_hash, FieldElement};
+use std::sync::LazyLock;
+
+/// The Stark field prime, 2^251 + 17 * 2^192 + 1
+pub static FIELD_PRIME: LazyLock<BigUint> = LazyLock::new(|| {
+    BigUint::from_str_radix("800000000000011000000000000000000000000000000000000000000000001", 16)
+        .expect("valid field prime")
+});
+
+/// Parses a felt given as `0x`-prefixed hex or as a decimal string
+pub fn parse_felt(value: &str) -> Option<BigUint> {
+    let value = value.trim();
+    match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+        Some("") => Some(BigUint::zero()),
+        Some(hex) => BigUint::from_str_radix(hex, 16).ok(),
+        None => BigUint::from_str_radix(value, 10).ok(),
+    }
+}
+
+pub fn felt_to_hex(value: &BigUint) -> String {
+    format!("0x{:x}", value)
+}
+
+/// Canonical form of a hex felt (lowercase, no leading zeros) so padded and unpadded
+/// addresses or selectors compare equal. Unparseable input is returned lowercased.
+pub fn normalize_hex(value: &str) -> String {
+    parse_felt(value)
+        .map(|v| felt_to_hex(&v))
+        .unwrap_or_else(|| value.to_lowercase())
+}
+
+/// Starknet's keccak: keccak256 truncated to the low 250 bits
+pub fn sn_keccak(data: &[u8]) -> BigUint {
+    let mut hash: [u8; 32] = Keccak256::digest(data).into();
+    hash[0] &= 0x03;
+    BigUint::from_bytes_be(&hash)
+}
+
+/// Selector of an entry point, event or storage variable name, as a hex felt
+pub fn selector(name: &str) -> String {
+    felt_to_hex(&sn_keccak(name.as_bytes()))
+}
+
+pub fn u256_from_parts(low: &BigUint, high: &BigUint) -> BigUint {
+    (high << 128u32) + low
+}
+
+/// Interprets a felt as a signed integer: values above P/2 are negative
+pub fn felt_to_signed(value: &BigUint) -> BigInt {
+    let half: BigUint = &*FIELD_PRIME >> 1u32;
+    if value > &half {
+        BigInt::from(value.clone()) - BigInt::from(FIELD_PRIME.clone())
+    } else {
+        BigInt::from(value.clone())
+    }
+}
+
+/// Inverse of `felt_to_signed`: negative values wrap around the field prime
+pub fn signed_to_felt(value: &BigInt) -> BigUint {
+    let prime = BigInt::from(FIELD_PRIME.clone());
+    let wrapped = ((value % &prime) + &prime) % &prime;
+    wrapped.to_biguint().expect("non-negative after wrapping")
+}
+
+/// Encodes a Cairo short string (at most 31 ASCII characters) as a felt
+pub fn short_string_to_felt(value: &str) -> Option<BigUint> {
+    (value.is_ascii() && value.len() <= 31).then(|| BigUint::from_bytes_be(value.as_bytes()))
+}
+
+/// Decodes a felt holding a Cairo short string; `None` when the bytes are not printable text
+pub fn felt_to_short_string(value: &BigUint) -> Option<String> {
+    let bytes = value.to_bytes_be();
+    let text = String::from_utf8(bytes.into_iter().skip_while(|b| *b == 0).collect()).ok()?;
+    (!text.is_empty() && !text.chars().any(char::is_control)).then_some(text)
+}
+
+/// Storage addresses are reduced below 2^251 - 256
+pub static ADDR_BOUND: LazyLock<BigUint> = LazyLock::new(|| (BigUint::from(1u8) << 251u32) - BigUint::from(256u32));
+
+fn to_field_element(value: &BigUint) -> FieldElement {
+    let bytes = (value % &*FIELD_PRIME).to_bytes_be();
+    let mut padded = [0u8; 32];
+    padded[32 - bytes.len()..].copy_from_slice(&bytes);
+    FieldElement::from_bytes_be(&padded).expect("reduced below the field prime")
+}
+
+pub fn pedersen(a: &BigUint, b: &BigUint) -> BigUint {
+    let hash = pedersen_hash(&to_field_element(a), &to_field_element(b));
+    BigUint::from_bytes_be(&hash.to_bytes_be())
+}
+
+/// Address of a storage variable the way Cairo derives it: sn_keccak of the variable name,
+/// folded with Pedersen over each felt of the mapping keys, reduced below `ADDR_BOUND`
+pub fn storage_address(variable: &str, keys: &[BigUint]) -> BigUint {
+    let base = sn_keccak(variable.as_bytes());
+    let address = keys.iter().fold(base, |acc, key| pedersen(&acc, key));
+    address % &*ADDR_BOUND
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selector_is_truncated_keccak() {
+        assert_eq!(selector("Transfer"), "0x99cd8bde557814842a3121e8ddfd433a539b8c9f14bf31ebf108d12e6196e9");
+        assert_eq!(selector("transfer"), "0x83afd3f4caedc6eebf44246fe54e38c95e3179a5ec9ea81740eca5b482d12e");
+    }
+
+    #[test]
+    fn pedersen_matches_reference_vector() {
+        let a = parse_felt("0x3d937c035c878245caf64531a5756109c53068da139362728feb561405371cb").unwrap();
+        let b = parse_felt("0x208a0a10250e382e1e4bbe2880906c2791bf6275695e02fbbc6aeff9cd8b31a").unwrap();
+        assert_eq!(
+            felt_to_hex(&pedersen(&a, &b)),
+            "0x30e480bed5fe53fa909cc0f8c4d99b8f9f2c016be4c41e13a4848797979c662"
+        );
+    }
+
+    #[test]
+    fn storage_address_of_plain_variable_is_its_selector() {
+        assert_eq!(felt_to_hex(&storage_address("balance", &[])), selector("balance"));
+        assert_eq!(
+            felt_to_hex(&storage_address("balance", &[])),
+            "0x206f38f7e4f15e87567361213c28f235cccdaa1d7fd34c9db1dfe9489c6a091"
+        );
+    }
+
+    #[test]
+    fn storage_address_folds_mapping_keys() {
+        let account = parse_felt("0x49d36570d4e46f48e99674bd3fcc84644ddd6b96f7c741b1562b82f9e004dc7").unwrap();
+        let low = BigUint::from(7u8);
+        let base = sn_keccak(b"ERC20_balances");
+
+        let single = storage_address("ERC20_balances", std::slice::from_ref(&account));
+        assert_eq!(single, pedersen(&base, &account) % &*ADDR_BOUND);
+
+        let nested = storage_address("ERC20_balances", &[account.clone(), low.clone()]);
+        assert_eq!(nested, pedersen(&pedersen(&base, &account), &low) % &*ADDR_BOUND);
+        assert!(nested < *ADDR_BOUND);
+    }
+}